@@ -204,13 +204,25 @@ pub trait Backend: BackendTypes + TypeMethods {
 
     fn set_is_dumping(&mut self, yes: bool);
     fn set_debug_assertions(&mut self, yes: bool);
+    /// Sets the source file that debug locations of the next built functions refer to.
+    ///
+    /// Disables debug info if `None`.
+    fn set_debug_file(&mut self, path: Option<&Path>);
     fn opt_level(&self) -> OptimizationLevel;
     fn set_opt_level(&mut self, level: OptimizationLevel);
+    /// Overrides the optimization level of a single function.
+    fn set_function_opt_level(&mut self, id: Self::FuncId, level: OptimizationLevel);
     fn dump_ir(&mut self, path: &Path) -> Result<()>;
     fn dump_disasm(&mut self, path: &Path) -> Result<()>;
 
     fn is_aot(&self) -> bool;
 
+    /// Returns a description of the backend settings that affect code generation and are not part
+    /// of the compiler configuration, such as the target and the pass pipeline.
+    ///
+    /// This is used in cache keys.
+    fn codegen_settings(&self) -> String;
+
     fn function_name_is_unique(&self, name: &str) -> bool;
 
     fn build_function(
@@ -224,7 +236,14 @@ pub trait Backend: BackendTypes + TypeMethods {
     fn verify_module(&mut self) -> Result<()>;
     fn optimize_module(&mut self) -> Result<()>;
     fn write_object<W: std::io::Write>(&mut self, w: W) -> Result<()>;
+    /// Sets whether to register JIT-compiled code with the GDB JIT compilation interface.
+    fn set_gdb_jit_registration(&mut self, yes: bool);
     fn jit_function(&mut self, id: Self::FuncId) -> Result<usize>;
+    /// Returns the size of the machine code of the given JIT-compiled function, if known.
+    ///
+    /// This must be the size of the code loaded at the address returned by `jit_function`, as it
+    /// is used to read that code.
+    fn function_size(&mut self, id: Self::FuncId) -> Result<Option<usize>>;
     unsafe fn free_function(&mut self, id: Self::FuncId) -> Result<()>;
     unsafe fn free_all_functions(&mut self) -> Result<()>;
 }
@@ -249,6 +268,13 @@ pub trait Builder: BackendTypes + TypeMethods {
 
     fn add_comment_to_current_inst(&mut self, comment: &str);
 
+    /// Sets the source location of the following instructions.
+    ///
+    /// Ignored if debug info is disabled.
+    fn set_debug_location(&mut self, line: u32, column: u32);
+    /// Clears the source location of the following instructions.
+    fn clear_debug_location(&mut self);
+
     fn fn_param(&mut self, index: usize) -> Self::Value;
     fn num_fn_params(&self) -> usize;
 
@@ -275,10 +301,16 @@ pub trait Builder: BackendTypes + TypeMethods {
         self.load_unaligned(ty, ptr, name)
     }
     fn load_unaligned(&mut self, ty: Self::Type, ptr: Self::Value, name: &str) -> Self::Value;
+    /// Loads an integer with a relaxed (monotonic) atomic ordering.
+    ///
+    /// Used for values that may be modified concurrently by other threads.
+    fn load_atomic(&mut self, ty: Self::Type, ptr: Self::Value, name: &str) -> Self::Value;
     fn store(&mut self, value: Self::Value, ptr: Self::Value) {
         self.store_unaligned(value, ptr);
     }
     fn store_unaligned(&mut self, value: Self::Value, ptr: Self::Value);
+    /// Stores an integer with a relaxed (monotonic) atomic ordering.
+    fn store_atomic(&mut self, value: Self::Value, ptr: Self::Value);
 
     fn nop(&mut self);
     fn ret(&mut self, values: &[Self::Value]);
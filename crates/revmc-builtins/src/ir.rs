@@ -102,6 +102,9 @@ macro_rules! builtins {
         impl Builtin {
             pub const COUNT: usize = builtins!(@count $($ident),*);
 
+            /// All builtins.
+            pub const ALL: [Self; Self::COUNT] = [$(Self::$ident,)*];
+
             pub const fn name(self) -> &'static str {
                 match self {
                     $(Self::$ident => stringify!($name),)*
@@ -0,0 +1,498 @@
+//! A minimal implementation of LLVM's `FileCheck`.
+//!
+//! Supports the `CHECK`, `CHECK-NEXT`, `CHECK-NOT` and `CHECK-LABEL` directives, `{{regex}}`
+//! blocks, and `[[VAR:regex]]` variable definitions and `[[VAR]]` uses. Other directives are
+//! rejected.
+//!
+//! See <https://llvm.org/docs/CommandGuide/FileCheck.html> for the full semantics.
+
+use fancy_regex::Regex;
+use std::{collections::HashMap, fmt, ops::Range};
+
+const PREFIX: &str = "CHECK";
+
+/// Checks `input` against the directives contained in `checks`.
+pub(crate) fn check(checks: &str, input: &str) -> Result<(), String> {
+    let directives = parse_directives(checks)?;
+    if directives.is_empty() {
+        return Err(format!("no {PREFIX} directives found"));
+    }
+    Checker::new(input).run(&directives)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Kind {
+    Check,
+    Next,
+    Not,
+    Label,
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(PREFIX)?;
+        f.write_str(match self {
+            Self::Check => "",
+            Self::Next => "-NEXT",
+            Self::Not => "-NOT",
+            Self::Label => "-LABEL",
+        })
+    }
+}
+
+#[derive(Debug)]
+struct Directive {
+    kind: Kind,
+    parts: Vec<Part>,
+    /// The pattern, as written.
+    text: String,
+    /// The 1-based line number in the check file.
+    line: usize,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Part {
+    /// A literal string. Horizontal whitespace matches any non-empty horizontal whitespace.
+    Literal(String),
+    /// `{{regex}}`.
+    Regex(String),
+    /// `[[VAR]]`.
+    Use(String),
+    /// `[[VAR:regex]]`.
+    Def(String, String),
+}
+
+fn parse_directives(s: &str) -> Result<Vec<Directive>, String> {
+    let mut directives = Vec::new();
+    for (i, line) in s.lines().enumerate() {
+        let line_number = i + 1;
+        let directive = parse_directive(line).map_err(|e| format!("{line_number}: error: {e}"))?;
+        let Some((kind, text)) = directive else { continue };
+        let text = text.trim().to_string();
+        let error = |msg: String| format!("{line_number}: error: {kind}: {msg}");
+        if text.is_empty() {
+            return Err(error("found empty check string".into()));
+        }
+        let parts = parse_pattern(&text).map_err(error)?;
+        if kind != Kind::Check && kind != Kind::Next && has_def(&parts) {
+            return Err(error("variables cannot be defined in this directive".into()));
+        }
+        if kind == Kind::Next && directives.is_empty() {
+            return Err(error("found on the first line of checks".into()));
+        }
+        directives.push(Directive { kind, parts, text, line: line_number });
+    }
+    Ok(directives)
+}
+
+fn parse_directive(line: &str) -> Result<Option<(Kind, &str)>, String> {
+    for (idx, _) in line.match_indices(PREFIX) {
+        // The prefix must not be part of a larger word.
+        if line[..idx].chars().next_back().is_some_and(|c| c.is_alphanumeric() || "_-".contains(c))
+        {
+            continue;
+        }
+        let rest = &line[idx + PREFIX.len()..];
+        let kinds = [
+            (":", Kind::Check),
+            ("-NEXT:", Kind::Next),
+            ("-NOT:", Kind::Not),
+            ("-LABEL:", Kind::Label),
+        ];
+        for (suffix, kind) in kinds {
+            if let Some(rest) = rest.strip_prefix(suffix) {
+                return Ok(Some((kind, rest)));
+            }
+        }
+        // Reject other directives, like `CHECK-SAME` or `CHECK-COUNT-2`, instead of silently
+        // ignoring them.
+        if let Some(suffix) = rest.strip_prefix('-') {
+            let name_len = suffix
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-'))
+                .unwrap_or(suffix.len());
+            if name_len > 0 && suffix[name_len..].starts_with(':') {
+                return Err(format!("unsupported directive `{PREFIX}-{}`", &suffix[..name_len]));
+            }
+        }
+    }
+    Ok(None)
+}
+
+fn parse_pattern(mut s: &str) -> Result<Vec<Part>, String> {
+    let mut parts = Vec::new();
+    while !s.is_empty() {
+        let next = [s.find("{{"), s.find("[[")].into_iter().flatten().min();
+        let Some(idx) = next else {
+            parts.push(Part::Literal(s.to_string()));
+            break;
+        };
+        if idx > 0 {
+            parts.push(Part::Literal(s[..idx].to_string()));
+        }
+        let rest = &s[idx + 2..];
+        if s[idx..].starts_with("{{") {
+            let end = rest.find("}}").ok_or("unterminated regex block `{{`")?;
+            let regex = &rest[..end];
+            if regex.is_empty() {
+                return Err("found empty regex block `{{}}`".into());
+            }
+            parts.push(Part::Regex(regex.to_string()));
+            s = &rest[end + 2..];
+        } else {
+            let end = find_var_end(rest).ok_or("unterminated variable block `[[`")?;
+            let var = &rest[..end];
+            let part = match var.split_once(':') {
+                Some((name, regex)) => {
+                    validate_var_name(name)?;
+                    if regex.is_empty() {
+                        return Err(format!("empty regex for variable `{name}`"));
+                    }
+                    Part::Def(name.to_string(), regex.to_string())
+                }
+                None => {
+                    validate_var_name(var)?;
+                    Part::Use(var.to_string())
+                }
+            };
+            parts.push(part);
+            s = &rest[end + 2..];
+        }
+    }
+    Ok(parts)
+}
+
+/// Finds the closing `]]` of a variable block, skipping over character classes in the regex.
+fn find_var_end(s: &str) -> Option<usize> {
+    let bytes = s.as_bytes();
+    let mut depth = 0usize;
+    for (i, &b) in bytes.iter().enumerate() {
+        match b {
+            b'[' => depth += 1,
+            b']' if depth > 0 => depth -= 1,
+            b']' if bytes.get(i + 1) == Some(&b']') => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn validate_var_name(name: &str) -> Result<(), String> {
+    let mut chars = name.chars();
+    let valid = chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("invalid variable name: `{name}`"))
+    }
+}
+
+fn has_def(parts: &[Part]) -> bool {
+    parts.iter().any(|part| matches!(part, Part::Def(..)))
+}
+
+/// A match of a directive in the input.
+struct Match {
+    range: Range<usize>,
+    defs: Vec<(String, String)>,
+}
+
+struct Checker<'a> {
+    input: &'a str,
+    /// The byte offsets at which each line of the input starts.
+    line_starts: Vec<usize>,
+    vars: HashMap<String, String>,
+}
+
+impl<'a> Checker<'a> {
+    fn new(input: &'a str) -> Self {
+        let line_starts =
+            std::iter::once(0).chain(input.match_indices('\n').map(|(i, _)| i + 1)).collect();
+        Self { input, line_starts, vars: HashMap::new() }
+    }
+
+    fn run(&mut self, directives: &[Directive]) -> Result<(), String> {
+        // Find all the labels first, which split the input into blocks that are checked
+        // independently.
+        let mut labels = Vec::new();
+        let mut pos = 0;
+        for (i, directive) in directives.iter().enumerate() {
+            if directive.kind == Kind::Label {
+                let m = self.find_or_err(directive, pos, self.input.len())?;
+                pos = m.range.end;
+                labels.push((i, m.range));
+            }
+        }
+
+        let mut first = 0;
+        let mut start = 0;
+        let mut prev_line = None;
+        for i in 0..=labels.len() {
+            let (last, end) = match labels.get(i) {
+                Some((last, range)) => (*last, range.start),
+                None => (directives.len(), self.input.len()),
+            };
+            self.check_block(&directives[first..last], start, end, prev_line)?;
+            if let Some((last, range)) = labels.get(i) {
+                first = last + 1;
+                start = range.end;
+                prev_line = Some(self.line_of(range.start));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the directives in the `start..end` range of the input.
+    fn check_block(
+        &mut self,
+        directives: &[Directive],
+        mut pos: usize,
+        end: usize,
+        mut prev_line: Option<usize>,
+    ) -> Result<(), String> {
+        let mut nots = Vec::new();
+        for directive in directives {
+            if directive.kind == Kind::Not {
+                nots.push(directive);
+                continue;
+            }
+
+            let m = self.find_or_err(directive, pos, end)?;
+            let line = self.line_of(m.range.start);
+            if directive.kind == Kind::Next && prev_line.map(|l| l + 1) != Some(line) {
+                let msg = if prev_line == Some(line) {
+                    "is on the same line as the previous match"
+                } else {
+                    "is not on the line after the previous match"
+                };
+                return Err(self.error(directive, msg, "match", m.range.start));
+            }
+            self.check_nots(&nots, pos..m.range.start)?;
+            nots.clear();
+
+            self.vars.extend(m.defs);
+            pos = m.range.end;
+            prev_line = Some(line);
+        }
+        self.check_nots(&nots, pos..end)
+    }
+
+    fn check_nots(&self, nots: &[&Directive], range: Range<usize>) -> Result<(), String> {
+        for directive in nots {
+            if let Some(m) = self.find(directive, range.start, range.end)? {
+                return Err(self.error(
+                    directive,
+                    "excluded string found in input",
+                    "found here",
+                    m.range.start,
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn find_or_err(
+        &self,
+        directive: &Directive,
+        start: usize,
+        end: usize,
+    ) -> Result<Match, String> {
+        self.find(directive, start, end)?.ok_or_else(|| {
+            self.error(directive, "expected string not found in input", "scanning from here", start)
+        })
+    }
+
+    /// Finds the first match of the directive in the `start..end` range of the input.
+    fn find(
+        &self,
+        directive: &Directive,
+        start: usize,
+        end: usize,
+    ) -> Result<Option<Match>, String> {
+        let regex = self.build_regex(directive)?;
+        let captures = regex.captures_from_pos(&self.input[..end], start).map_err(|e| {
+            format!("{}: error: {}: failed to match pattern: {e}", directive.line, directive.kind)
+        })?;
+        let Some(captures) = captures else { return Ok(None) };
+        let defs = directive
+            .parts
+            .iter()
+            .filter_map(|part| match part {
+                Part::Def(name, _) => Some((name.clone(), captures[name.as_str()].to_string())),
+                _ => None,
+            })
+            .collect();
+        Ok(Some(Match { range: captures.get(0).unwrap().range(), defs }))
+    }
+
+    fn build_regex(&self, directive: &Directive) -> Result<Regex, String> {
+        let mut pattern = String::new();
+        for (i, part) in directive.parts.iter().enumerate() {
+            match part {
+                Part::Literal(s) => push_literal(&mut pattern, s),
+                Part::Regex(r) => {
+                    pattern.push_str("(?:");
+                    pattern.push_str(r);
+                    pattern.push(')');
+                }
+                // Variables defined earlier in the same directive refer to the value matched there.
+                Part::Use(name)
+                    if directive.parts[..i]
+                        .iter()
+                        .any(|part| matches!(part, Part::Def(def, _) if def == name)) =>
+                {
+                    pattern.push_str("\\k<");
+                    pattern.push_str(name);
+                    pattern.push('>');
+                }
+                Part::Use(name) => {
+                    let value = self.vars.get(name).ok_or_else(|| {
+                        format!(
+                            "{}: error: {}: undefined variable: `{name}`",
+                            directive.line, directive.kind
+                        )
+                    })?;
+                    pattern.push_str(&fancy_regex::escape(value));
+                }
+                Part::Def(name, r) => {
+                    pattern.push_str("(?P<");
+                    pattern.push_str(name);
+                    pattern.push('>');
+                    pattern.push_str(r);
+                    pattern.push(')');
+                }
+            }
+        }
+        Regex::new(&pattern).map_err(|e| {
+            format!("{}: error: {}: invalid pattern: {e}", directive.line, directive.kind)
+        })
+    }
+
+    /// Returns the 0-based line number of the given byte offset in the input.
+    fn line_of(&self, offset: usize) -> usize {
+        self.line_starts.partition_point(|&start| start <= offset) - 1
+    }
+
+    fn error(&self, directive: &Directive, msg: &str, note: &str, offset: usize) -> String {
+        let line = self.line_of(offset);
+        let line_start = self.line_starts[line];
+        let line_end =
+            self.input[line_start..].find('\n').map_or(self.input.len(), |i| line_start + i);
+        let column = offset - line_start;
+        format!(
+            "{}: error: {kind}: {msg}\n\
+             {kind}: {}\n\
+             <input>:{}:{}: note: {note}:\n\
+             {}\n\
+             {:>column$}^",
+            directive.line,
+            directive.text,
+            line + 1,
+            column + 1,
+            &self.input[line_start..line_end],
+            "",
+            kind = directive.kind,
+        )
+    }
+}
+
+/// Pushes the escaped literal to the pattern, allowing any horizontal whitespace in place of
+/// whitespace.
+fn push_literal(pattern: &mut String, s: &str) {
+    let mut in_whitespace = false;
+    for c in s.chars() {
+        if c == ' ' || c == '\t' {
+            if !in_whitespace {
+                pattern.push_str("[ \\t]+");
+            }
+            in_whitespace = true;
+        } else {
+            pattern.push_str(&fancy_regex::escape(c.encode_utf8(&mut [0; 4])));
+            in_whitespace = false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INPUT: &str = "\
+define i8 @test(ptr %arg) {
+entry:
+  %value = load i256, ptr %arg
+  %cmp = icmp ne i256 %value, 0
+  %ext = zext i1 %cmp to i256
+  store i256 %ext, ptr %arg
+  ret i8 0
+}
+
+define void @other() {
+entry:
+  ret void
+}
+";
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            parse_pattern("load [[V:%[a-z]+]], {{.*}} [[V]]").unwrap(),
+            [
+                Part::Literal("load ".into()),
+                Part::Def("V".into(), "%[a-z]+".into()),
+                Part::Literal(", ".into()),
+                Part::Regex(".*".into()),
+                Part::Literal(" ".into()),
+                Part::Use("V".into()),
+            ]
+        );
+        assert!(parse_pattern("[[1X]]").is_err());
+        assert!(parse_pattern("{{.*").is_err());
+        assert_eq!(parse_directive("; CHECK-NEXT: a"), Ok(Some((Kind::Next, " a"))));
+        assert_eq!(parse_directive("; XCHECK: a"), Ok(None));
+        assert_eq!(parse_directive("; CHECK-NEXTLINE a"), Ok(None));
+        for unsupported in ["CHECK-SAME", "CHECK-DAG", "CHECK-EMPTY", "CHECK-COUNT-2"] {
+            let line = format!("; {unsupported}: a");
+            assert_eq!(
+                parse_directive(&line),
+                Err(format!("unsupported directive `{unsupported}`")),
+                "{line:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn matches() {
+        let ok = [
+            "; CHECK: load i256",
+            "; CHECK: [[V:%.*]] = load\n; CHECK-NEXT: icmp ne i256 [[V]], 0",
+            "; CHECK: load\n; CHECK-NOT: and\n; CHECK: store",
+            "; CHECK-LABEL: @test\n; CHECK: ret i8\n; CHECK-LABEL: @other\n; CHECK: ret void",
+            "; CHECK: zext   i1",
+            "; CHECK: ret i8 {{[0-9]+}}",
+            "; CHECK: store i256 [[V:%[a-z]+]], ptr [[P:%[a-z]+]]\n; CHECK: ret",
+            "; CHECK: [[V:%[a-z]+]] = zext i1 {{.*}}\n; CHECK-NEXT: store i256 [[V]]",
+            // Uses in the defining directive match the value defined there.
+            "; CHECK: @test(ptr [[P:%[a-z]+]]){{(.|\\n)*}}store i256 %ext, ptr [[P]]",
+        ];
+        for checks in ok {
+            check(checks, INPUT).unwrap_or_else(|e| panic!("{checks:?}:\n{e}"));
+        }
+
+        let err = [
+            "",
+            "; CHECK: and",
+            "; CHECK: load\n; CHECK-NEXT: zext",
+            "; CHECK: load\n; CHECK-NOT: store\n; CHECK: ret",
+            "; CHECK: ret i8\n; CHECK: load",
+            "; CHECK-LABEL: @test\n; CHECK: ret void\n; CHECK-LABEL: @other",
+            "; CHECK: [[UNDEFINED]]",
+            "; CHECK-NEXT: load",
+            "; CHECK-SAME: load",
+            "; CHECK: [[V:%[a-z]+]] = {{.*}} [[V]]",
+        ];
+        for checks in err {
+            assert!(check(checks, INPUT).is_err(), "{checks:?}");
+        }
+    }
+}
@@ -3,6 +3,7 @@
 use tester as test;
 
 use std::{
+    ffi::OsStr,
     fs,
     path::{Path, PathBuf},
     process::Command,
@@ -11,6 +12,9 @@ use std::{
 use test::{ShouldPanic, TestDesc, TestDescAndFn, TestFn, TestName, TestType};
 use walkdir::{DirEntry, WalkDir};
 
+mod filecheck;
+mod snapshot;
+
 /// Run all tests with the given command.
 pub fn run_tests(cmd: &'static Path) -> i32 {
     let args = std::env::args().collect::<Vec<_>>();
@@ -64,20 +68,35 @@ fn make_tests(cmd: &'static Path, tests: &mut Vec<TestDescAndFn>) {
 
     let codegen = config.root.join("tests/codegen");
     for entry in collect_tests(&codegen) {
-        let config = config.clone();
         let path = entry.path().to_path_buf();
         let stripped = path.strip_prefix(config.root).unwrap();
         let name = stripped.display().to_string();
-        tests.push(TestDescAndFn {
-            desc: TestDesc {
-                name: TestName::DynTestName(name),
-                allow_fail: false,
-                ignore: false,
-                should_panic: ShouldPanic::No,
-                test_type: TestType::Unknown,
-            },
-            testfn: TestFn::DynTestFn(Box::new(move || run_test(&config, &path))),
-        });
+        let config = config.clone();
+        tests.push(make_test(name, move || run_test(&config, &path)));
+    }
+
+    let data = config.root.join("data");
+    for entry in collect_tests(&data) {
+        let path = entry.path().to_path_buf();
+        if !path.to_str().unwrap().ends_with(".rt.hex") {
+            continue;
+        }
+        let name = format!("snapshots/{}", snapshot::snapshot_name(&path));
+        let config = config.clone();
+        tests.push(make_test(name, move || snapshot::run_snapshot_test(&config, &path)));
+    }
+}
+
+fn make_test(name: String, f: impl FnOnce() + Send + 'static) -> TestDescAndFn {
+    TestDescAndFn {
+        desc: TestDesc {
+            name: TestName::DynTestName(name),
+            allow_fail: false,
+            ignore: false,
+            should_panic: ShouldPanic::No,
+            test_type: TestType::Unknown,
+        },
+        testfn: TestFn::DynTestFn(Box::new(f)),
     }
 }
 
@@ -92,50 +111,41 @@ fn collect_tests(root: &Path) -> impl Iterator<Item = DirEntry> {
 fn run_test(config: &Config, path: &Path) {
     let test_name = path.file_stem().unwrap().to_str().unwrap();
 
-    // let s = fs::read_to_string(path).unwrap();
-    // let comment = if path.extension() == Some("evm".as_ref()) { "#"} else { "//"};
-
-    // let lines = s.lines().map(str::trim).filter(|s| !s.is_empty());
-    // let comments = lines.filter_map(|s| s.strip_prefix(comment)).map(str::trim_start);
-    // let mut commands = comments.filter_map(|s| s.strip_prefix("RUN:")).map(str::trim_start);
-    // let command = commands.next().expect("no `RUN:` directive provided");
-    // assert!(commands.next().is_none(), "multiple `RUN:` directives provided");
+    let s = fs::read_to_string(path).unwrap();
+    let comment = if path.extension() == Some("evm".as_ref()) { ";" } else { "//" };
+    let args = run_command(&s, comment, path);
 
     let build_dir = &config.build_base;
-
-    let mut compiler = Command::new(config.cmd);
-    fs::create_dir_all(build_dir).unwrap();
-    compiler.arg(path).arg("-o").arg(build_dir);
-    // eprintln!("running compiler: {compiler:?}");
-    let output = compiler.output().expect("failed to run test");
-    assert!(
-        output.status.success(),
-        "compiler failed with {}:\n{}",
-        output.status,
-        String::from_utf8_lossy(&output.stderr)
-    );
+    config.run_compiler(args.iter().map(OsStr::new), build_dir);
     let out_dir = build_dir.join(test_name);
     assert!(out_dir.exists(), "no output produced");
 
     let input_path = out_dir.join("opt.ll");
+    let input = fs::read_to_string(&input_path).unwrap();
+    if let Err(e) = filecheck::check(&s, &input) {
+        panic!("FileCheck failed:\n{}:{e}\ninput file: {}", path.display(), input_path.display());
+    }
+}
 
-    let mut filecheck = Command::new(config.filecheck.as_deref().unwrap_or("FileCheck".as_ref()));
-    filecheck.arg(path).arg("--input-file").arg(&input_path);
-    // eprintln!("running filecheck: {filecheck:?}");
-    let output = filecheck.output().expect("failed to run FileCheck");
-    assert!(
-        output.status.success(),
-        "FileCheck failed with {}:\n{}",
-        output.status,
-        String::from_utf8_lossy(&output.stderr)
-    );
+/// Parses the compiler arguments from the `RUN:` directive in the test file, substituting `%s`
+/// with the path to the test file.
+///
+/// Defaults to `run %s` if there is no `RUN:` directive. The output directory is always passed
+/// with `-o`.
+fn run_command(s: &str, comment: &str, path: &Path) -> Vec<String> {
+    let lines = s.lines().map(str::trim).filter(|s| !s.is_empty());
+    let comments = lines.filter_map(|s| s.strip_prefix(comment)).map(str::trim_start);
+    let mut commands = comments.filter_map(|s| s.strip_prefix("RUN:")).map(str::trim_start);
+    let command = commands.next().unwrap_or("run %s");
+    assert!(commands.next().is_none(), "multiple `RUN:` directives provided");
+    let path = path.to_str().unwrap();
+    command.split_whitespace().map(|arg| arg.replace("%s", path)).collect()
 }
 
 struct Config {
     cmd: &'static Path,
     root: &'static Path,
     build_base: PathBuf,
-    filecheck: Option<PathBuf>,
 }
 
 impl Config {
@@ -143,6 +153,21 @@ impl Config {
         let root = Path::new(env!("CARGO_MANIFEST_DIR")).parent().unwrap().parent().unwrap();
         let build_base = root.join("target/tester");
         fs::create_dir_all(&build_base).unwrap();
-        Self { root, cmd, build_base, filecheck: None }
+        Self { root, cmd, build_base }
+    }
+
+    /// Runs the compiler with the given arguments, writing its output to `out_dir`.
+    fn run_compiler<'a>(&self, args: impl IntoIterator<Item = &'a OsStr>, out_dir: &Path) {
+        let mut compiler = Command::new(self.cmd);
+        fs::create_dir_all(out_dir).unwrap();
+        compiler.args(args).arg("-o").arg(out_dir);
+        // eprintln!("running compiler: {compiler:?}");
+        let output = compiler.output().expect("failed to run test");
+        assert!(
+            output.status.success(),
+            "compiler failed with {}:\n{}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr)
+        );
     }
 }
@@ -0,0 +1,290 @@
+//! IR snapshot tests for the bundled contracts in `data/`.
+//!
+//! Each contract is compiled with the default optimization level, and a few metrics are extracted
+//! from the optimized IR dump. These are then compared against the snapshots checked in to
+//! `tests/snapshots`, with some tolerance.
+//!
+//! Set `REVMC_BLESS=1` to write or update the snapshots. A missing snapshot is an error otherwise.
+
+use crate::Config;
+use std::{collections::BTreeMap, fmt, fs, path::Path};
+
+/// The maximum allowed relative difference of a metric, in percent.
+const TOLERANCE_PERCENT: u64 = 2;
+
+/// Metrics extracted from an LLVM IR module.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct IrMetrics {
+    metrics: BTreeMap<&'static str, u64>,
+}
+
+impl IrMetrics {
+    const KEYS: &'static [&'static str] =
+        &["functions", "blocks", "instructions", "stack_stores", "builtin_calls", "branches"];
+
+    /// Extracts the metrics from the textual LLVM IR.
+    pub(crate) fn from_ir(ir: &str) -> Self {
+        let mut functions = 0;
+        let mut blocks = 0;
+        let mut instructions = 0;
+        let mut stack_stores = 0;
+        let mut builtin_calls = 0;
+        let mut branches = 0;
+
+        let mut in_function = false;
+        let mut at_entry = false;
+        let mut in_switch = false;
+        for line in ir.lines() {
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            if !in_function {
+                if line.starts_with("define ") {
+                    in_function = true;
+                    at_entry = true;
+                    functions += 1;
+                }
+                continue;
+            }
+            if line == "}" {
+                in_function = false;
+                continue;
+            }
+            // `switch` cases are listed on separate lines.
+            if in_switch {
+                in_switch = line != "]";
+                continue;
+            }
+            if is_label(line) {
+                blocks += 1;
+                at_entry = false;
+                continue;
+            }
+            // The entry block only has a label if it is named.
+            if std::mem::take(&mut at_entry) {
+                blocks += 1;
+            }
+
+            instructions += 1;
+            let inst = line.split_once(" = ").map_or(line, |(_, inst)| inst);
+            if inst.starts_with("store i256 ") {
+                stack_stores += 1;
+            } else if inst.starts_with("br ") {
+                branches += 1;
+            } else if inst.starts_with("switch ") {
+                branches += 1;
+                in_switch = inst.ends_with('[');
+            }
+            if is_call(inst) && inst.contains("@__revmc_builtin_") {
+                builtin_calls += 1;
+            }
+        }
+
+        Self {
+            metrics: BTreeMap::from([
+                ("functions", functions),
+                ("blocks", blocks),
+                ("instructions", instructions),
+                ("stack_stores", stack_stores),
+                ("builtin_calls", builtin_calls),
+                ("branches", branches),
+            ]),
+        }
+    }
+
+    /// Parses a snapshot file.
+    pub(crate) fn parse(s: &str) -> Result<Self, String> {
+        let mut metrics = BTreeMap::new();
+        for line in s.lines().map(str::trim).filter(|s| !s.is_empty() && !s.starts_with('#')) {
+            let (key, value) =
+                line.split_once(':').ok_or_else(|| format!("invalid snapshot line: {line:?}"))?;
+            let key = Self::KEYS
+                .iter()
+                .find(|&&k| k == key.trim())
+                .ok_or_else(|| format!("unknown metric: {key:?}"))?;
+            let value =
+                value.trim().parse().map_err(|e| format!("invalid value for {key}: {e}"))?;
+            metrics.insert(*key, value);
+        }
+        Ok(Self { metrics })
+    }
+
+    /// Compares `self` against the `expected` snapshot, returning a description of every metric
+    /// that differs by more than [`TOLERANCE_PERCENT`].
+    pub(crate) fn compare(&self, expected: &Self) -> Vec<String> {
+        let mut errors = Vec::new();
+        for key in Self::KEYS {
+            let actual = self.metrics.get(key).copied().unwrap_or(0);
+            let Some(&expected) = expected.metrics.get(key) else {
+                errors.push(format!("{key}: missing from snapshot (actual: {actual})"));
+                continue;
+            };
+            if actual.abs_diff(expected) * 100 > expected * TOLERANCE_PERCENT {
+                let kind = if actual > expected { "regressed" } else { "improved" };
+                errors.push(format!("{key}: {kind} from {expected} to {actual}"));
+            }
+        }
+        errors
+    }
+}
+
+impl fmt::Display for IrMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (key, value) in &self.metrics {
+            writeln!(f, "{key}: {value}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Compiles the contract at `path` and compares the IR metrics against its snapshot.
+pub(crate) fn run_snapshot_test(config: &Config, path: &Path) {
+    let name = snapshot_name(path);
+    let out_dir = config.build_base.join("snapshots");
+    fs::create_dir_all(&out_dir).unwrap();
+
+    let mut args = vec!["compile".as_ref(), path.as_os_str(), "--no-link".as_ref()];
+    if name.ends_with("-eof") {
+        args.push("--eof".as_ref());
+    }
+    config.run_compiler(args, &out_dir);
+
+    // The dump directory is named after the file stem.
+    let stem = path.file_stem().unwrap();
+    let ir = fs::read_to_string(out_dir.join(stem).join("opt.ll")).unwrap();
+    let actual = IrMetrics::from_ir(&ir);
+
+    let snapshot_path = config.root.join("tests/snapshots").join(format!("{name}.snap"));
+    let bless = std::env::var_os("REVMC_BLESS").is_some_and(|v| v != "0");
+    if bless {
+        fs::create_dir_all(snapshot_path.parent().unwrap()).unwrap();
+        fs::write(&snapshot_path, actual.to_string()).unwrap();
+        return;
+    }
+    let contents = fs::read_to_string(&snapshot_path).unwrap_or_else(|e| {
+        panic!(
+            "failed to read snapshot {}: {e}
+run with `REVMC_BLESS=1` to create it",
+            snapshot_path.display()
+        )
+    });
+    let expected = IrMetrics::parse(&contents).unwrap();
+    let errors = actual.compare(&expected);
+    assert!(
+        errors.is_empty(),
+        "IR metrics differ from {} by more than {TOLERANCE_PERCENT}%:\n{}\n\
+         run with `REVMC_BLESS=1` to update the snapshot",
+        snapshot_path.display(),
+        errors.join("\n")
+    );
+}
+
+/// Returns the snapshot name of a contract file, e.g. `weth` for `data/weth.rt.hex`.
+pub(crate) fn snapshot_name(path: &Path) -> String {
+    let file_name = path.file_name().unwrap().to_str().unwrap();
+    let name = file_name.strip_suffix(".hex").unwrap_or(file_name);
+    name.strip_suffix(".rt").unwrap_or(name).to_string()
+}
+
+fn strip_comment(line: &str) -> &str {
+    // `;` does not appear in instructions outside of string constants, which are only present in
+    // global definitions.
+    line.split_once(';').map_or(line, |(line, _)| line)
+}
+
+fn is_label(line: &str) -> bool {
+    line.ends_with(':') && !line.contains(' ')
+}
+
+fn is_call(inst: &str) -> bool {
+    let inst =
+        inst.strip_prefix("tail ").or_else(|| inst.strip_prefix("musttail ")).unwrap_or(inst);
+    inst.starts_with("call ") || inst.starts_with("invoke ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IR: &str = r#"
+@global = private constant [4 x i8] c"a;b\00"
+
+define void @no_label(ptr %0) {
+  store i256 0, ptr %0, align 8 ; comment
+  ret void
+}
+
+define i8 @f(ptr %0, i1 %c) {
+entry:
+  %x = load i256, ptr %0, align 8
+  br i1 %c, label %a, label %b
+
+a:                                                ; preds = %entry
+  store i256 %x, ptr %0, align 8
+  %r = tail call i8 @__revmc_builtin_sstore(ptr %0)
+  switch i8 %r, label %b [
+    i8 0, label %b
+  ]
+
+b:
+  call void @other()
+  ret i8 0
+}
+
+declare void @__revmc_builtin_sload(ptr)
+"#;
+
+    fn metrics(pairs: &[(&'static str, u64)]) -> IrMetrics {
+        IrMetrics { metrics: pairs.iter().copied().collect() }
+    }
+
+    #[test]
+    fn from_ir() {
+        assert_eq!(
+            IrMetrics::from_ir(IR),
+            metrics(&[
+                ("functions", 2),
+                ("blocks", 4),
+                ("instructions", 9),
+                ("stack_stores", 2),
+                ("builtin_calls", 1),
+                ("branches", 2),
+            ])
+        );
+    }
+
+    #[test]
+    fn parse() {
+        let m = IrMetrics::from_ir(IR);
+        assert_eq!(IrMetrics::parse(&m.to_string()), Ok(m));
+        assert_eq!(IrMetrics::parse("# comment\n\n blocks : 3\n"), Ok(metrics(&[("blocks", 3)])));
+        assert!(IrMetrics::parse("blocks").unwrap_err().contains("invalid snapshot line"));
+        assert!(IrMetrics::parse("foo: 1").unwrap_err().contains("unknown metric"));
+        assert!(IrMetrics::parse("blocks: x").unwrap_err().contains("invalid value"));
+    }
+
+    #[test]
+    fn compare() {
+        let all = |value| metrics(&IrMetrics::KEYS.iter().map(|&k| (k, value)).collect::<Vec<_>>());
+        let expected = all(100);
+        assert!(all(100).compare(&expected).is_empty());
+        assert!(all(102).compare(&expected).is_empty());
+        assert!(all(98).compare(&expected).is_empty());
+
+        let errors = all(103).compare(&expected);
+        assert_eq!(errors.len(), IrMetrics::KEYS.len());
+        assert_eq!(errors[0], "functions: regressed from 100 to 103");
+        assert_eq!(all(97).compare(&expected)[0], "functions: improved from 100 to 97");
+
+        let errors = all(100).compare(&metrics(&[("functions", 100)]));
+        assert_eq!(errors.len(), IrMetrics::KEYS.len() - 1);
+        assert_eq!(errors[0], "blocks: missing from snapshot (actual: 100)");
+    }
+
+    #[test]
+    fn snapshot_name() {
+        assert_eq!(super::snapshot_name(Path::new("data/weth.rt.hex")), "weth");
+        assert_eq!(super::snapshot_name(Path::new("data/counter-eof.rt.hex")), "counter-eof");
+    }
+}
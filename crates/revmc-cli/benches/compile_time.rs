@@ -0,0 +1,124 @@
+#![allow(missing_docs)]
+
+//! Compile-time benchmarks of each compiler phase, for every bundled contract.
+//!
+//! Each benchmark measures a single phase, with all the previous phases done in its setup:
+//! - `analyze`: decoding, validating and analyzing the bytecode;
+//! - `translate`: translating the analyzed bytecode into LLVM IR;
+//! - `verify`: verifying the module;
+//! - `optimize`: optimizing the module;
+//! - `codegen`: emitting the object file.
+
+use iai_callgrind::{
+    library_benchmark, library_benchmark_group, main, EventKind, LibraryBenchmarkConfig,
+    RegressionConfig,
+};
+use revm_primitives::SpecId;
+use revmc::{
+    llvm::inkwell::context::Context, Bytecode, EvmCompiler, EvmCompilerInput, EvmLlvmBackend,
+    OptimizationLevel,
+};
+use std::hint::black_box;
+
+const SPEC_ID: SpecId = SpecId::OSAKA;
+
+/// The maximum allowed regression of the instruction count of a phase, in percent.
+const MAX_REGRESSION: f64 = 5.0;
+
+type Compiler = EvmCompiler<EvmLlvmBackend<'static>>;
+
+macro_rules! phase_benches {
+    ($($phase:ident($setup:ident) => |$arg:ident: $ty:ty| $body:expr;)*) => {$(
+        #[library_benchmark]
+        // The benches of `revmc_cli::get_benches` that are contracts in `data/`.
+        // `args` must be a literal list, so keep it in sync when adding a contract there.
+        #[benches::contracts(
+            args = [
+                "counter",
+                "counter-eof",
+                "snailtracer",
+                "snailtracer-eof",
+                "weth",
+                "hash_10k",
+                "hash_10k-eof",
+                "erc20_transfer",
+                "push0_proxy",
+                "usdc_proxy",
+                "fiat_token",
+                "uniswap_v2_pair",
+                "univ2_router",
+                "seaport",
+                "airdrop",
+                "bswap64",
+                "bswap64_opt",
+            ],
+            setup = $setup
+        )]
+        fn $phase(mut $arg: $ty) {
+            black_box($body);
+        }
+    )*};
+}
+
+phase_benches! {
+    analyze(setup_analyze) => |input: (Compiler, &'static [u8])| {
+        input.0.parse(EvmCompilerInput::Code(input.1), SPEC_ID).unwrap()
+    };
+    translate(setup_translate) => |input: (Compiler, Bytecode<'static>)| {
+        input.0.translate_parsed("test", &input.1).unwrap()
+    };
+    verify(setup_verify) => |compiler: Compiler| {
+        compiler.verify_module().unwrap()
+    };
+    optimize(setup_optimize) => |compiler: Compiler| {
+        compiler.optimize_module().unwrap()
+    };
+    codegen(setup_codegen) => |compiler: Compiler| {
+        compiler.write_object(std::io::sink()).unwrap()
+    };
+}
+
+fn setup_analyze(name: &str) -> (Compiler, &'static [u8]) {
+    let bench = revmc_cli::get_bench(name).unwrap();
+    // The compiler must outlive the benchmark, so leak the LLVM context.
+    let context = Box::leak(Box::new(Context::create()));
+    let backend = EvmLlvmBackend::new(context, true, OptimizationLevel::Aggressive).unwrap();
+    let mut compiler = EvmCompiler::new(backend);
+    compiler.set_module_name(name);
+    (compiler, bench.bytecode.leak())
+}
+
+fn setup_translate(name: &str) -> (Compiler, Bytecode<'static>) {
+    let (mut compiler, code) = setup_analyze(name);
+    let bytecode = compiler.parse(EvmCompilerInput::Code(code), SPEC_ID).unwrap();
+    (compiler, bytecode)
+}
+
+fn setup_verify(name: &str) -> Compiler {
+    let (mut compiler, bytecode) = setup_translate(name);
+    compiler.translate_parsed("test", &bytecode).unwrap();
+    compiler
+}
+
+fn setup_optimize(name: &str) -> Compiler {
+    let mut compiler = setup_verify(name);
+    compiler.verify_module().unwrap();
+    compiler
+}
+
+fn setup_codegen(name: &str) -> Compiler {
+    let mut compiler = setup_verify(name);
+    compiler.finalize().unwrap();
+    compiler
+}
+
+library_benchmark_group!(
+    name = compile_time;
+    benchmarks = analyze, translate, verify, optimize, codegen
+);
+
+main!(
+    config = LibraryBenchmarkConfig::default()
+        .regression(RegressionConfig::default().limits([(EventKind::Ir, MAX_REGRESSION)]));
+    library_benchmark_groups = compile_time
+);
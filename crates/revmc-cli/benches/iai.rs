@@ -15,15 +15,14 @@ binary_benchmark_group!(
 fn setup_group(group: &mut BinaryBenchmarkGroup) {
     let make_bench = |name: &str, small: bool, is_ct: bool| {
         let mut args = Vec::with_capacity(3);
-        args.push(name);
         // let out_dir = std::env::temp_dir().join("revmc-cli-iai");
         // let so = out_dir.join(name).join("a.so");
         if is_ct {
-            args.extend(["--aot", "--no-link"]);
-            // args.extend(["--aot", "--no-link", "-o", out_dir.to_str().unwrap()]);
+            args.extend(["compile", name, "--no-link"]);
+            // args.extend(["compile", name, "--no-link", "-o", out_dir.to_str().unwrap()]);
         } else {
-            args.push("1");
-            // args.extend(["1", "--shared-library", so.to_str().unwrap()]);
+            args.extend(["run", name]);
+            // args.extend(["run", name, "--load", so.to_str().unwrap()]);
         }
         let mut bench = Bench::new(name.replace('-', "_"));
         bench.command(Command::new(CMD).args(&args)).config(BinaryBenchmarkConfig::default());
@@ -0,0 +1,197 @@
+use alloy_dyn_abi::{DynSolValue, FunctionExt, JsonAbiExt, Specifier};
+use alloy_json_abi::{Function, JsonAbi};
+use revm_primitives::hex;
+use revmc::eyre::{bail, ensure, eyre, Result, WrapErr};
+use std::{fmt::Write, path::Path};
+
+/// ABI information used to encode calldata and to decode return data and revert reasons.
+#[derive(Clone, Debug, Default)]
+pub struct Abi {
+    /// The function being called, if known.
+    pub function: Option<Function>,
+    /// The contract ABI, if any.
+    ///
+    /// Used to look up functions by name or selector, and to decode custom errors.
+    pub abi: Option<JsonAbi>,
+}
+
+impl Abi {
+    /// Creates a new ABI from a function signature or name, and an ABI JSON file.
+    ///
+    /// Without an ABI file, `sig` must be a full function signature, like
+    /// `transfer(address,uint256)`. With one, it may also be a function name.
+    ///
+    /// The ABI file may either be a JSON ABI, or a compiler artifact with an `abi` field.
+    pub fn new(sig: Option<&str>, abi_path: Option<&Path>) -> Result<Self> {
+        let abi = abi_path.map(read_abi).transpose()?;
+        let function = match (sig, &abi) {
+            (None, _) => None,
+            (Some(sig), None) => {
+                Some(Function::parse(sig).map_err(|e| eyre!("invalid function signature: {e}"))?)
+            }
+            (Some(sig), Some(abi)) => {
+                let mut functions = abi.functions().filter(|f| {
+                    if sig.contains('(') {
+                        f.signature() == sig
+                    } else {
+                        f.name == sig
+                    }
+                });
+                let function = functions.next().ok_or_else(|| eyre!("function {sig} not found"))?;
+                ensure!(
+                    functions.next().is_none(),
+                    "function {sig} is overloaded; specify the full signature"
+                );
+                Some(function.clone())
+            }
+        };
+        Ok(Self { function, abi })
+    }
+
+    /// ABI-encodes a call to the function with the given arguments, including the selector.
+    ///
+    /// The arguments are parsed according to the function's input types.
+    pub fn encode_calldata(&self, args: &[String]) -> Result<Vec<u8>> {
+        let Some(function) = &self.function else { bail!("no function signature") };
+        ensure!(
+            args.len() == function.inputs.len(),
+            "{} expects {} arguments, got {}",
+            function.signature(),
+            function.inputs.len(),
+            args.len()
+        );
+        let values = function
+            .inputs
+            .iter()
+            .zip(args)
+            .map(|(param, arg)| {
+                let ty = param.resolve()?;
+                ty.coerce_str(arg).wrap_err_with(|| format!("invalid {ty} argument: {arg:?}"))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(function.abi_encode_input(&values)?)
+    }
+
+    /// Sets the function from the selector in the given calldata, if not already set.
+    pub fn resolve_function(&mut self, calldata: &[u8]) {
+        if self.function.is_some() {
+            return;
+        }
+        let (Some(abi), Some(selector)) = (&self.abi, calldata.get(..4)) else { return };
+        self.function = abi.functions().find(|f| f.selector() == selector).cloned();
+    }
+
+    /// Decodes the return data of a successful call, or the revert reason of a reverted one.
+    ///
+    /// Returns `None` if the data could not be decoded.
+    pub fn decode_output(&self, success: bool, output: &[u8]) -> Option<String> {
+        if success {
+            let function = self.function.as_ref()?;
+            let values = function.abi_decode_output(output, true).ok()?;
+            return Some(format!("decoded output: {}", format_values(&values)));
+        }
+
+        if output.is_empty() {
+            return None;
+        }
+        // Custom errors first, since `decode_revert_reason` falls back to any valid UTF-8 string.
+        if let Some(reason) = self.decode_custom_error(output) {
+            return Some(format!("revert reason: {reason}"));
+        }
+        let reason = alloy_sol_types::decode_revert_reason(output)?;
+        Some(format!("revert reason: {reason}"))
+    }
+
+    fn decode_custom_error(&self, output: &[u8]) -> Option<String> {
+        let selector = output.get(..4)?;
+        let error = self.abi.as_ref()?.errors().find(|e| e.selector() == selector)?;
+        let values = error.abi_decode_input(&output[4..], true).ok()?;
+        Some(format!("{}{}", error.name, format_values(&values)))
+    }
+}
+
+fn read_abi(path: &Path) -> Result<JsonAbi> {
+    let contents = std::fs::read_to_string(path)?;
+    let mut value: serde_json::Value = serde_json::from_str(&contents)
+        .wrap_err_with(|| format!("failed to parse ABI file {}", path.display()))?;
+    if let Some(abi) = value.get_mut("abi") {
+        value = abi.take();
+    }
+    serde_json::from_value(value).wrap_err_with(|| format!("invalid ABI in {}", path.display()))
+}
+
+fn format_values(values: &[DynSolValue]) -> String {
+    let mut s = String::from("(");
+    for (i, value) in values.iter().enumerate() {
+        if i > 0 {
+            s.push_str(", ");
+        }
+        format_value(&mut s, value);
+    }
+    s.push(')');
+    s
+}
+
+fn format_value(s: &mut String, value: &DynSolValue) {
+    let _ = match value {
+        DynSolValue::Bool(b) => write!(s, "{b}"),
+        DynSolValue::Int(i, _) => write!(s, "{i}"),
+        DynSolValue::Uint(u, _) => write!(s, "{u}"),
+        DynSolValue::FixedBytes(word, size) => {
+            write!(s, "{}", hex::encode_prefixed(&word[..*size]))
+        }
+        DynSolValue::Address(address) => write!(s, "{address}"),
+        DynSolValue::Function(function) => write!(s, "{function}"),
+        DynSolValue::Bytes(bytes) => write!(s, "{}", hex::encode_prefixed(bytes)),
+        DynSolValue::String(string) => write!(s, "{string:?}"),
+        DynSolValue::Array(values) | DynSolValue::FixedArray(values) => {
+            s.push('[');
+            for (i, value) in values.iter().enumerate() {
+                if i > 0 {
+                    s.push_str(", ");
+                }
+                format_value(s, value);
+            }
+            s.push(']');
+            Ok(())
+        }
+        DynSolValue::Tuple(values) => {
+            s.push_str(&format_values(values));
+            Ok(())
+        }
+        #[allow(unreachable_patterns)]
+        value => write!(s, "{value:?}"),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use revm_primitives::{address, U256};
+
+    #[test]
+    fn encode_calldata() {
+        let abi = Abi::new(Some("transfer(address,uint256)"), None).unwrap();
+        let to = address!("00000000000000000000000000000000deadbeef");
+        let calldata = abi.encode_calldata(&[to.to_string(), "100".to_string()]).unwrap();
+        assert_eq!(calldata[..4], [0xa9, 0x05, 0x9c, 0xbb]);
+        assert_eq!(calldata.len(), 4 + 32 * 2);
+        assert_eq!(calldata[4 + 12..4 + 32], to[..]);
+        assert_eq!(U256::from_be_slice(&calldata[4 + 32..]), U256::from(100));
+
+        assert!(abi.encode_calldata(&["100".to_string()]).is_err());
+        assert!(abi.encode_calldata(&["0x".to_string(), "100".to_string()]).is_err());
+    }
+
+    #[test]
+    fn decode_output() {
+        let abi = Abi::new(Some("function balanceOf(address) returns (uint256)"), None).unwrap();
+        let output = U256::from(42).to_be_bytes::<32>();
+        assert_eq!(abi.decode_output(true, &output).unwrap(), "decoded output: (42)");
+
+        let revert = alloy_sol_types::Revert::from("nope");
+        let output = alloy_sol_types::SolError::abi_encode(&revert);
+        assert_eq!(abi.decode_output(false, &output).unwrap(), "revert reason: revert: nope");
+        assert_eq!(abi.decode_output(false, &[]), None);
+    }
+}
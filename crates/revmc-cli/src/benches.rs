@@ -129,6 +129,11 @@ pub fn get_benches() -> Vec<Bench> {
             bytecode: include_code_str!("../../../data/uniswap_v2_pair.rt.hex").unwrap(),
             ..Default::default()
         },
+        Bench {
+            name: "univ2_router",
+            bytecode: include_code_str!("../../../data/univ2_router.rt.hex").unwrap(),
+            ..Default::default()
+        },
         Bench {
             name: "seaport",
             bytecode: include_code_str!("../../../data/seaport.rt.hex").unwrap(),
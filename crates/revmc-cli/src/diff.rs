@@ -0,0 +1,229 @@
+use revm_interpreter::{InstructionResult, Interpreter, InterpreterAction, InterpreterResult};
+use revm_primitives::{hex, Bytes, Log, U256};
+use std::fmt;
+
+/// The observable state after executing bytecode, used to compare interpreted and compiled
+/// execution.
+#[derive(Clone, Debug)]
+pub struct ExecutionSnapshot {
+    pub result: InstructionResult,
+    pub gas_remaining: u64,
+    pub gas_refunded: i64,
+    pub stack: Vec<U256>,
+    pub memory: Vec<u8>,
+    pub return_data: Bytes,
+    pub logs: Vec<Log>,
+    pub action: InterpreterAction,
+}
+
+/// The first difference between two [`ExecutionSnapshot`]s.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    /// What differs, e.g. `stack[1]` or `memory[0x20..0x40]`.
+    pub what: String,
+    /// The value in the interpreter.
+    pub interpreter: String,
+    /// The value in the compiled function.
+    pub compiled: String,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self { what, interpreter, compiled } = self;
+        writeln!(f, "first divergence in {what}:")?;
+        writeln!(f, "  interpreter: {interpreter}")?;
+        write!(f, "  compiled:    {compiled}")
+    }
+}
+
+impl ExecutionSnapshot {
+    /// Captures the state of the interpreter after execution returned the given action.
+    ///
+    /// If the action is [`InterpreterAction::None`], it is replaced with the return action that
+    /// [`Interpreter::run`] would have created.
+    pub fn new(interpreter: &Interpreter, mut action: InterpreterAction, logs: &[Log]) -> Self {
+        if action.is_none() {
+            action = InterpreterAction::Return {
+                result: InterpreterResult {
+                    result: interpreter.instruction_result,
+                    output: Bytes::new(),
+                    gas: interpreter.gas,
+                },
+            };
+        }
+        Self {
+            result: interpreter.instruction_result,
+            gas_remaining: interpreter.gas.remaining(),
+            gas_refunded: interpreter.gas.refunded(),
+            stack: interpreter.stack.data().clone(),
+            memory: interpreter.shared_memory.context_memory().to_vec(),
+            return_data: interpreter.return_data_buffer.clone(),
+            logs: logs.to_vec(),
+            action,
+        }
+    }
+
+    /// Returns the first difference between the interpreter's snapshot (`self`) and the compiled
+    /// function's snapshot, if any.
+    ///
+    /// Like the compiler's test suite, this considers all stack and out-of-gas errors equivalent,
+    /// and ignores the stack, memory, and gas when execution halted with an error, since the
+    /// compiled function may bail out earlier than the interpreter.
+    pub fn first_divergence(&self, compiled: &Self) -> Option<Divergence> {
+        let diff = |what: &str, a: &dyn fmt::Debug, b: &dyn fmt::Debug| {
+            Some(Divergence {
+                what: what.to_string(),
+                interpreter: format!("{a:?}"),
+                compiled: format!("{b:?}"),
+            })
+        };
+
+        let (a, b) = (self, compiled);
+        if a.result != b.result && !(is_equivalent_error(a.result) && is_equivalent_error(b.result))
+        {
+            return diff("instruction result", &a.result, &b.result);
+        }
+
+        if !b.result.is_error() {
+            if let Some(i) =
+                (0..a.stack.len().min(b.stack.len())).find(|&i| a.stack[i] != b.stack[i])
+            {
+                return diff(&format!("stack[{i}]"), &a.stack[i], &b.stack[i]);
+            }
+            if a.stack.len() != b.stack.len() {
+                return diff("stack length", &a.stack.len(), &b.stack.len());
+            }
+
+            if let Some(i) = (0..a.memory.len().max(b.memory.len()))
+                .find(|&i| a.memory.get(i) != b.memory.get(i))
+            {
+                let start = i / 32 * 32;
+                let word = |mem: &[u8]| {
+                    let word = mem.get(start..mem.len().min(start + 32)).unwrap_or_default();
+                    format!("{} (length {:#x})", hex::encode_prefixed(word), mem.len())
+                };
+                return Some(Divergence {
+                    what: format!("memory[{start:#x}..{:#x}]", start + 32),
+                    interpreter: word(&a.memory),
+                    compiled: word(&b.memory),
+                });
+            }
+
+            if a.gas_remaining != b.gas_remaining {
+                return diff("remaining gas", &a.gas_remaining, &b.gas_remaining);
+            }
+            if a.gas_refunded != b.gas_refunded {
+                return diff("gas refund", &a.gas_refunded, &b.gas_refunded);
+            }
+        }
+
+        if a.return_data != b.return_data {
+            return diff("return data", &a.return_data, &b.return_data);
+        }
+
+        if let Some(i) =
+            (0..a.logs.len().max(b.logs.len())).find(|&i| a.logs.get(i) != b.logs.get(i))
+        {
+            return diff(&format!("logs[{i}]"), &a.logs.get(i), &b.logs.get(i));
+        }
+
+        match (&a.action, &b.action) {
+            (
+                InterpreterAction::Return { result: ra },
+                InterpreterAction::Return { result: rb },
+            ) => {
+                if ra.result != rb.result
+                    && !(is_equivalent_error(ra.result) && is_equivalent_error(rb.result))
+                {
+                    return diff("action result", &ra.result, &rb.result);
+                }
+                if ra.output != rb.output {
+                    return diff("action output", &ra.output, &rb.output);
+                }
+                if !rb.result.is_error() && ra.gas.spent() != rb.gas.spent() {
+                    return diff("action gas spent", &ra.gas.spent(), &rb.gas.spent());
+                }
+            }
+            (a, b) if a != b => return diff("action", a, b),
+            _ => {}
+        }
+
+        None
+    }
+}
+
+/// Stack and out-of-gas errors may be reported differently, since the compiled function checks
+/// them per section rather than per instruction.
+fn is_equivalent_error(result: InstructionResult) -> bool {
+    matches!(
+        result,
+        InstructionResult::StackOverflow
+            | InstructionResult::StackUnderflow
+            | InstructionResult::OutOfGas
+            | InstructionResult::MemoryOOG
+            | InstructionResult::InvalidOperandOOG
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use revm_interpreter::Gas;
+
+    fn snapshot(result: InstructionResult, stack: &[u64]) -> ExecutionSnapshot {
+        ExecutionSnapshot {
+            result,
+            gas_remaining: 100,
+            gas_refunded: 0,
+            stack: stack.iter().map(|&x| U256::from(x)).collect(),
+            memory: vec![],
+            return_data: Bytes::new(),
+            logs: vec![],
+            action: InterpreterAction::Return {
+                result: InterpreterResult { result, output: Bytes::new(), gas: Gas::new(100) },
+            },
+        }
+    }
+
+    #[test]
+    fn first_divergence() {
+        let a = snapshot(InstructionResult::Stop, &[1, 2, 3]);
+        assert_eq!(a.first_divergence(&a), None);
+
+        let what = |b: &ExecutionSnapshot| a.first_divergence(b).unwrap().what;
+        assert_eq!(what(&snapshot(InstructionResult::Stop, &[1, 5, 3])), "stack[1]");
+        assert_eq!(what(&snapshot(InstructionResult::Stop, &[1, 5])), "stack[1]");
+        assert_eq!(what(&snapshot(InstructionResult::Stop, &[1, 2])), "stack length");
+        assert_eq!(what(&snapshot(InstructionResult::Stop, &[1, 2, 3, 4])), "stack length");
+        assert_eq!(what(&snapshot(InstructionResult::Return, &[1, 2, 3])), "instruction result");
+
+        let mut b = a.clone();
+        b.gas_remaining = 99;
+        assert_eq!(what(&b), "remaining gas");
+
+        let mut b = a.clone();
+        b.memory = vec![0; 64];
+        b.memory[0x21] = 1;
+        let divergence = a.first_divergence(&b).unwrap();
+        assert_eq!(divergence.what, "memory[0x0..0x20]");
+        assert_eq!(divergence.interpreter, "0x (length 0x0)");
+
+        let mut a = a.clone();
+        a.memory = vec![0; 64];
+        let divergence = a.first_divergence(&b).unwrap();
+        assert_eq!(divergence.what, "memory[0x20..0x40]");
+        assert_eq!(divergence.interpreter, format!("0x{} (length 0x40)", "00".repeat(32)));
+        assert_eq!(divergence.compiled, format!("0x0001{} (length 0x40)", "00".repeat(30)));
+    }
+
+    #[test]
+    fn first_divergence_errors() {
+        // Equivalent errors are not reported, and the stack is ignored on error.
+        let a = snapshot(InstructionResult::StackUnderflow, &[1]);
+        let b = snapshot(InstructionResult::OutOfGas, &[]);
+        assert_eq!(a.first_divergence(&b), None);
+
+        let b = snapshot(InstructionResult::InvalidJump, &[1]);
+        assert_eq!(a.first_divergence(&b).unwrap().what, "instruction result");
+    }
+}
@@ -0,0 +1,355 @@
+//! EVM assembly DSL.
+//!
+//! Each line contains whitespace-separated words, and `;` starts a comment until the end of the
+//! line. Words are:
+//! - opcode mnemonics, followed by their immediate if any, e.g. `PUSH1 0x01` or `ADD`;
+//! - `PUSH <imm>`, which picks the smallest `PUSH` that fits the immediate;
+//! - label definitions, e.g. `loop:`, which mark the position of the next instruction;
+//! - macro invocations, which expand to the macro's body.
+//!
+//! Immediates are decimal or `0x`-prefixed hexadecimal numbers, constants, or label names. `PUSH
+//! <label>` is sized to fit the label's final position, while `PUSHn <label>` always uses `n`
+//! bytes.
+//!
+//! Lines starting with `.` are directives:
+//! - `.const <name> <value>` defines a constant;
+//! - `.macro <name>` starts a macro definition, which lasts until `.end`;
+//! - `.include "<path>"` includes another file, relative to the current file's directory.
+
+use revm_interpreter::OpCode;
+use revmc::{
+    eyre::{bail, ensure, eyre, Result, WrapErr},
+    U256,
+};
+use std::{cmp::Ordering, collections::HashMap, path::Path, str::FromStr};
+
+const COM: char = ';';
+const MAX_DEPTH: usize = 64;
+
+/// Parses EVM code from a string.
+///
+/// `.include` paths are resolved relative to `base_dir`, or the current directory if `None`.
+pub(crate) fn parse_evm_dsl(s: &str, base_dir: Option<&Path>) -> Result<Vec<u8>> {
+    let mut asm = Assembler::default();
+    asm.parse(s, base_dir.unwrap_or(Path::new("")), 0)?;
+    asm.assemble()
+}
+
+enum Item {
+    /// Raw bytes: an opcode followed by its immediate, if any.
+    Bytes(Vec<u8>),
+    /// A label definition.
+    Label(String),
+    /// A `PUSH` of the given label's position, with an optional fixed size.
+    PushLabel { size: Option<u8>, label: String },
+}
+
+#[derive(Default)]
+struct Assembler {
+    consts: HashMap<String, U256>,
+    macros: HashMap<String, Vec<String>>,
+    items: Vec<Item>,
+}
+
+impl Assembler {
+    fn parse(&mut self, s: &str, dir: &Path, depth: usize) -> Result<()> {
+        ensure!(depth < MAX_DEPTH, "maximum include or macro depth reached");
+
+        let mut lines = s.lines().map(|line| line.split(COM).next().unwrap().trim());
+        while let Some(line) = lines.next() {
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some(directive) = line.strip_prefix('.') {
+                let mut words = directive.split_whitespace();
+                match words.next().unwrap_or_default() {
+                    "const" => {
+                        let (Some(name), Some(value), None) =
+                            (words.next(), words.next(), words.next())
+                        else {
+                            bail!("expected `.const <name> <value>`, got {line:?}");
+                        };
+                        let value = self.value(value)?;
+                        ensure!(
+                            self.consts.insert(name.to_string(), value).is_none(),
+                            "constant {name:?} is already defined"
+                        );
+                    }
+                    "macro" => {
+                        let (Some(name), None) = (words.next(), words.next()) else {
+                            bail!("expected `.macro <name>`, got {line:?}");
+                        };
+                        let mut body = Vec::new();
+                        loop {
+                            match lines.next() {
+                                Some(".end") => break,
+                                Some(line) => body.push(line.to_string()),
+                                None => bail!("unterminated `.macro {name}`"),
+                            }
+                        }
+                        ensure!(
+                            self.macros.insert(name.to_string(), body).is_none(),
+                            "macro {name:?} is already defined"
+                        );
+                    }
+                    "include" => {
+                        let path = directive["include".len()..].trim();
+                        let path = path
+                            .strip_prefix('"')
+                            .and_then(|path| path.strip_suffix('"'))
+                            .ok_or_else(|| {
+                            eyre!("expected `.include \"<path>\"`, got {line:?}")
+                        })?;
+                        let path = dir.join(path);
+                        let contents = std::fs::read_to_string(&path)
+                            .wrap_err_with(|| format!("failed to include {}", path.display()))?;
+                        let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+                        self.parse(&contents, &dir, depth + 1)?;
+                    }
+                    "end" => bail!("unexpected `.end` outside of a macro"),
+                    _ => bail!("unknown directive: {line:?}"),
+                }
+                continue;
+            }
+
+            self.parse_words(line, dir, depth)?;
+        }
+        Ok(())
+    }
+
+    fn parse_words(&mut self, line: &str, dir: &Path, depth: usize) -> Result<()> {
+        let mut words = line.split_whitespace().peekable();
+        while let Some(word) = words.next() {
+            if let Some(label) = word.strip_suffix(':') {
+                ensure!(is_ident(label), "invalid label name: {label:?}");
+                self.items.push(Item::Label(label.to_string()));
+            } else if let Some(body) = self.macros.get(word) {
+                let body = body.join("\n");
+                self.parse(&body, dir, depth + 1)?;
+            } else if word == "PUSH" {
+                let next =
+                    words.next().ok_or_else(|| eyre!("missing immediate for opcode PUSH"))?;
+                if self.is_label(next) {
+                    self.items.push(Item::PushLabel { size: None, label: next.to_string() });
+                } else {
+                    let imm_bytes = self.imm(next, None)?;
+                    let mut bytes = vec![OpCode::PUSH0.get() + imm_bytes.len() as u8];
+                    bytes.extend_from_slice(&imm_bytes);
+                    self.items.push(Item::Bytes(bytes));
+                }
+            } else {
+                let op = OpCode::parse(word).ok_or_else(|| eyre!("invalid opcode: {word:?}"))?;
+                let imm_len = op.info().immediate_size();
+                if imm_len > 0 {
+                    let imm =
+                        words.next().ok_or_else(|| eyre!("missing immediate for opcode {op}"))?;
+                    if op.is_push() && self.is_label(imm) {
+                        let item = Item::PushLabel { size: Some(imm_len), label: imm.to_string() };
+                        self.items.push(item);
+                        continue;
+                    }
+                    let mut bytes = vec![op.get()];
+                    bytes.extend_from_slice(&self.imm(imm, Some(imm_len))?);
+                    self.items.push(Item::Bytes(bytes));
+                } else {
+                    if let Some(next) = words.peek() {
+                        if self.value(next).is_ok() {
+                            bail!("unexpected immediate for opcode {op}");
+                        }
+                    }
+                    self.items.push(Item::Bytes(vec![op.get()]));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn assemble(self) -> Result<Vec<u8>> {
+        // Resolve the sizes of `PUSH <label>`s. Sizes only ever grow, so this terminates.
+        let mut sizes = self
+            .items
+            .iter()
+            .map(|item| match item {
+                Item::Bytes(bytes) => bytes.len(),
+                Item::Label(_) => 0,
+                Item::PushLabel { size, .. } => 1 + size.unwrap_or(0) as usize,
+            })
+            .collect::<Vec<_>>();
+        let labels = loop {
+            let mut labels = HashMap::new();
+            let mut pc = 0;
+            for (item, size) in self.items.iter().zip(&sizes) {
+                if let Item::Label(label) = item {
+                    ensure!(
+                        labels.insert(label.as_str(), pc).is_none(),
+                        "duplicate label {label:?}"
+                    );
+                }
+                pc += size;
+            }
+
+            let mut changed = false;
+            for (item, size) in self.items.iter().zip(&mut sizes) {
+                if let Item::PushLabel { size: None, label } = item {
+                    let pc = *labels.get(label.as_str()).ok_or_else(|| undefined_label(label))?;
+                    let needed = 1 + U256::from(pc).byte_len();
+                    if needed > *size {
+                        *size = needed;
+                        changed = true;
+                    }
+                }
+            }
+            if !changed {
+                break labels;
+            }
+        };
+
+        let mut code = Vec::with_capacity(sizes.iter().sum());
+        for (item, &size) in self.items.iter().zip(&sizes) {
+            match item {
+                Item::Bytes(bytes) => code.extend_from_slice(bytes),
+                Item::Label(_) => {}
+                Item::PushLabel { label, .. } => {
+                    let pc = *labels.get(label.as_str()).ok_or_else(|| undefined_label(label))?;
+                    let imm_len = size - 1;
+                    let imm = pad_imm(U256::from(pc).to_be_bytes_trimmed_vec(), imm_len as u8)
+                        .wrap_err_with(|| {
+                            format!("label {label:?} does not fit in PUSH{imm_len}")
+                        })?;
+                    code.push(OpCode::PUSH0.get() + imm_len as u8);
+                    code.extend_from_slice(&imm);
+                }
+            }
+        }
+        Ok(code)
+    }
+
+    fn is_label(&self, s: &str) -> bool {
+        is_ident(s) && !self.consts.contains_key(s) && U256::from_str(s).is_err()
+    }
+
+    fn value(&self, s: &str) -> Result<U256> {
+        if let Some(&value) = self.consts.get(s) {
+            return Ok(value);
+        }
+        s.parse().wrap_err("failed to parse immediate")
+    }
+
+    fn imm(&self, s: &str, size: Option<u8>) -> Result<Vec<u8>> {
+        let imm_bytes = self.value(s)?.to_be_bytes_trimmed_vec();
+        match size {
+            Some(size) => pad_imm(imm_bytes, size),
+            None => Ok(imm_bytes),
+        }
+    }
+}
+
+fn pad_imm(mut imm_bytes: Vec<u8>, size: u8) -> Result<Vec<u8>> {
+    debug_assert!(size <= 32);
+    match imm_bytes.len().cmp(&(size as usize)) {
+        Ordering::Less => {
+            let extend = size as usize - imm_bytes.len();
+            imm_bytes.splice(0..0, std::iter::repeat(0).take(extend));
+        }
+        Ordering::Equal => {}
+        Ordering::Greater => {
+            bail!("expected at most {size} immediate bytes, got {}", imm_bytes.len())
+        }
+    }
+    Ok(imm_bytes)
+}
+
+fn is_ident(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn undefined_label(label: &str) -> revmc::eyre::Report {
+    eyre!("undefined label {label:?}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use revm_interpreter::opcode as op;
+
+    #[test]
+    fn test_evm_dsl() {
+        let cases: &[(&str, Vec<u8>)] = &[
+            ("ADD ; ADD\n ADD", vec![op::ADD, op::ADD]),
+            ("PUSH1 0", vec![op::PUSH1, 0]),
+            ("PUSH3 0x000069", vec![op::PUSH3, 0, 0, 0x69]),
+            ("PUSH3 0x69 ; padded", vec![op::PUSH3, 0, 0, 0x69]),
+            ("PUSH 0", vec![op::PUSH0]),
+            ("PUSH 1", vec![op::PUSH1, 1]),
+            ("PUSH 2", vec![op::PUSH1, 2]),
+            ("PUSH 69", vec![op::PUSH1, 69]),
+            ("PUSH 0x2222", vec![op::PUSH2, 0x22, 0x22]),
+            // Labels.
+            ("start: PUSH start JUMP", vec![op::PUSH0, op::JUMP]),
+            ("PUSH end JUMP end: JUMPDEST", vec![op::PUSH1, 3, op::JUMP, op::JUMPDEST]),
+            ("PUSH2 end JUMP\nend:\nJUMPDEST", vec![op::PUSH2, 0, 4, op::JUMP, op::JUMPDEST]),
+            (
+                "loop: JUMPDEST PUSH1 1 PUSH loop JUMPI",
+                vec![op::JUMPDEST, op::PUSH1, 1, op::PUSH0, op::JUMPI],
+            ),
+            // Constants.
+            (".const X 0x20\nPUSH X PUSH2 X", vec![op::PUSH1, 0x20, op::PUSH2, 0, 0x20]),
+            (".const X 1\n.const Y X\nPUSH Y", vec![op::PUSH1, 1]),
+            // Macros.
+            (".macro two\nPUSH 2\n.end\ntwo two ADD", vec![op::PUSH1, 2, op::PUSH1, 2, op::ADD]),
+        ];
+        for (s, expected) in cases.iter() {
+            let code = match parse_evm_dsl(s, None) {
+                Ok(code) => code,
+                Err(e) => panic!("code: {s:?}\n\n err: {e}"),
+            };
+            assert_eq!(code, *expected, "{s:?}");
+        }
+    }
+
+    #[test]
+    fn test_evm_dsl_label_sizing() {
+        // 300 bytes of padding pushes the label out of `PUSH1` range.
+        let s = format!("PUSH end JUMP {} end: JUMPDEST", "STOP ".repeat(300));
+        let code = parse_evm_dsl(&s, None).unwrap();
+        assert_eq!(code[..4], [op::PUSH2, 0x01, 0x30, op::JUMP]);
+        assert_eq!(code[0x130..], [op::JUMPDEST]);
+    }
+
+    #[test]
+    fn test_evm_dsl_errors() {
+        let cases = [
+            "PUSH nope",
+            "a: a: STOP",
+            "PUSH1 0x100",
+            "ADD 1",
+            ".const X",
+            ".end",
+            ".macro m\nm\n.end\nm",
+            ".include \"does_not_exist.evm\"",
+        ];
+        for s in cases {
+            assert!(parse_evm_dsl(s, None).is_err(), "{s:?}");
+        }
+    }
+
+    #[test]
+    fn test_evm_dsl_unterminated_macro() {
+        let err = parse_evm_dsl(".macro two\nPUSH 2\ntwo", None).unwrap_err();
+        assert_eq!(err.to_string(), "unterminated `.macro two`");
+    }
+
+    #[test]
+    fn test_evm_dsl_include() {
+        let tmp = std::env::temp_dir().join(format!("revmc-cli-dsl-{}", std::process::id()));
+        std::fs::create_dir_all(&tmp).unwrap();
+        std::fs::write(tmp.join("lib.evm"), ".const ONE 1\n.macro inc\nPUSH ONE ADD\n.end\n")
+            .unwrap();
+        let code = parse_evm_dsl(".include \"lib.evm\"\nPUSH0 inc", Some(&tmp));
+        let _ = std::fs::remove_dir_all(&tmp);
+        assert_eq!(code.unwrap(), [op::PUSH0, op::PUSH1, 1, op::ADD]);
+    }
+}
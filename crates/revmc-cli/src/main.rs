@@ -1,48 +1,78 @@
 #![allow(missing_docs)]
 
-use clap::{Parser, ValueEnum};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use color_eyre::{eyre::eyre, Result};
-use revm_interpreter::{opcode::make_instruction_table, SharedMemory};
-use revm_primitives::{address, spec_to_generic, Env, SpecId, TransactTo};
-use revmc::{eyre::ensure, EvmCompiler, EvmContext, EvmLlvmBackend, OptimizationLevel};
-use revmc_cli::{get_benches, read_code, Bench};
+use revm_interpreter::{
+    opcode::{make_instruction_table, InstructionTable},
+    DummyHost, Interpreter, InterpreterAction, SharedMemory,
+};
+use revm_primitives::{
+    address, spec_to_generic, Bytes, Env, EvmState, ExecutionResult, ResultAndState, SpecId,
+    TransactTo, U256,
+};
+use revmc::{
+    eyre::ensure, EvmCompiler, EvmCompilerInput, EvmContext, EvmLlvmBackend, ExecutionLimits,
+    OptimizationLevel,
+};
+use revmc_cli::{
+    build_evm, get_benches, read_code, read_state, run_state_test_file, set_code, state_db, Abi,
+    Bench, BenchStats, CompileReport, CompiledFunctions, ExecutionSnapshot, RunReport, StateDb,
+    StateTestReport,
+};
 use std::{
     hint::black_box,
+    io::{self, Write},
     path::{Path, PathBuf},
+    sync::Arc,
+    time::Instant,
 };
 
 #[derive(Parser)]
 struct Cli {
-    /// Benchmark name, "custom", path to a file, or a symbol to load from a shared object.
-    bench_name: String,
-    #[arg(default_value = "1")]
-    n_iters: u64,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Compile the code to an object file and link it into a shared library.
+    Compile(CompileArgs),
+    /// Run the code once with the JIT, a compiled shared library, or the interpreter.
+    Run(RunArgs),
+    /// Print the annotated disassembly, with the results of the bytecode analysis.
+    Disasm(DisasmArgs),
+    /// Print the result of the bytecode analysis.
+    Analyze(InputArgs),
+    /// Print the control-flow graph as Graphviz DOT or JSON.
+    Cfg(CfgArgs),
+    /// Run the code repeatedly and print the average execution time.
+    Bench(BenchArgs),
+    /// Run Ethereum `GeneralStateTests` JSON fixtures with compiled execution.
+    Statetest(StatetestArgs),
+}
+
+#[derive(Args)]
+struct InputArgs {
+    /// Benchmark name, "custom", or path to a file.
+    input: String,
 
     #[arg(long)]
     code: Option<String>,
     #[arg(long, conflicts_with = "code")]
     code_path: Option<PathBuf>,
-    #[arg(long)]
-    calldata: Option<String>,
-
-    /// Load a shared object file instead of JIT compiling.
-    ///
-    /// Use with `--aot` to also run the compiled library.
-    #[arg(long)]
-    load: Option<Option<PathBuf>>,
 
-    /// Parse the bytecode only.
-    #[arg(long)]
-    parse_only: bool,
-
-    /// Compile and link to a shared library.
-    #[arg(long)]
-    aot: bool,
-
-    /// Interpret the code instead of compiling.
-    #[arg(long, conflicts_with = "aot")]
-    interpret: bool,
+    #[arg(long, value_enum, default_value = "osaka")]
+    spec_id: SpecIdValueEnum,
+    /// Short-hand for `--spec-id osaka`.
+    #[arg(long, conflicts_with = "spec_id")]
+    eof: bool,
+    /// Skip validating EOF code.
+    #[arg(long, requires = "eof")]
+    no_validate: bool,
+}
 
+#[derive(Args)]
+struct CompilerArgs {
     /// Target triple.
     #[arg(long, default_value = "native")]
     target: String,
@@ -53,30 +83,163 @@ struct Cli {
     #[arg(long)]
     target_features: Option<String>,
 
-    /// Compile only, do not link.
-    #[arg(long, requires = "aot")]
-    no_link: bool,
-
     #[arg(short = 'o', long)]
     out_dir: Option<PathBuf>,
     #[arg(short = 'O', long, default_value = "3")]
     opt_level: OptimizationLevel,
-    #[arg(long, value_enum, default_value = "osaka")]
-    spec_id: SpecIdValueEnum,
-    /// Short-hand for `--spec-id osaka`.
-    #[arg(long, conflicts_with = "spec_id")]
-    eof: bool,
-    /// Skip validating EOF code.
-    #[arg(long, requires = "eof")]
-    no_validate: bool,
+    /// Custom LLVM pass pipeline, in the same format as `opt -passes`.
+    #[arg(long)]
+    passes: Option<String>,
+    /// Log the time spent in each LLVM pass.
+    ///
+    /// The events are logged at the `info` level under the `revmc_llvm::passes` target, for
+    /// example with `RUST_LOG=revmc_llvm::passes=info`.
+    #[arg(long)]
+    time_passes: bool,
     #[arg(long)]
     debug_assertions: bool,
+    /// Emit debug info mapping machine code back to the bytecode.
+    #[arg(long)]
+    debug_info: bool,
+    /// Write a `perf` map entry for the JIT-compiled function.
+    #[arg(long)]
+    perf_map: bool,
+    /// Write a `jitdump` record for the JIT-compiled function.
+    #[arg(long)]
+    jitdump: bool,
+    /// Register the JIT-compiled function with the GDB JIT interface.
+    #[arg(long)]
+    gdb: bool,
     #[arg(long)]
     no_gas: bool,
     #[arg(long)]
     no_len_checks: bool,
+}
+
+#[derive(Args)]
+struct DisasmArgs {
+    #[command(flatten)]
+    input: InputArgs,
+
+    /// Print the raw instructions, without running the bytecode analysis.
+    #[arg(long)]
+    raw: bool,
+}
+
+#[derive(Args)]
+struct CfgArgs {
+    #[command(flatten)]
+    input: InputArgs,
+
+    /// The output format.
+    #[arg(long, value_enum, default_value = "dot")]
+    format: CfgFormat,
+    /// Write the graph to this file instead of stdout.
+    #[arg(long, short)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum CfgFormat {
+    Dot,
+    Json,
+}
+
+#[derive(Args)]
+struct CompileArgs {
+    #[command(flatten)]
+    input: InputArgs,
+    #[command(flatten)]
+    compiler: CompilerArgs,
+
+    /// Compile only, do not link.
+    #[arg(long)]
+    no_link: bool,
+}
+
+#[derive(Args)]
+struct RunArgs {
+    #[command(flatten)]
+    input: InputArgs,
+    #[command(flatten)]
+    compiler: CompilerArgs,
+
+    #[arg(long)]
+    calldata: Option<String>,
+    /// The function signature to ABI-encode the calldata with, followed by the arguments.
+    ///
+    /// For example: `--sig "transfer(address,uint256)" 0x... 100`. With `--abi`, this may also
+    /// be just the function name. The return data and revert reasons are decoded accordingly.
+    #[arg(long, conflicts_with = "calldata")]
+    sig: Option<String>,
+    /// The function arguments, ABI-encoded according to `--sig`.
+    #[arg(requires = "sig", allow_hyphen_values = true)]
+    args: Vec<String>,
+    /// Path to the contract ABI JSON, or an artifact with an `abi` field.
+    ///
+    /// Used to look up `--sig` by name, and to decode return data and custom errors. Without
+    /// `--sig`, the function is looked up from the calldata selector.
+    #[arg(long)]
+    abi: Option<PathBuf>,
     #[arg(long, default_value = "1000000000")]
     gas_limit: u64,
+
+    /// Load the function from a shared object file instead of JIT compiling.
+    ///
+    /// The input is then the name of the symbol to load if it is not a known benchmark.
+    #[arg(long)]
+    load: Option<PathBuf>,
+
+    /// Interpret the code instead of compiling.
+    #[arg(long, conflicts_with = "load")]
+    interpret: bool,
+
+    /// Execute a full transaction against the state loaded from the given JSON file, and print
+    /// the resulting state diff.
+    ///
+    /// The file maps addresses to accounts with optional `balance`, `nonce`, `code`, and
+    /// `storage` fields. Every contract in the state is compiled.
+    #[arg(long, conflicts_with = "load")]
+    state: Option<PathBuf>,
+
+    /// Halt the transaction once the compiled functions have executed approximately this many
+    /// instructions.
+    ///
+    /// This compiles the functions with execution limits, see `EvmCompiler::execution_limits`.
+    #[arg(long, requires = "state", conflicts_with = "interpret")]
+    instruction_budget: Option<u64>,
+
+    /// Run the code with both the interpreter and the JIT-compiled function, and report the first
+    /// difference in their results.
+    #[arg(long, conflicts_with_all = ["load", "interpret", "state"])]
+    diff: bool,
+
+    /// Print the result, compilation statistics and benchmark timings as JSON to stdout.
+    #[arg(long, conflicts_with_all = ["state", "diff"])]
+    json: bool,
+}
+
+#[derive(Args)]
+struct BenchArgs {
+    #[command(flatten)]
+    run: RunArgs,
+
+    /// The number of iterations.
+    #[arg(short = 'n', long, default_value = "1000")]
+    n_iters: u64,
+}
+
+#[derive(Args)]
+struct StatetestArgs {
+    /// Paths to test files, or directories to search for `.json` test files.
+    #[arg(required = true)]
+    paths: Vec<PathBuf>,
+    #[command(flatten)]
+    compiler: CompilerArgs,
+
+    /// Interpret the code instead of compiling.
+    #[arg(long)]
+    interpret: bool,
 }
 
 fn main() -> Result<()> {
@@ -86,60 +249,133 @@ fn main() -> Result<()> {
     let _ = color_eyre::install();
     let _ = init_tracing_subscriber();
 
-    let cli = Cli::parse();
+    match Cli::parse().command {
+        Command::Compile(args) => compile(args),
+        Command::Run(args) => run(args, None),
+        Command::Disasm(args) => disasm(args),
+        Command::Analyze(args) => parse(args, true),
+        Command::Cfg(args) => cfg(args),
+        Command::Bench(args) => run(args.run, Some(args.n_iters)),
+        Command::Statetest(args) => statetest(args),
+    }
+}
 
-    // Build the compiler.
+fn compile(args: CompileArgs) -> Result<()> {
     let context = revmc::llvm::inkwell::context::Context::create();
-    let target = revmc::Target::new(cli.target, cli.target_cpu, cli.target_features);
-    let backend = EvmLlvmBackend::new_for_target(&context, cli.aot, cli.opt_level, &target)?;
+    let mut compiler = args.compiler.build(&context, true)?;
+    let Bench { name, bytecode, stack_input, .. } = args.input.bench(false)?;
+    compiler.set_module_name(name);
+    compiler.validate_eof(!args.input.no_validate);
+    if !stack_input.is_empty() {
+        compiler.inspect_stack_length(true);
+    }
+    compiler.translate(name, &bytecode[..], args.input.spec_id())?;
+
+    let out_dir = if let Some(out_dir) = compiler.out_dir() {
+        out_dir.join(name)
+    } else {
+        let dir = std::env::temp_dir().join("revmc-cli").join(name);
+        std::fs::create_dir_all(&dir)?;
+        dir
+    };
+
+    // Compile.
+    let obj = out_dir.join("a.o");
+    compiler.write_object_to_file(&obj)?;
+    ensure!(obj.exists(), "Failed to write object file");
+    eprintln!("Compiled object file to {}", obj.display());
+
+    // Link.
+    if !args.no_link {
+        let so = out_dir.join("a.so");
+        let linker = revmc::Linker::new();
+        linker.link(&so, [obj.to_str().unwrap()])?;
+        ensure!(so.exists(), "Failed to link object file");
+        eprintln!("Linked shared object file to {}", so.display());
+    }
+
+    Ok(())
+}
+
+fn disasm(args: DisasmArgs) -> Result<()> {
+    if args.raw {
+        return parse(args.input, false);
+    }
+    let context = revmc::llvm::inkwell::context::Context::create();
+    let backend = EvmLlvmBackend::new(&context, false, OptimizationLevel::None)?;
     let mut compiler = EvmCompiler::new(backend);
-    compiler.set_dump_to(cli.out_dir);
-    compiler.gas_metering(!cli.no_gas);
-    unsafe { compiler.stack_bound_checks(!cli.no_len_checks) };
-    compiler.frame_pointers(true);
-    compiler.debug_assertions(cli.debug_assertions);
-    compiler.validate_eof(!cli.no_validate);
-
-    let Bench { name, bytecode, calldata, stack_input, native: _ } = if cli.bench_name == "custom" {
-        Bench {
-            name: "custom",
-            bytecode: read_code(cli.code.as_deref(), cli.code_path.as_deref())?,
-            ..Default::default()
-        }
-    } else if Path::new(&cli.bench_name).exists() {
-        let path = Path::new(&cli.bench_name);
-        ensure!(path.is_file(), "argument must be a file");
-        ensure!(cli.code.is_none(), "--code is not allowed with a file argument");
-        ensure!(cli.code_path.is_none(), "--code-path is not allowed with a file argument");
-        Bench {
-            name: path.file_stem().unwrap().to_str().unwrap().to_string().leak(),
-            bytecode: read_code(None, Some(path))?,
-            ..Default::default()
+    compiler.validate_eof(!args.input.no_validate);
+    let Bench { bytecode, .. } = args.input.bench(false)?;
+    let disassembly =
+        compiler.disassemble(EvmCompilerInput::Code(&bytecode), args.input.spec_id())?;
+    print!("{disassembly}");
+    Ok(())
+}
+
+fn cfg(args: CfgArgs) -> Result<()> {
+    let context = revmc::llvm::inkwell::context::Context::create();
+    let backend = EvmLlvmBackend::new(&context, false, OptimizationLevel::None)?;
+    let mut compiler = EvmCompiler::new(backend);
+    compiler.validate_eof(!args.input.no_validate);
+    let Bench { bytecode, .. } = args.input.bench(false)?;
+    let cfg =
+        compiler.control_flow_graph(EvmCompilerInput::Code(&bytecode), args.input.spec_id())?;
+
+    let mut out: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(io::BufWriter::new(std::fs::File::create(path)?)),
+        None => Box::new(io::stdout().lock()),
+    };
+    match args.format {
+        CfgFormat::Dot => cfg.write_dot(&mut out)?,
+        CfgFormat::Json => {
+            serde_json::to_writer_pretty(&mut out, &cfg)?;
+            writeln!(out)?;
         }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+fn parse(args: InputArgs, analyze: bool) -> Result<()> {
+    let context = revmc::llvm::inkwell::context::Context::create();
+    let backend = EvmLlvmBackend::new(&context, false, OptimizationLevel::None)?;
+    let mut compiler = EvmCompiler::new(backend);
+    compiler.validate_eof(!args.no_validate);
+    let Bench { bytecode, .. } = args.bench(false)?;
+    let bytecode = compiler.parse(EvmCompilerInput::Code(&bytecode), args.spec_id())?;
+    if analyze {
+        println!("{bytecode:#?}");
     } else {
-        match get_benches().into_iter().find(|b| b.name == cli.bench_name) {
-            Some(b) => b,
-            None => {
-                if cli.load.is_some() {
-                    Bench {
-                        name: cli.bench_name.clone().leak(),
-                        bytecode: Vec::new(),
-                        ..Default::default()
-                    }
-                } else {
-                    return Err(eyre!("unknown benchmark: {}", cli.bench_name));
-                }
-            }
-        }
-    };
+        print!("{bytecode}");
+    }
+    Ok(())
+}
+
+fn run(args: RunArgs, n_iters: Option<u64>) -> Result<()> {
+    let context = revmc::llvm::inkwell::context::Context::create();
+    let mut compiler = args.compiler.build(&context, false)?;
+    let Bench { name, bytecode, calldata, stack_input, native: _ } =
+        args.input.bench(args.load.is_some())?;
     compiler.set_module_name(name);
+    compiler.validate_eof(!args.input.no_validate);
 
-    let calldata = if let Some(calldata) = cli.calldata {
+    let mut abi = if args.sig.is_some() || args.abi.is_some() {
+        Some(Abi::new(args.sig.as_deref(), args.abi.as_deref())?)
+    } else {
+        None
+    };
+    let calldata: Bytes = if let Some(calldata) = &args.calldata {
         revmc::primitives::hex::decode(calldata)?.into()
+    } else if let (Some(abi), Some(_)) = (&abi, &args.sig) {
+        abi.encode_calldata(&args.args)?.into()
     } else {
         calldata.into()
     };
-    let gas_limit = cli.gas_limit;
+    if let Some(abi) = &mut abi {
+        abi.resolve_function(&calldata);
+    }
+    let abi = abi.as_ref();
+    let gas_limit = args.gas_limit;
 
     let mut env = Env::default();
     env.tx.caller = address!("0000000000000000000000000000000000000001");
@@ -147,6 +383,11 @@ fn main() -> Result<()> {
     env.tx.data = calldata;
     env.tx.gas_limit = gas_limit;
 
+    let spec_id = args.input.spec_id();
+    if let Some(state) = &args.state {
+        return run_with_state(&mut compiler, &args, state, env, &bytecode, abi, n_iters);
+    }
+
     let bytecode = revm_interpreter::analysis::to_analysed(revm_primitives::Bytecode::new_raw(
         revm_primitives::Bytes::copy_from_slice(&bytecode),
     ));
@@ -155,78 +396,42 @@ fn main() -> Result<()> {
 
     let bytecode = contract.bytecode.original_byte_slice();
 
-    let spec_id = if cli.eof { SpecId::OSAKA } else { cli.spec_id.into() };
-    if !stack_input.is_empty() {
+    if !stack_input.is_empty() || args.diff {
         compiler.inspect_stack_length(true);
     }
 
-    if cli.parse_only {
-        let _ = compiler.parse(bytecode.into(), spec_id)?;
-        return Ok(());
-    }
-
-    let f_id = compiler.translate(name, bytecode, spec_id)?;
-
-    let mut load = cli.load;
-    if cli.aot {
-        let out_dir = if let Some(out_dir) = compiler.out_dir() {
-            out_dir.join(cli.bench_name)
-        } else {
-            let dir = std::env::temp_dir().join("revmc-cli").join(cli.bench_name);
-            std::fs::create_dir_all(&dir)?;
-            dir
-        };
-
-        // Compile.
-        let obj = out_dir.join("a.o");
-        compiler.write_object_to_file(&obj)?;
-        ensure!(obj.exists(), "Failed to write object file");
-        eprintln!("Compiled object file to {}", obj.display());
-
-        // Link.
-        if !cli.no_link {
-            let so = out_dir.join("a.so");
-            let linker = revmc::Linker::new();
-            linker.link(&so, [obj.to_str().unwrap()])?;
-            ensure!(so.exists(), "Failed to link object file");
-            eprintln!("Linked shared object file to {}", so.display());
-        }
-
-        // Fall through to loading the library below if requested.
-        if let Some(load @ None) = &mut load {
-            *load = Some(out_dir.join("a.so"));
-        } else {
-            return Ok(());
-        }
-    }
-
     let lib;
-    let f = if let Some(load) = load {
-        if let Some(load) = load {
-            lib = unsafe { libloading::Library::new(load) }?;
-            let f: libloading::Symbol<'_, revmc::EvmCompilerFn> =
-                unsafe { lib.get(name.as_bytes())? };
-            *f
-        } else {
-            return Err(eyre!("--load with no argument requires --aot"));
-        }
+    let mut compile_report = None;
+    let f = if args.interpret {
+        None
+    } else if let Some(load) = &args.load {
+        lib = unsafe { libloading::Library::new(load) }?;
+        let f: libloading::Symbol<'_, revmc::EvmCompilerFn> = unsafe { lib.get(name.as_bytes())? };
+        Some(*f)
     } else {
-        unsafe { compiler.jit_function(f_id)? }
+        let f_id = compiler.translate(name, bytecode, spec_id)?;
+        let f = unsafe { compiler.jit_function(f_id)? };
+        if args.json {
+            let code_size = compiler.function_size(f_id)?;
+            compile_report = Some(CompileReport::new(compiler.timings(), code_size));
+        }
+        Some(f)
     };
 
     #[allow(unused_parens)]
     let table = spec_to_generic!(spec_id, (const { &make_instruction_table::<_, SPEC>() }));
-    let mut run = |f: revmc::EvmCompilerFn| {
+    if args.diff {
+        return run_diff(f.unwrap(), &contract, host.env, gas_limit, &stack_input, table, abi);
+    }
+
+    let run = |host: &mut DummyHost| {
         let mut interpreter =
             revm_interpreter::Interpreter::new(contract.clone(), gas_limit, false);
         host.clear();
 
-        if cli.interpret {
-            let action = interpreter.run(SharedMemory::new(), table, &mut host);
-            (interpreter.instruction_result, action)
-        } else {
+        if let Some(f) = f {
             let (mut ecx, stack, stack_len) =
-                EvmContext::from_interpreter_with_stack(&mut interpreter, &mut host);
+                EvmContext::from_interpreter_with_stack(&mut interpreter, host);
 
             for (i, input) in stack_input.iter().enumerate() {
                 stack.as_mut_slice()[i] = input.into();
@@ -234,38 +439,310 @@ fn main() -> Result<()> {
             *stack_len = stack_input.len();
 
             let r = unsafe { f.call_noinline(Some(stack), Some(stack_len), &mut ecx) };
-            (r, interpreter.next_action)
+            (r, interpreter.next_action, interpreter.gas)
+        } else {
+            let action = interpreter.run(SharedMemory::new(), table, host);
+            (interpreter.instruction_result, action, interpreter.gas)
         }
     };
 
-    if cli.n_iters == 0 {
-        return Ok(());
+    let (ret, action, gas) = run(&mut host);
+    if !args.json {
+        println!("InstructionResult::{ret:?}");
+        println!("InterpreterAction::{action:#?}");
+        print_decoded_action(abi, &action);
+    }
+
+    let logs = host.log.iter().map(Into::into).collect();
+    let bench_stats = n_iters.map(|n_iters| bench(n_iters, || run(&mut host)));
+    if args.json {
+        let output = match &action {
+            InterpreterAction::Return { result } => result.output.clone(),
+            _ => Bytes::new(),
+        };
+        let report = RunReport {
+            name: name.to_string(),
+            result: format!("{ret:?}"),
+            gas_used: gas.spent(),
+            gas_refunded: gas.refunded(),
+            output,
+            logs,
+            compile: compile_report,
+            bench: bench_stats,
+        };
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else if let Some(stats) = bench_stats {
+        eprintln!("{name}: {stats}");
+    }
+
+    Ok(())
+}
+
+fn run_diff(
+    f: revmc::EvmCompilerFn,
+    contract: &revm_interpreter::Contract,
+    env: Env,
+    gas_limit: u64,
+    stack_input: &[U256],
+    table: &InstructionTable<DummyHost>,
+    abi: Option<&Abi>,
+) -> Result<()> {
+    // Interpreter.
+    let mut interpreter = Interpreter::new(contract.clone(), gas_limit, false);
+    let mut host = DummyHost::new(env.clone());
+    for &input in stack_input {
+        interpreter.stack.push(input).map_err(|_| eyre!("stack input is too large"))?;
+    }
+    let action = interpreter.run(SharedMemory::new(), table, &mut host);
+    let interpreted = ExecutionSnapshot::new(&interpreter, action, &host.log);
+
+    // Compiled.
+    let mut interpreter = Interpreter::new(contract.clone(), gas_limit, false);
+    let mut host = DummyHost::new(env);
+    let (mut ecx, stack, stack_len) =
+        EvmContext::from_interpreter_with_stack(&mut interpreter, &mut host);
+    for (i, input) in stack_input.iter().enumerate() {
+        stack.as_mut_slice()[i] = input.into();
     }
+    *stack_len = stack_input.len();
+    interpreter.instruction_result =
+        unsafe { f.call_noinline(Some(stack), Some(stack_len), &mut ecx) };
+    let action = std::mem::take(&mut interpreter.next_action);
+    let compiled = ExecutionSnapshot::new(&interpreter, action, &host.log);
+
+    match interpreted.first_divergence(&compiled) {
+        Some(divergence) => {
+            println!("{divergence}");
+            Err(eyre!("interpreter and compiled execution diverged"))
+        }
+        None => {
+            println!("no divergence");
+            println!("InstructionResult::{:?}", compiled.result);
+            println!("InterpreterAction::{:#?}", compiled.action);
+            print_decoded_action(abi, &compiled.action);
+            Ok(())
+        }
+    }
+}
 
-    let (ret, action) = run(f);
-    println!("InstructionResult::{ret:?}");
-    println!("InterpreterAction::{action:#?}");
+fn run_with_state(
+    compiler: &mut EvmCompiler<EvmLlvmBackend<'_>>,
+    args: &RunArgs,
+    state: &Path,
+    env: Env,
+    bytecode: &[u8],
+    abi: Option<&Abi>,
+    n_iters: Option<u64>,
+) -> Result<()> {
+    let spec_id = args.input.spec_id();
+    let mut db = state_db(&read_state(state)?)?;
+    let address = *env.tx.transact_to.to().unwrap();
+    set_code(&mut db, address, Bytes::copy_from_slice(bytecode))?;
+
+    let mut functions = if args.interpret {
+        CompiledFunctions::default()
+    } else {
+        compiler.execution_limits(args.instruction_budget.is_some());
+        unsafe { CompiledFunctions::jit_all(compiler, &db, spec_id)? }
+    };
+    let limits = args.instruction_budget.map(|budget| {
+        let limits = Arc::new(ExecutionLimits::with_instruction_budget(budget));
+        functions.set_limits(Some(limits.clone()));
+        (limits, budget)
+    });
+    let mut evm = build_evm(db, functions, spec_id);
+    *evm.context.evm.env = env;
+
+    let ResultAndState { result, state } = evm.transact().map_err(|e| eyre!("{e}"))?;
+    print_result(&result);
+    if let (Some(abi), Some(output)) = (abi, result.output()) {
+        if let Some(decoded) = abi.decode_output(result.is_success(), output) {
+            println!("{decoded}");
+        }
+    }
+    print_state_diff(evm.db(), &state);
 
-    if cli.n_iters > 1 {
-        bench(cli.n_iters, name, || run(f));
-        return Ok(());
+    if let Some(n_iters) = n_iters {
+        let stats = bench(n_iters, || {
+            if let Some((limits, budget)) = &limits {
+                limits.reset(*budget);
+            }
+            evm.transact()
+        });
+        eprintln!("{}: {stats}", args.input.input);
     }
 
     Ok(())
 }
 
-fn bench<T>(n_iters: u64, name: &str, mut f: impl FnMut() -> T) {
+fn statetest(args: StatetestArgs) -> Result<()> {
+    let context = revmc::llvm::inkwell::context::Context::create();
+    let mut compiler = args.compiler.build(&context, false)?;
+    let mut report = StateTestReport::default();
+    for path in &args.paths {
+        for entry in walkdir::WalkDir::new(path).sort_by_file_name() {
+            let entry = entry?;
+            let path = entry.path();
+            if !entry.file_type().is_file() || path.extension() != Some("json".as_ref()) {
+                continue;
+            }
+            if let Err(e) = run_state_test_file(path, &mut compiler, args.interpret, &mut report) {
+                report.failures.push(format!("{}: {e}", path.display()));
+                unsafe { compiler.clear()? };
+            }
+        }
+    }
+    print!("{report}");
+    ensure!(report.is_success(), "{} tests failed", report.failures.len());
+    Ok(())
+}
+
+fn print_result(result: &ExecutionResult) {
+    match result {
+        ExecutionResult::Success { reason, .. } => println!("status: success ({reason:?})"),
+        ExecutionResult::Revert { .. } => println!("status: revert"),
+        ExecutionResult::Halt { reason, .. } => println!("status: halt ({reason:?})"),
+    }
+    println!("gas used: {}", result.gas_used());
+    if let Some(output) = result.output() {
+        println!("output: {output}");
+    }
+    for (i, log) in result.logs().iter().enumerate() {
+        println!("log {i}: {}", log.address);
+        for topic in log.data.topics() {
+            println!("  topic: {topic}");
+        }
+        println!("  data: {}", log.data.data);
+    }
+}
+
+fn print_decoded_action(abi: Option<&Abi>, action: &InterpreterAction) {
+    let (Some(abi), InterpreterAction::Return { result }) = (abi, action) else { return };
+    if let Some(decoded) = abi.decode_output(result.result.is_ok(), &result.output) {
+        println!("{decoded}");
+    }
+}
+
+fn print_state_diff(db: &StateDb, state: &EvmState) {
+    let mut accounts = state.iter().filter(|(_, account)| account.is_touched()).collect::<Vec<_>>();
+    accounts.sort_by_key(|(address, _)| *address);
+    println!("state diff:");
+    for (address, account) in accounts {
+        let old = db.accounts.get(address).map(|a| a.info.clone()).unwrap_or_default();
+        let new = &account.info;
+        let mut storage =
+            account.storage.iter().filter(|(_, slot)| slot.is_changed()).collect::<Vec<_>>();
+        storage.sort_by_key(|(key, _)| *key);
+        if old.balance == new.balance
+            && old.nonce == new.nonce
+            && old.code_hash == new.code_hash
+            && storage.is_empty()
+        {
+            continue;
+        }
+
+        println!("  {address}:");
+        if old.balance != new.balance {
+            println!("    balance: {} -> {}", old.balance, new.balance);
+        }
+        if old.nonce != new.nonce {
+            println!("    nonce: {} -> {}", old.nonce, new.nonce);
+        }
+        if old.code_hash != new.code_hash {
+            println!("    code hash: {} -> {}", old.code_hash, new.code_hash);
+        }
+        for (key, slot) in storage {
+            println!("    {key:#x}: {:#x} -> {:#x}", slot.original_value, slot.present_value);
+        }
+    }
+}
+
+impl InputArgs {
+    /// Resolves the input to a benchmark.
+    ///
+    /// If `allow_symbol` is true, an unknown input is treated as the name of a symbol to load.
+    fn bench(&self, allow_symbol: bool) -> Result<Bench> {
+        if self.input == "custom" {
+            return Ok(Bench {
+                name: "custom",
+                bytecode: read_code(self.code.as_deref(), self.code_path.as_deref())?,
+                ..Default::default()
+            });
+        }
+
+        let path = Path::new(&self.input);
+        if path.exists() {
+            ensure!(path.is_file(), "argument must be a file");
+            ensure!(self.code.is_none(), "--code is not allowed with a file argument");
+            ensure!(self.code_path.is_none(), "--code-path is not allowed with a file argument");
+            return Ok(Bench {
+                name: path.file_stem().unwrap().to_str().unwrap().to_string().leak(),
+                bytecode: read_code(None, Some(path))?,
+                ..Default::default()
+            });
+        }
+
+        match get_benches().into_iter().find(|b| b.name == self.input) {
+            Some(b) => Ok(b),
+            None if allow_symbol => {
+                Ok(Bench { name: self.input.clone().leak(), ..Default::default() })
+            }
+            None => Err(eyre!("unknown benchmark: {}", self.input)),
+        }
+    }
+
+    fn spec_id(&self) -> SpecId {
+        if self.eof {
+            SpecId::OSAKA
+        } else {
+            self.spec_id.into()
+        }
+    }
+}
+
+impl CompilerArgs {
+    fn build<'ctx>(
+        &self,
+        context: &'ctx revmc::llvm::inkwell::context::Context,
+        aot: bool,
+    ) -> Result<EvmCompiler<EvmLlvmBackend<'ctx>>> {
+        let target = revmc::Target::new(
+            self.target.clone(),
+            self.target_cpu.clone(),
+            self.target_features.clone(),
+        );
+        let mut backend = EvmLlvmBackend::new_for_target(context, aot, self.opt_level, &target)?;
+        backend.set_pass_pipeline(self.passes.clone());
+        backend.set_time_passes(self.time_passes);
+        let mut compiler = EvmCompiler::new(backend);
+        compiler.set_dump_to(self.out_dir.clone());
+        compiler.gas_metering(!self.no_gas);
+        unsafe { compiler.stack_bound_checks(!self.no_len_checks) };
+        compiler.frame_pointers(true);
+        compiler.debug_assertions(self.debug_assertions);
+        compiler.debug_info(self.debug_info);
+        compiler.perf_map(self.perf_map);
+        compiler.jitdump(self.jitdump);
+        compiler.gdb_jit_registration(self.gdb);
+        Ok(compiler)
+    }
+}
+
+fn bench<T>(n_iters: u64, mut f: impl FnMut() -> T) -> BenchStats {
     let warmup = (n_iters / 10).max(10);
     for _ in 0..warmup {
         black_box(f());
     }
 
-    let t = std::time::Instant::now();
+    let mut samples = Vec::with_capacity(n_iters as usize);
+    let t = Instant::now();
     for _ in 0..n_iters {
+        let t = Instant::now();
         black_box(f());
+        samples.push(t.elapsed());
     }
     let d = t.elapsed();
-    eprintln!("{name}: {:>9?} ({d:>12?} / {n_iters})", d / n_iters as u32);
+    BenchStats::new(d, &mut samples)
 }
 
 fn init_tracing_subscriber() -> Result<(), tracing_subscriber::util::TryInitError> {
@@ -0,0 +1,148 @@
+use revm_primitives::{Address, Bytes, Log, B256};
+use revmc::CompileTimings;
+use serde::Serialize;
+use std::{fmt, time::Duration};
+
+/// The result of a single run, printed with `--json`.
+#[derive(Clone, Debug, Serialize)]
+pub struct RunReport {
+    pub name: String,
+    /// The `InstructionResult` of the run.
+    pub result: String,
+    pub gas_used: u64,
+    pub gas_refunded: i64,
+    /// The return data, if the run returned.
+    pub output: Bytes,
+    pub logs: Vec<LogReport>,
+    /// Compilation statistics, if the code was JIT-compiled.
+    pub compile: Option<CompileReport>,
+    /// Timing statistics, if the code was benchmarked.
+    pub bench: Option<BenchStats>,
+}
+
+/// A log emitted during a run.
+#[derive(Clone, Debug, Serialize)]
+pub struct LogReport {
+    pub address: Address,
+    pub topics: Vec<B256>,
+    pub data: Bytes,
+}
+
+impl From<&Log> for LogReport {
+    fn from(log: &Log) -> Self {
+        Self { address: log.address, topics: log.topics().to_vec(), data: log.data.data.clone() }
+    }
+}
+
+/// Compilation statistics.
+#[derive(Clone, Debug, Serialize)]
+pub struct CompileReport {
+    pub parse_ns: u64,
+    pub translate_ns: u64,
+    pub verify_ns: u64,
+    pub optimize_ns: u64,
+    pub codegen_ns: u64,
+    pub total_ns: u64,
+    /// The size of the machine code of the function in bytes, if known.
+    pub code_size: Option<usize>,
+}
+
+impl CompileReport {
+    pub fn new(timings: CompileTimings, code_size: Option<usize>) -> Self {
+        Self {
+            parse_ns: nanos(timings.parse),
+            translate_ns: nanos(timings.translate),
+            verify_ns: nanos(timings.verify),
+            optimize_ns: nanos(timings.optimize),
+            codegen_ns: nanos(timings.codegen),
+            total_ns: nanos(timings.total()),
+            code_size,
+        }
+    }
+}
+
+/// Timing statistics over the iterations of a benchmark.
+///
+/// The mean is measured over all iterations at once, while the other statistics are computed from
+/// individually timed iterations, which include the overhead of reading the clock.
+#[derive(Clone, Debug, Serialize)]
+pub struct BenchStats {
+    pub iterations: u64,
+    pub total_ns: u64,
+    pub mean_ns: u64,
+    pub median_ns: u64,
+    pub min_ns: u64,
+    pub max_ns: u64,
+    pub stddev_ns: u64,
+}
+
+impl BenchStats {
+    /// Computes the statistics from the total duration and the individual iteration durations.
+    pub fn new(total: Duration, samples: &mut [Duration]) -> Self {
+        let iterations = samples.len() as u64;
+        if samples.is_empty() {
+            return Self {
+                iterations,
+                total_ns: nanos(total),
+                mean_ns: 0,
+                median_ns: 0,
+                min_ns: 0,
+                max_ns: 0,
+                stddev_ns: 0,
+            };
+        }
+
+        samples.sort_unstable();
+        let mean = total.as_nanos() as f64 / iterations as f64;
+        let variance = samples
+            .iter()
+            .map(|sample| {
+                let diff = sample.as_nanos() as f64 - mean;
+                diff * diff
+            })
+            .sum::<f64>()
+            / iterations as f64;
+        Self {
+            iterations,
+            total_ns: nanos(total),
+            mean_ns: mean as u64,
+            median_ns: nanos(samples[samples.len() / 2]),
+            min_ns: nanos(samples[0]),
+            max_ns: nanos(samples[samples.len() - 1]),
+            stddev_ns: variance.sqrt() as u64,
+        }
+    }
+
+    /// Returns the mean duration of an iteration.
+    pub fn mean(&self) -> Duration {
+        Duration::from_nanos(self.mean_ns)
+    }
+}
+
+impl fmt::Display for BenchStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total = Duration::from_nanos(self.total_ns);
+        write!(f, "{:>9?} ({total:>12?} / {})", self.mean(), self.iterations)
+    }
+}
+
+fn nanos(d: Duration) -> u64 {
+    d.as_nanos().try_into().unwrap_or(u64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bench_stats() {
+        let mut samples = [4, 1, 3, 2, 10].map(Duration::from_nanos);
+        let stats = BenchStats::new(Duration::from_nanos(20), &mut samples);
+        assert_eq!(stats.iterations, 5);
+        assert_eq!(stats.mean_ns, 4);
+        assert_eq!(stats.median_ns, 3);
+        assert_eq!(stats.min_ns, 1);
+        assert_eq!(stats.max_ns, 10);
+        assert_eq!(stats.stddev_ns, 3);
+    }
+}
@@ -0,0 +1,202 @@
+use alloy_primitives::U64;
+use revm::{
+    db::{CacheDB, EmptyDB},
+    handler::register::EvmHandler,
+    primitives::{Address, Bytecode, Bytes, EVMError, HashMap, SpecId, B256, U256},
+    Database,
+};
+use revmc::{
+    eyre::{eyre, Result, WrapErr},
+    Backend, EvmCompiler, EvmCompilerFn, ExecutionLimits,
+};
+use serde::Deserialize;
+use std::{collections::BTreeMap, path::Path, sync::Arc};
+
+/// The in-memory database used to run against a state snapshot.
+pub type StateDb = CacheDB<EmptyDB>;
+
+/// A state snapshot, mapping addresses to accounts.
+pub type State = BTreeMap<Address, AccountState>;
+
+/// An account in a state snapshot.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct AccountState {
+    pub balance: U256,
+    pub nonce: U64,
+    pub code: Bytes,
+    pub storage: BTreeMap<U256, U256>,
+}
+
+/// Reads a state snapshot from a JSON file.
+pub fn read_state(path: &Path) -> Result<State> {
+    let contents = std::fs::read_to_string(path)?;
+    serde_json::from_str(&contents)
+        .wrap_err_with(|| format!("failed to parse state file {}", path.display()))
+}
+
+/// Creates a new database populated with the given state.
+pub fn state_db(state: &State) -> Result<StateDb> {
+    let mut db = StateDb::new(EmptyDB::new());
+    for (&address, account) in state {
+        set_code(&mut db, address, account.code.clone())?;
+        let db_account = db.accounts.get_mut(&address).unwrap();
+        db_account.info.balance = account.balance;
+        db_account.info.nonce = account.nonce.to();
+        for (&slot, &value) in &account.storage {
+            db.insert_account_storage(address, slot, value).unwrap();
+        }
+    }
+    Ok(db)
+}
+
+/// Sets the code of the given account, keeping its balance, nonce, and storage.
+pub fn set_code(db: &mut StateDb, address: Address, code: Bytes) -> Result<()> {
+    let code = Bytecode::new_raw_checked(code)
+        .map_err(|e| eyre!("invalid code for account {address}: {e:?}"))?;
+    let mut info = db.accounts.get(&address).map(|a| a.info.clone()).unwrap_or_default();
+    info.code_hash = code.hash_slow();
+    info.code = Some(code);
+    db.insert_account_info(address, info);
+    Ok(())
+}
+
+/// Compiled functions, keyed by bytecode hash.
+///
+/// Used as the external context of an EVM with [`register_handler`].
+#[derive(Clone, Debug, Default)]
+pub struct CompiledFunctions {
+    functions: HashMap<B256, EvmCompilerFn>,
+    limits: Option<Arc<ExecutionLimits>>,
+}
+
+impl CompiledFunctions {
+    /// JIT-compiles the code of every account in the database.
+    ///
+    /// # Safety
+    ///
+    /// The returned functions must not be called after the compiler's module is cleared.
+    pub unsafe fn jit_all<B: Backend>(
+        compiler: &mut EvmCompiler<B>,
+        db: &StateDb,
+        spec_id: SpecId,
+    ) -> Result<Self> {
+        let mut ids = Vec::new();
+        for account in db.accounts.values() {
+            let Some(code) = &account.info.code else { continue };
+            let hash = account.info.code_hash;
+            if code.is_empty() || ids.iter().any(|&(h, _)| h == hash) {
+                continue;
+            }
+            let id = compiler.translate(&hash.to_string(), code.original_byte_slice(), spec_id)?;
+            ids.push((hash, id));
+        }
+
+        let mut functions = HashMap::default();
+        for (hash, id) in ids {
+            functions.insert(hash, unsafe { compiler.jit_function(id)? });
+        }
+        Ok(Self { functions, limits: None })
+    }
+
+    /// Returns the compiled function for the given bytecode hash.
+    pub fn get(&self, bytecode_hash: B256) -> Option<EvmCompilerFn> {
+        self.functions.get(&bytecode_hash).copied()
+    }
+
+    /// Sets the execution limits checked by the compiled functions.
+    ///
+    /// The functions must have been compiled with [`EvmCompiler::execution_limits`] enabled.
+    pub fn set_limits(&mut self, limits: Option<Arc<ExecutionLimits>>) {
+        self.limits = limits;
+    }
+
+    /// Returns the execution limits checked by the compiled functions.
+    pub fn limits(&self) -> Option<&Arc<ExecutionLimits>> {
+        self.limits.as_ref()
+    }
+}
+
+/// Registers a handler that executes frames with the compiled functions, if any.
+///
+/// If the [execution limits](CompiledFunctions::set_limits) are exceeded, the transaction fails
+/// with [`EVMError::Custom`].
+pub fn register_handler<DB: Database + 'static>(
+    handler: &mut EvmHandler<'_, CompiledFunctions, DB>,
+) {
+    let prev = handler.execution.execute_frame.clone();
+    handler.execution.execute_frame = Arc::new(move |frame, memory, tables, context| {
+        let interpreter = frame.interpreter_mut();
+        let bytecode_hash = interpreter.contract.hash.unwrap_or_default();
+        if let Some(f) = context.external.get(bytecode_hash) {
+            let Some(limits) = context.external.limits.clone() else {
+                return Ok(unsafe {
+                    f.call_with_interpreter_and_memory(interpreter, memory, context)
+                });
+            };
+            unsafe { f.call_with_limits(interpreter, memory, context, &limits) }
+                .map_err(|reason| EVMError::Custom(format!("execution halted: {reason}")))
+        } else {
+            prev(frame, memory, tables, context)
+        }
+    });
+}
+
+/// Builds a [`revm::Evm`] over the given database which executes compiled functions.
+pub fn build_evm<'a>(
+    db: StateDb,
+    functions: CompiledFunctions,
+    spec_id: SpecId,
+) -> revm::Evm<'a, CompiledFunctions, StateDb> {
+    revm::Evm::builder()
+        .with_db(db)
+        .with_external_context(functions)
+        .with_spec_id(spec_id)
+        .append_handler_register(register_handler)
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use revm::{
+        interpreter::opcode as op,
+        primitives::{address, TransactTo},
+    };
+
+    #[test]
+    #[cfg(feature = "llvm")]
+    fn limits_exceeded() {
+        let cx = revmc::llvm::inkwell::context::Context::create();
+        let backend =
+            revmc::EvmLlvmBackend::new(&cx, false, revmc::OptimizationLevel::None).unwrap();
+        let mut compiler = EvmCompiler::new(backend);
+        compiler.gas_metering(false);
+        compiler.execution_limits(true);
+
+        let address = address!("00000000000000000000000000000000deadbeef");
+        let mut db = StateDb::new(EmptyDB::new());
+        let code = Bytes::from_static(&[op::JUMPDEST, op::PUSH0, op::JUMP]);
+        set_code(&mut db, address, code).unwrap();
+        let mut functions =
+            unsafe { CompiledFunctions::jit_all(&mut compiler, &db, SpecId::CANCUN) }.unwrap();
+        let limits = Arc::new(ExecutionLimits::with_instruction_budget(1_000));
+        functions.set_limits(Some(limits.clone()));
+
+        let mut evm = build_evm(db, functions, SpecId::CANCUN);
+        evm.context.evm.env.tx.transact_to = TransactTo::Call(address);
+        match evm.transact() {
+            Err(EVMError::Custom(e)) => {
+                assert_eq!(e, "execution halted: instruction budget exhausted")
+            }
+            r => panic!("unexpected result: {r:?}"),
+        }
+
+        limits.reset(1_000);
+        limits.cancel();
+        match evm.transact() {
+            Err(EVMError::Custom(e)) => assert_eq!(e, "execution halted: execution cancelled"),
+            r => panic!("unexpected result: {r:?}"),
+        }
+    }
+}
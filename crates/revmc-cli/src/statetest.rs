@@ -0,0 +1,459 @@
+//! Ethereum `GeneralStateTests` runner.
+//!
+//! See <https://ethereum-tests.readthedocs.io/en/latest/state-tests/index.html>.
+
+use crate::{build_evm, state_db, CompiledFunctions, State, StateDb};
+use alloy_rlp::RlpEncodable;
+use alloy_trie::{HashBuilder, Nibbles};
+use revm::{
+    db::AccountState as DbAccountState,
+    primitives::{
+        keccak256, AccessList, Address, Bytes, Env, ExecutionResult, Log, SpecId, TxKind, B256,
+        U256,
+    },
+    DatabaseCommit,
+};
+use revmc::{eyre::Result, Backend, EvmCompiler};
+use serde::{Deserialize, Deserializer};
+use std::{collections::BTreeMap, fmt, path::Path};
+
+/// A state test file, which may contain multiple tests.
+pub type StateTestFile = BTreeMap<String, StateTest>;
+
+/// A single state test.
+#[derive(Debug, Deserialize)]
+pub struct StateTest {
+    pub env: TestEnv,
+    pub pre: State,
+    pub post: BTreeMap<String, Vec<PostState>>,
+    pub transaction: TestTransaction,
+}
+
+/// The block environment of a state test.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TestEnv {
+    pub current_coinbase: Address,
+    #[serde(default)]
+    pub current_difficulty: U256,
+    pub current_gas_limit: U256,
+    pub current_number: U256,
+    pub current_timestamp: U256,
+    pub current_base_fee: Option<U256>,
+    pub current_random: Option<B256>,
+    pub current_excess_blob_gas: Option<U256>,
+}
+
+/// The expected post-state for a single combination of transaction fields.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PostState {
+    pub hash: B256,
+    pub logs: B256,
+    pub indexes: TxIndexes,
+    pub expect_exception: Option<String>,
+}
+
+/// Indexes into the transaction fields with multiple values.
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct TxIndexes {
+    pub data: usize,
+    pub gas: usize,
+    pub value: usize,
+}
+
+/// The transaction of a state test, with multiple values for some fields.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TestTransaction {
+    pub data: Vec<Bytes>,
+    pub gas_limit: Vec<U256>,
+    pub gas_price: Option<U256>,
+    pub nonce: U256,
+    pub sender: Option<Address>,
+    #[serde(deserialize_with = "deserialize_to")]
+    pub to: Option<Address>,
+    pub value: Vec<U256>,
+    pub max_fee_per_gas: Option<U256>,
+    pub max_priority_fee_per_gas: Option<U256>,
+    #[serde(default)]
+    pub access_lists: Vec<Option<AccessList>>,
+    #[serde(default)]
+    pub blob_versioned_hashes: Vec<B256>,
+    pub max_fee_per_blob_gas: Option<U256>,
+    /// Not supported; tests with an authorization list are skipped.
+    pub authorization_list: Option<serde_json::Value>,
+}
+
+/// The results of running state tests, per fork.
+#[derive(Debug, Default)]
+pub struct StateTestReport {
+    /// The number of passed, failed, and skipped tests, per fork.
+    pub forks: BTreeMap<String, ForkReport>,
+    /// The failure messages.
+    pub failures: Vec<String>,
+}
+
+/// The results of running state tests for a single fork.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ForkReport {
+    pub passed: usize,
+    pub failed: usize,
+    pub skipped: usize,
+}
+
+impl StateTestReport {
+    /// Returns `true` if no tests failed.
+    pub fn is_success(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+impl fmt::Display for StateTestReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for failure in &self.failures {
+            writeln!(f, "FAILED: {failure}")?;
+        }
+        for (fork, ForkReport { passed, failed, skipped }) in &self.forks {
+            writeln!(f, "{fork}: {passed} passed, {failed} failed, {skipped} skipped")?;
+        }
+        Ok(())
+    }
+}
+
+/// The fork that failures are counted in when the forks of a test are unknown.
+pub const UNKNOWN_FORK: &str = "unknown";
+
+/// Runs all the state tests in the given file.
+///
+/// Every contract in the pre-state is JIT-compiled with `compiler`, unless `interpret` is set.
+pub fn run_state_test_file<B: Backend>(
+    path: &Path,
+    compiler: &mut EvmCompiler<B>,
+    interpret: bool,
+    report: &mut StateTestReport,
+) -> Result<()> {
+    let contents = std::fs::read_to_string(path)?;
+    let file: BTreeMap<String, serde_json::Value> = match serde_json::from_str(&contents) {
+        Ok(file) => file,
+        Err(e) => {
+            report.forks.entry(UNKNOWN_FORK.to_string()).or_default().failed += 1;
+            report.failures.push(format!("{}: {e}", path.display()));
+            return Ok(());
+        }
+    };
+    for (name, test) in file {
+        let test = match StateTest::deserialize(&test) {
+            Ok(test) => test,
+            Err(e) => {
+                // Count every expected post-state as failed, as the test would have been run for
+                // each of them.
+                let forks = test.get("post").and_then(|post| post.as_object());
+                for (fork, posts) in forks.into_iter().flatten() {
+                    let count = posts.as_array().map_or(1, Vec::len);
+                    report.forks.entry(fork.clone()).or_default().failed += count;
+                }
+                if forks.map_or(true, |forks| forks.is_empty()) {
+                    report.forks.entry(UNKNOWN_FORK.to_string()).or_default().failed += 1;
+                }
+                report.failures.push(format!("{}: {name}: {e}", path.display()));
+                continue;
+            }
+        };
+        for (fork, posts) in &test.post {
+            let fork_report = report.forks.entry(fork.clone()).or_default();
+            let Some(spec_id) = fork_spec_id(fork) else {
+                fork_report.skipped += posts.len();
+                continue;
+            };
+            if test.transaction.authorization_list.is_some() {
+                fork_report.skipped += posts.len();
+                continue;
+            }
+
+            let functions = if interpret {
+                CompiledFunctions::default()
+            } else {
+                let db = match state_db(&test.pre) {
+                    Ok(db) => db,
+                    Err(e) => {
+                        fork_report.failed += posts.len();
+                        let path = path.display();
+                        report.failures.push(format!("{path}: {name}/{fork}: pre-state: {e}"));
+                        continue;
+                    }
+                };
+                match unsafe { CompiledFunctions::jit_all(compiler, &db, spec_id) } {
+                    Ok(functions) => functions,
+                    Err(e) => {
+                        fork_report.failed += posts.len();
+                        let path = path.display();
+                        report.failures.push(format!("{path}: {name}/{fork}: compilation: {e}"));
+                        unsafe { compiler.clear()? };
+                        continue;
+                    }
+                }
+            };
+            for (i, post) in posts.iter().enumerate() {
+                match run_post_state(&test, post, spec_id, functions.clone()) {
+                    Ok(()) => fork_report.passed += 1,
+                    Err(e) => {
+                        fork_report.failed += 1;
+                        report.failures.push(format!("{}: {name}/{fork}/{i}: {e}", path.display()));
+                    }
+                }
+            }
+            unsafe { compiler.clear()? };
+        }
+    }
+    Ok(())
+}
+
+fn run_post_state(
+    test: &StateTest,
+    post: &PostState,
+    spec_id: SpecId,
+    functions: CompiledFunctions,
+) -> Result<(), String> {
+    let env = test_env(test, post.indexes, spec_id)?;
+    let db = state_db(&test.pre).map_err(|e| e.to_string())?;
+    let mut evm = build_evm(db, functions, spec_id);
+    *evm.context.evm.env = env;
+
+    let logs = match evm.transact() {
+        Ok(_) if post.expect_exception.is_some() => {
+            return Err(format!(
+                "expected exception {:?}, but the transaction succeeded",
+                post.expect_exception.as_deref().unwrap()
+            ));
+        }
+        Ok(result) => {
+            evm.db_mut().commit(result.state);
+            match result.result {
+                ExecutionResult::Success { logs, .. } => logs,
+                ExecutionResult::Revert { .. } | ExecutionResult::Halt { .. } => Vec::new(),
+            }
+        }
+        Err(_) if post.expect_exception.is_some() => return Ok(()),
+        Err(e) => return Err(format!("unexpected error: {e}")),
+    };
+
+    let logs_hash = logs_hash(&logs);
+    if logs_hash != post.logs {
+        return Err(format!("logs hash mismatch: expected {}, got {logs_hash}", post.logs));
+    }
+    let state_root = state_root(evm.db(), spec_id);
+    if state_root != post.hash {
+        return Err(format!("state root mismatch: expected {}, got {state_root}", post.hash));
+    }
+    Ok(())
+}
+
+fn test_env(test: &StateTest, indexes: TxIndexes, spec_id: SpecId) -> Result<Env, String> {
+    let mut env = Env::default();
+    env.cfg.chain_id = 1;
+
+    let block = &mut env.block;
+    block.number = test.env.current_number;
+    block.coinbase = test.env.current_coinbase;
+    block.timestamp = test.env.current_timestamp;
+    block.gas_limit = test.env.current_gas_limit;
+    block.basefee = test.env.current_base_fee.unwrap_or_default();
+    block.difficulty = test.env.current_difficulty;
+    block.prevrandao = test.env.current_random;
+    if let Some(excess_blob_gas) = test.env.current_excess_blob_gas {
+        block.set_blob_excess_gas_and_price(
+            excess_blob_gas.to(),
+            spec_id.is_enabled_in(SpecId::PRAGUE),
+        );
+    }
+
+    let tx = &test.transaction;
+    let index = |name: &str, len: usize, i: usize| {
+        if i < len {
+            Ok(i)
+        } else {
+            Err(format!("transaction {name} index {i} out of bounds"))
+        }
+    };
+    let data = index("data", tx.data.len(), indexes.data)?;
+    let gas = index("gas", tx.gas_limit.len(), indexes.gas)?;
+    let value = index("value", tx.value.len(), indexes.value)?;
+    env.tx.caller = tx.sender.ok_or("transactions without a sender are not supported")?;
+    env.tx.data = tx.data[data].clone();
+    env.tx.gas_limit = tx.gas_limit[gas].saturating_to();
+    env.tx.value = tx.value[value];
+    env.tx.gas_price = tx.gas_price.or(tx.max_fee_per_gas).unwrap_or_default();
+    env.tx.gas_priority_fee = tx.max_priority_fee_per_gas;
+    env.tx.nonce = Some(tx.nonce.saturating_to());
+    env.tx.transact_to = match tx.to {
+        Some(to) => TxKind::Call(to),
+        None => TxKind::Create,
+    };
+    env.tx.access_list =
+        tx.access_lists.get(data).cloned().flatten().map(|list| list.0).unwrap_or_default();
+    env.tx.blob_hashes.clone_from(&tx.blob_versioned_hashes);
+    env.tx.max_fee_per_blob_gas = tx.max_fee_per_blob_gas;
+    Ok(env)
+}
+
+/// Returns the spec ID of the given fork name, as used in the test fixtures.
+fn fork_spec_id(fork: &str) -> Option<SpecId> {
+    Some(match fork {
+        "Frontier" => SpecId::FRONTIER,
+        "Homestead" => SpecId::HOMESTEAD,
+        "EIP150" => SpecId::TANGERINE,
+        "EIP158" => SpecId::SPURIOUS_DRAGON,
+        "Byzantium" => SpecId::BYZANTIUM,
+        "Constantinople" => SpecId::CONSTANTINOPLE,
+        "ConstantinopleFix" => SpecId::PETERSBURG,
+        "Istanbul" => SpecId::ISTANBUL,
+        "Berlin" => SpecId::BERLIN,
+        "London" => SpecId::LONDON,
+        "Paris" | "Merge" => SpecId::MERGE,
+        "Shanghai" => SpecId::SHANGHAI,
+        "Cancun" => SpecId::CANCUN,
+        "Prague" => SpecId::PRAGUE,
+        "Osaka" => SpecId::OSAKA,
+        _ => return None,
+    })
+}
+
+fn deserialize_to<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Address>, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    if s.is_empty() {
+        return Ok(None);
+    }
+    s.parse().map(Some).map_err(serde::de::Error::custom)
+}
+
+#[derive(RlpEncodable)]
+struct TrieAccount {
+    nonce: u64,
+    balance: U256,
+    storage_root: B256,
+    code_hash: B256,
+}
+
+fn logs_hash(logs: &[Log]) -> B256 {
+    let mut out = Vec::new();
+    alloy_rlp::encode_list::<_, Log>(logs, &mut out);
+    keccak256(out)
+}
+
+/// Computes the state root of the database.
+///
+/// From Spurious Dragon, empty accounts are not part of the state (EIP-161). Transactions can only
+/// create them by touching non-existent accounts, which then get removed.
+fn state_root(db: &StateDb, spec_id: SpecId) -> B256 {
+    let state_clear = spec_id.is_enabled_in(SpecId::SPURIOUS_DRAGON);
+    let accounts = db.accounts.iter().filter(|(_, a)| {
+        let removed = state_clear && a.info.is_empty();
+        !matches!(a.account_state, DbAccountState::NotExisting) && !removed
+    });
+    trie_root(accounts.map(|(address, account)| {
+        let storage = account.storage.iter().filter(|(_, value)| !value.is_zero());
+        let storage_root =
+            trie_root(storage.map(|(key, value)| {
+                (keccak256(key.to_be_bytes::<32>()), alloy_rlp::encode(value))
+            }));
+        let account = TrieAccount {
+            nonce: account.info.nonce,
+            balance: account.info.balance,
+            storage_root,
+            code_hash: account.info.code_hash,
+        };
+        (keccak256(address), alloy_rlp::encode(account))
+    }))
+}
+
+/// Computes the root of a Merkle-Patricia trie with the given hashed keys and encoded values.
+fn trie_root(items: impl Iterator<Item = (B256, Vec<u8>)>) -> B256 {
+    let mut items = items.collect::<Vec<_>>();
+    items.sort_unstable_by_key(|&(key, _)| key);
+    let mut builder = HashBuilder::default();
+    for (key, value) in items {
+        builder.add_leaf(Nibbles::unpack(key), &value);
+    }
+    builder.root()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use revm::{db::EmptyDB, primitives::b256};
+
+    #[test]
+    fn empty_roots() {
+        let empty_root = b256!("56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421");
+        assert_eq!(trie_root(std::iter::empty()), empty_root);
+        assert_eq!(state_root(&StateDb::new(EmptyDB::new()), SpecId::CANCUN), empty_root);
+        let empty_logs = b256!("1dcc4de8dec75d7aab85b567b6ccd41ad312451b948a7413f0a142fd40d49347");
+        assert_eq!(logs_hash(&[]), empty_logs);
+    }
+
+    #[cfg(feature = "llvm")]
+    fn run_file(path: &Path, interpret: bool) -> StateTestReport {
+        let cx = revmc::llvm::inkwell::context::Context::create();
+        let backend =
+            revmc::EvmLlvmBackend::new(&cx, false, revmc::OptimizationLevel::None).unwrap();
+        let mut compiler = EvmCompiler::new(backend);
+        let mut report = StateTestReport::default();
+        run_state_test_file(path, &mut compiler, interpret, &mut report).unwrap();
+        report
+    }
+
+    #[test]
+    #[cfg(feature = "llvm")]
+    fn fixtures() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../tests/GeneralStateTests");
+        let cases = [
+            ("stCallCodes/callValueToContract.json", 2),
+            ("stExample/add11.json", 2),
+            ("stLogTests/log1_nonEmptyMem.json", 1),
+            ("stRevmc/sstoreEmptyCoinbase.json", 1),
+        ];
+        for (file, posts) in cases {
+            for interpret in [true, false] {
+                let report = run_file(&root.join(file), interpret);
+                assert!(report.is_success(), "{file}: {report}");
+                let passed = report.forks.values().map(|fork| fork.passed).sum::<usize>();
+                assert_eq!(passed, posts, "{file}: {report}");
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "llvm")]
+    fn parse_errors() {
+        let dir = std::env::temp_dir().join(format!("revmc-statetest-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let path = dir.join("invalid_test.json");
+        std::fs::write(&path, r#"{"test": {"post": {"Cancun": [{}, {}], "Prague": [{}]}}}"#)
+            .unwrap();
+        let report = run_file(&path, true);
+        assert_eq!(report.failures.len(), 1, "{report}");
+        assert_eq!(report.forks["Cancun"].failed, 2);
+        assert_eq!(report.forks["Prague"].failed, 1);
+
+        let path = dir.join("invalid_json.json");
+        std::fs::write(&path, "{").unwrap();
+        let report = run_file(&path, true);
+        assert_eq!(report.failures.len(), 1, "{report}");
+        assert_eq!(report.forks[UNKNOWN_FORK].failed, 1);
+
+        let fixture = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../../tests/GeneralStateTests/stExample/add11.json");
+        let contents = std::fs::read_to_string(fixture).unwrap();
+        let path = dir.join("invalid_code.json");
+        std::fs::write(&path, contents.replace("0x600160010160005500", "0xef0001")).unwrap();
+        let report = run_file(&path, false);
+        assert_eq!(report.failures.len(), 2, "{report}");
+        assert!(report.failures[0].contains("pre-state"), "{report}");
+        assert_eq!(report.forks["Cancun"].failed, 1);
+        assert_eq!(report.forks["Shanghai"].failed, 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
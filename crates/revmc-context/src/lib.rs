@@ -6,12 +6,17 @@
 extern crate alloc;
 
 use alloc::vec::Vec;
-use core::{fmt, mem::MaybeUninit, ptr};
+use core::{
+    fmt,
+    mem::MaybeUninit,
+    ptr,
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+};
 use revm_interpreter::{
-    Contract, FunctionStack, Gas, Host, InstructionResult, Interpreter, InterpreterAction,
-    InterpreterResult, SharedMemory, EMPTY_SHARED_MEMORY,
+    Contract, FunctionReturnFrame, FunctionStack, Gas, Host, InstructionResult, Interpreter,
+    InterpreterAction, InterpreterResult, SharedMemory, EMPTY_SHARED_MEMORY,
 };
-use revm_primitives::{Address, Bytes, Env, U256};
+use revm_primitives::{Address, Bytes, Env, SpecId, B256, U256};
 
 #[cfg(feature = "host-ext-any")]
 use core::any::Any;
@@ -39,6 +44,8 @@ pub struct EvmContext<'a> {
     pub is_static: bool,
     /// Whether the context is EOF init.
     pub is_eof_init: bool,
+    /// The execution limits, checked only if the function was compiled with them enabled.
+    pub limits: Option<&'a ExecutionLimits>,
     /// An index that is used internally to keep track of where execution should resume.
     /// `0` is the initial state.
     #[doc(hidden)]
@@ -79,6 +86,7 @@ impl<'a> EvmContext<'a> {
             func_stack: &mut interpreter.function_stack,
             is_static: interpreter.is_static,
             is_eof_init: interpreter.is_eof_init,
+            limits: None,
             resume_at,
         };
         (this, stack, stack_len)
@@ -263,11 +271,54 @@ impl EvmCompilerFn {
         self,
         interpreter: &mut Interpreter,
         host: &mut dyn HostExt,
+    ) -> InterpreterAction {
+        self.call_with_interpreter_inner(interpreter, host, None)
+    }
+
+    /// Calls the function by re-using the interpreter's resources and memory, checking the given
+    /// execution limits.
+    ///
+    /// Returns the reason as an error if the function was halted by a limit. The interpreter is
+    /// left with [`InstructionResult::FatalExternalError`] in that case.
+    ///
+    /// See [`call_with_interpreter_and_memory`](Self::call_with_interpreter_and_memory) for more
+    /// information.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure that the function is safe to call.
+    #[inline]
+    pub unsafe fn call_with_limits(
+        self,
+        interpreter: &mut Interpreter,
+        memory: &mut SharedMemory,
+        host: &mut dyn HostExt,
+        limits: &ExecutionLimits,
+    ) -> Result<InterpreterAction, LimitExceeded> {
+        interpreter.shared_memory = core::mem::replace(memory, EMPTY_SHARED_MEMORY);
+        let action = self.call_with_interpreter_inner(interpreter, host, Some(limits));
+        *memory = interpreter.take_memory();
+        // Builtins also fail with `FatalExternalError`, so the flag tells the two apart.
+        if interpreter.instruction_result == InstructionResult::FatalExternalError {
+            if let Some(reason) = limits.exceeded() {
+                return Err(reason);
+            }
+        }
+        Ok(action)
+    }
+
+    #[inline]
+    unsafe fn call_with_interpreter_inner(
+        self,
+        interpreter: &mut Interpreter,
+        host: &mut dyn HostExt,
+        limits: Option<&ExecutionLimits>,
     ) -> InterpreterAction {
         interpreter.next_action = InterpreterAction::None;
 
         let (mut ecx, stack, stack_len) =
             EvmContext::from_interpreter_with_stack(interpreter, host);
+        ecx.limits = limits;
         let result = self.call(Some(stack), Some(stack_len), &mut ecx);
 
         // Set the remaining gas to 0 if the result is `OutOfGas`,
@@ -345,6 +396,478 @@ impl EvmCompilerFn {
     }
 }
 
+/// Limits on the execution of compiled functions.
+///
+/// These are only checked by functions compiled with execution limits enabled, at loop back-edges,
+/// dynamic jumps, and EOF code section entries. When a limit is exceeded, the function halts with
+/// [`InstructionResult::FatalExternalError`], and [`exceeded`](Self::exceeded) returns the reason.
+/// [`EvmCompilerFn::call_with_limits`] turns this into a [`LimitExceeded`] error.
+///
+/// The same limits can be shared by all the frames of a transaction, and cancelled from another
+/// thread with [`cancel`](Self::cancel).
+#[repr(C)]
+pub struct ExecutionLimits {
+    /// The remaining instruction budget.
+    ///
+    /// This is only modified by the compiled function on the executing thread.
+    #[doc(hidden)]
+    pub instruction_budget: AtomicU64,
+    /// Whether execution has been cancelled.
+    #[doc(hidden)]
+    pub cancelled: AtomicBool,
+    /// Whether a limit has been exceeded. Set by the compiled function.
+    #[doc(hidden)]
+    pub exceeded: AtomicBool,
+}
+
+impl Default for ExecutionLimits {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for ExecutionLimits {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExecutionLimits")
+            .field("instruction_budget", &self.instruction_budget())
+            .field("cancelled", &self.is_cancelled())
+            .field("exceeded", &self.exceeded())
+            .finish()
+    }
+}
+
+impl ExecutionLimits {
+    /// Creates new execution limits with an unlimited instruction budget.
+    #[inline]
+    pub const fn new() -> Self {
+        Self::with_instruction_budget(u64::MAX)
+    }
+
+    /// Creates new execution limits with the given instruction budget.
+    ///
+    /// The budget is approximate: it is charged with the number of instructions in a loop's body
+    /// at every back-edge, and with one at every dynamic jump and EOF code section entry.
+    /// Straight-line code is not charged, as it is bounded by the code size.
+    #[inline]
+    pub const fn with_instruction_budget(budget: u64) -> Self {
+        Self {
+            instruction_budget: AtomicU64::new(budget),
+            cancelled: AtomicBool::new(false),
+            exceeded: AtomicBool::new(false),
+        }
+    }
+
+    /// Returns the remaining instruction budget.
+    #[inline]
+    pub fn instruction_budget(&self) -> u64 {
+        self.instruction_budget.load(Ordering::Relaxed)
+    }
+
+    /// Sets the remaining instruction budget.
+    ///
+    /// This must not be called while a function is executing with these limits.
+    #[inline]
+    pub fn set_instruction_budget(&self, budget: u64) {
+        self.instruction_budget.store(budget, Ordering::Relaxed);
+    }
+
+    /// Cancels execution. Can be called from any thread.
+    ///
+    /// The executing function halts at the next check.
+    #[inline]
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns `true` if execution has been cancelled.
+    #[inline]
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Returns the reason execution was halted, if a limit has been exceeded.
+    #[inline]
+    pub fn exceeded(&self) -> Option<LimitExceeded> {
+        if !self.exceeded.load(Ordering::Relaxed) {
+            return None;
+        }
+        Some(if self.is_cancelled() {
+            LimitExceeded::Cancelled
+        } else {
+            LimitExceeded::InstructionBudget
+        })
+    }
+
+    /// Clears the cancellation and exceeded flags, and sets the instruction budget.
+    #[inline]
+    pub fn reset(&self, budget: u64) {
+        self.set_instruction_budget(budget);
+        self.cancelled.store(false, Ordering::Relaxed);
+        self.exceeded.store(false, Ordering::Relaxed);
+    }
+}
+
+/// The reason a compiled function was halted by its [`ExecutionLimits`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LimitExceeded {
+    /// The instruction budget was exhausted.
+    InstructionBudget,
+    /// Execution was cancelled.
+    Cancelled,
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::InstructionBudget => "instruction budget exhausted",
+            Self::Cancelled => "execution cancelled",
+        })
+    }
+}
+
+/// The state of a compiled function suspended on a call or create.
+///
+/// This can be [encoded](Self::encode) to bytes and later [resumed](Self::into_interpreter) in a
+/// different process, as long as it has loaded the same compiled code. The function must have been
+/// compiled with `EvmCompiler::stable_resume_points` enabled, as the resume point is otherwise a
+/// code address that is only valid in the current process, and is identified by its
+/// `EvmCompiler::cache_key`, which covers the bytecode and all the settings that affect the
+/// compiled code.
+///
+/// The state of the suspended frame does not include the pending call, or the contract being
+/// executed, which must be provided when resuming.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SuspendedFrame {
+    /// The spec ID the function was compiled with.
+    pub spec_id: SpecId,
+    /// The hash of the bytecode being executed.
+    pub code_hash: B256,
+    /// The cache key of the compiled function.
+    pub cache_key: B256,
+    /// The index used internally by the compiled function to resume execution.
+    pub resume_at: usize,
+    /// The stack, from bottom to top.
+    pub stack: Vec<U256>,
+    /// The memory of the current call context.
+    pub memory: Vec<u8>,
+    /// The gas state.
+    pub gas: Gas,
+    /// The return data buffer.
+    pub return_data: Bytes,
+    /// The EOF function stack.
+    pub function_stack: FunctionStack,
+    /// Whether the frame is static.
+    pub is_static: bool,
+    /// Whether the frame is EOF init.
+    pub is_eof_init: bool,
+}
+
+impl SuspendedFrame {
+    /// The magic bytes at the start of an encoded frame.
+    pub const MAGIC: [u8; 4] = *b"rvsf";
+    /// The current version of the encoding.
+    pub const VERSION: u8 = 2;
+
+    /// Captures the state of an interpreter suspended by a compiled function, with its memory
+    /// context.
+    ///
+    /// `memory` is the shared memory passed to
+    /// [`call_with_interpreter_and_memory`](EvmCompilerFn::call_with_interpreter_and_memory), or
+    /// the interpreter's own if it was called with
+    /// [`call_with_interpreter`](EvmCompilerFn::call_with_interpreter). `cache_key` is the cache
+    /// key of the compiled function.
+    ///
+    /// Returns an error if the resume point is not a stable index.
+    pub fn from_interpreter(
+        interpreter: &Interpreter,
+        memory: &SharedMemory,
+        spec_id: SpecId,
+        cache_key: B256,
+    ) -> Result<Self, SuspendedFrameError> {
+        let contract = &interpreter.contract;
+        let code = contract.bytecode.original_byte_slice();
+        let resume_at = ResumeAt::load(interpreter.instruction_pointer, code);
+        ResumeAt::check_stable(resume_at, code)?;
+        Ok(Self {
+            spec_id,
+            code_hash: contract.hash.unwrap_or_else(|| contract.bytecode.hash_slow()),
+            cache_key,
+            resume_at,
+            stack: interpreter.stack.data().clone(),
+            memory: memory.context_memory().to_vec(),
+            gas: interpreter.gas,
+            return_data: interpreter.return_data_buffer.clone(),
+            function_stack: interpreter.function_stack.clone(),
+            is_static: interpreter.is_static,
+            is_eof_init: interpreter.is_eof_init,
+        })
+    }
+
+    /// Creates a new interpreter that resumes execution of the suspended frame, with its memory in
+    /// [`Interpreter::shared_memory`].
+    ///
+    /// The result of the pending call must be inserted into the interpreter before calling the
+    /// function again.
+    ///
+    /// Returns an error if `cache_key` or the code hash of `contract` do not match, or if the
+    /// resume point is not a stable index.
+    pub fn into_interpreter(
+        self,
+        contract: Contract,
+        cache_key: B256,
+    ) -> Result<Interpreter, SuspendedFrameError> {
+        if cache_key != self.cache_key {
+            return Err(SuspendedFrameError::CacheKeyMismatch {
+                expected: self.cache_key,
+                found: cache_key,
+            });
+        }
+        let code_hash = contract.hash.unwrap_or_else(|| contract.bytecode.hash_slow());
+        if code_hash != self.code_hash {
+            return Err(SuspendedFrameError::CodeHashMismatch {
+                expected: self.code_hash,
+                found: code_hash,
+            });
+        }
+        ResumeAt::check_stable(self.resume_at, contract.bytecode.original_byte_slice())?;
+
+        let mut interpreter = Interpreter::new(contract, self.gas.limit(), self.is_static);
+        interpreter.gas = self.gas;
+        *interpreter.stack.data_mut() = self.stack;
+        interpreter.shared_memory.resize(self.memory.len());
+        interpreter.shared_memory.set(0, &self.memory);
+        interpreter.return_data_buffer = self.return_data;
+        interpreter.function_stack = self.function_stack;
+        interpreter.is_eof_init = self.is_eof_init;
+        ResumeAt::store(&mut interpreter.instruction_pointer, self.resume_at);
+        Ok(interpreter)
+    }
+
+    /// Encodes the frame to bytes.
+    ///
+    /// The encoding is versioned with [`VERSION`](Self::VERSION), and all integers are encoded in
+    /// little-endian, except for stack words which are encoded in big-endian.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(
+            128 + self.stack.len() * 32 + self.memory.len() + self.return_data.len(),
+        );
+        out.extend_from_slice(&Self::MAGIC);
+        out.push(Self::VERSION);
+        out.push(self.spec_id as u8);
+        out.push(self.is_static as u8 | ((self.is_eof_init as u8) << 1));
+        out.extend_from_slice(self.code_hash.as_slice());
+        out.extend_from_slice(self.cache_key.as_slice());
+        write_u64(&mut out, self.resume_at as u64);
+
+        write_u64(&mut out, self.gas.limit());
+        write_u64(&mut out, self.gas.remaining());
+        out.extend_from_slice(&self.gas.refunded().to_le_bytes());
+
+        write_u64(&mut out, self.function_stack.current_code_idx as u64);
+        write_u64(&mut out, self.function_stack.return_stack.len() as u64);
+        for frame in &self.function_stack.return_stack {
+            write_u64(&mut out, frame.idx as u64);
+            write_u64(&mut out, frame.pc as u64);
+        }
+
+        write_u64(&mut out, self.stack.len() as u64);
+        for word in &self.stack {
+            out.extend_from_slice(&word.to_be_bytes::<32>());
+        }
+
+        write_u64(&mut out, self.memory.len() as u64);
+        out.extend_from_slice(&self.memory);
+
+        write_u64(&mut out, self.return_data.len() as u64);
+        out.extend_from_slice(&self.return_data);
+
+        out
+    }
+
+    /// Decodes a frame encoded with [`encode`](Self::encode).
+    pub fn decode(bytes: &[u8]) -> Result<Self, SuspendedFrameError> {
+        let mut r = Reader(bytes);
+        if r.array()? != Self::MAGIC {
+            return Err(SuspendedFrameError::InvalidMagic);
+        }
+        let [version] = r.array()?;
+        if version != Self::VERSION {
+            return Err(SuspendedFrameError::UnsupportedVersion(version));
+        }
+        let [spec_id] = r.array()?;
+        let spec_id =
+            SpecId::try_from_u8(spec_id).ok_or(SuspendedFrameError::InvalidSpecId(spec_id))?;
+        let [flags] = r.array()?;
+        if flags & !0b11 != 0 {
+            return Err(SuspendedFrameError::InvalidFlags(flags));
+        }
+        let code_hash = B256::new(r.array()?);
+        let cache_key = B256::new(r.array()?);
+        let resume_at = r.usize()?;
+
+        let limit = r.u64()?;
+        let remaining = r.u64()?;
+        let refunded = i64::from_le_bytes(r.array()?);
+        let mut gas = Gas::new(limit);
+        if remaining > limit || !gas.record_cost(limit - remaining) {
+            return Err(SuspendedFrameError::InvalidGas);
+        }
+        gas.record_refund(refunded);
+
+        let current_code_idx = r.usize()?;
+        let return_stack_len = r.seq_len(16)?;
+        let return_stack = (0..return_stack_len)
+            .map(|_| Ok(FunctionReturnFrame { idx: r.usize()?, pc: r.usize()? }))
+            .collect::<Result<_, SuspendedFrameError>>()?;
+
+        let stack_len = r.seq_len(32)?;
+        if stack_len > EvmStack::CAPACITY {
+            return Err(SuspendedFrameError::StackOverflow(stack_len));
+        }
+        let stack = (0..stack_len)
+            .map(|_| Ok(U256::from_be_bytes(r.array::<32>()?)))
+            .collect::<Result<_, SuspendedFrameError>>()?;
+
+        let memory_len = r.seq_len(1)?;
+        if memory_len % 32 != 0 {
+            return Err(SuspendedFrameError::InvalidMemoryLength(memory_len));
+        }
+        let memory = r.bytes(memory_len)?.to_vec();
+
+        let return_data_len = r.seq_len(1)?;
+        let return_data = Bytes::copy_from_slice(r.bytes(return_data_len)?);
+
+        if !r.0.is_empty() {
+            return Err(SuspendedFrameError::TrailingBytes(r.0.len()));
+        }
+
+        Ok(Self {
+            spec_id,
+            code_hash,
+            cache_key,
+            resume_at,
+            stack,
+            memory,
+            gas,
+            return_data,
+            function_stack: FunctionStack { return_stack, current_code_idx },
+            is_static: flags & 0b01 != 0,
+            is_eof_init: flags & 0b10 != 0,
+        })
+    }
+}
+
+/// An error that can occur when decoding or resuming a [`SuspendedFrame`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SuspendedFrameError {
+    /// The input does not start with [`SuspendedFrame::MAGIC`].
+    InvalidMagic,
+    /// The encoding version is not supported.
+    UnsupportedVersion(u8),
+    /// The input ended unexpectedly.
+    UnexpectedEnd,
+    /// The input has the given number of bytes left over after decoding.
+    TrailingBytes(usize),
+    /// An integer does not fit in a `usize`.
+    ValueTooLarge(u64),
+    /// The spec ID is invalid.
+    InvalidSpecId(u8),
+    /// The flags byte has unknown bits set.
+    InvalidFlags(u8),
+    /// The remaining gas is greater than the gas limit.
+    InvalidGas,
+    /// The stack length exceeds the stack limit.
+    StackOverflow(usize),
+    /// The memory length is not a multiple of 32.
+    InvalidMemoryLength(usize),
+    /// The cache key of the compiled function does not match the suspended frame's.
+    CacheKeyMismatch {
+        /// The cache key of the suspended frame.
+        expected: B256,
+        /// The cache key of the compiled function.
+        found: B256,
+    },
+    /// The resume point is a code address instead of a stable index.
+    UnstableResumePoint(usize),
+    /// The code hash of the contract does not match the suspended frame's.
+    CodeHashMismatch {
+        /// The code hash of the suspended frame.
+        expected: B256,
+        /// The code hash of the contract.
+        found: B256,
+    },
+}
+
+impl fmt::Display for SuspendedFrameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidMagic => f.write_str("invalid magic bytes"),
+            Self::UnsupportedVersion(version) => write!(f, "unsupported version {version}"),
+            Self::UnexpectedEnd => f.write_str("unexpected end of input"),
+            Self::TrailingBytes(len) => write!(f, "{len} trailing bytes"),
+            Self::ValueTooLarge(value) => write!(f, "value {value} does not fit in a usize"),
+            Self::InvalidSpecId(spec_id) => write!(f, "invalid spec ID {spec_id}"),
+            Self::InvalidFlags(flags) => write!(f, "invalid flags {flags:#04x}"),
+            Self::InvalidGas => f.write_str("remaining gas exceeds the gas limit"),
+            Self::StackOverflow(len) => write!(f, "stack length {len} exceeds the stack limit"),
+            Self::InvalidMemoryLength(len) => write!(f, "invalid memory length {len}"),
+            Self::CacheKeyMismatch { expected, found } => {
+                write!(f, "cache key mismatch: expected {expected}, found {found}")
+            }
+            Self::UnstableResumePoint(resume_at) => {
+                write!(f, "resume point {resume_at:#x} is not a stable index")
+            }
+            Self::CodeHashMismatch { expected, found } => {
+                write!(f, "code hash mismatch: expected {expected}, found {found}")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SuspendedFrameError {}
+
+fn write_u64(out: &mut Vec<u8>, value: u64) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+/// A cursor over the bytes of an encoded [`SuspendedFrame`].
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], SuspendedFrameError> {
+        if self.0.len() < len {
+            return Err(SuspendedFrameError::UnexpectedEnd);
+        }
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], SuspendedFrameError> {
+        self.bytes(N).map(|bytes| bytes.try_into().unwrap())
+    }
+
+    fn u64(&mut self) -> Result<u64, SuspendedFrameError> {
+        self.array().map(u64::from_le_bytes)
+    }
+
+    fn usize(&mut self) -> Result<usize, SuspendedFrameError> {
+        let value = self.u64()?;
+        usize::try_from(value).map_err(|_| SuspendedFrameError::ValueTooLarge(value))
+    }
+
+    /// Reads the length of a sequence of `elem_size`-byte elements, checking that the input is
+    /// long enough.
+    fn seq_len(&mut self, elem_size: usize) -> Result<usize, SuspendedFrameError> {
+        let len = self.usize()?;
+        match len.checked_mul(elem_size) {
+            Some(size) if size <= self.0.len() => Ok(len),
+            _ => Err(SuspendedFrameError::UnexpectedEnd),
+        }
+    }
+}
+
 /// EVM context stack.
 #[repr(C)]
 #[allow(missing_debug_implementations)]
@@ -751,6 +1274,17 @@ impl ResumeAt {
     fn store(ip: &mut *const u8, value: usize) {
         *ip = value as *const u8;
     }
+
+    /// Checks that the value is a stable index rather than a code address.
+    ///
+    /// There is at most one resume point per instruction, so indexes are bounded by the code
+    /// length, while code addresses are far above it.
+    fn check_stable(value: usize, code: &[u8]) -> Result<(), SuspendedFrameError> {
+        if value > code.len() {
+            return Err(SuspendedFrameError::UnstableResumePoint(value));
+        }
+        Ok(())
+    }
 }
 
 #[inline(always)]
@@ -805,6 +1339,82 @@ mod tests {
         assert_eq!(test_fn as usize, __test_fn as usize);
     }
 
+    #[test]
+    fn suspended_frame_encoding() {
+        let frame = SuspendedFrame {
+            spec_id: SpecId::CANCUN,
+            code_hash: B256::repeat_byte(0x11),
+            cache_key: B256::repeat_byte(0x44),
+            resume_at: 3,
+            stack: vec![U256::from(1), U256::MAX],
+            memory: vec![0x22; 64],
+            gas: {
+                let mut gas = Gas::new(1000);
+                assert!(gas.record_cost(100));
+                gas.record_refund(-5);
+                gas
+            },
+            return_data: Bytes::from_static(&[0x33; 3]),
+            function_stack: FunctionStack {
+                return_stack: vec![FunctionReturnFrame { idx: 1, pc: 2 }],
+                current_code_idx: 4,
+            },
+            is_static: true,
+            is_eof_init: false,
+        };
+        let encoded = frame.encode();
+        assert_eq!(SuspendedFrame::decode(&encoded), Ok(frame.clone()));
+
+        for len in 0..encoded.len() {
+            assert_eq!(
+                SuspendedFrame::decode(&encoded[..len]),
+                Err(SuspendedFrameError::UnexpectedEnd),
+                "{len}"
+            );
+        }
+
+        let mut trailing = encoded.clone();
+        trailing.push(0);
+        assert_eq!(SuspendedFrame::decode(&trailing), Err(SuspendedFrameError::TrailingBytes(1)));
+
+        let mut invalid = encoded.clone();
+        invalid[0] = 0;
+        assert_eq!(SuspendedFrame::decode(&invalid), Err(SuspendedFrameError::InvalidMagic));
+
+        let mut invalid = encoded;
+        invalid[4] = SuspendedFrame::VERSION + 1;
+        assert_eq!(
+            SuspendedFrame::decode(&invalid),
+            Err(SuspendedFrameError::UnsupportedVersion(SuspendedFrame::VERSION + 1))
+        );
+
+        assert_eq!(
+            frame.clone().into_interpreter(Contract::default(), B256::ZERO).err(),
+            Some(SuspendedFrameError::CacheKeyMismatch {
+                expected: B256::repeat_byte(0x44),
+                found: B256::ZERO
+            })
+        );
+
+        let contract = Contract::default();
+        let found = contract.bytecode.hash_slow();
+        assert_eq!(
+            frame.clone().into_interpreter(contract, frame.cache_key).err(),
+            Some(SuspendedFrameError::CodeHashMismatch {
+                expected: B256::repeat_byte(0x11),
+                found
+            })
+        );
+
+        // The default contract has no code, so `3` cannot be an index.
+        let contract = Contract::default();
+        let frame = SuspendedFrame { code_hash: contract.bytecode.hash_slow(), ..frame };
+        assert_eq!(
+            frame.clone().into_interpreter(contract, frame.cache_key).err(),
+            Some(SuspendedFrameError::UnstableResumePoint(3))
+        );
+    }
+
     #[test]
     fn borrowing_host() {
         #[allow(unused)]
@@ -49,6 +49,7 @@ pub struct EvmCraneliftBackend {
     opt_level: OptimizationLevel,
     comments: CommentWriter,
     functions: Vec<FuncId>,
+    function_sizes: HashMap<FuncId, usize>,
 }
 
 #[allow(clippy::new_without_default)]
@@ -77,6 +78,7 @@ impl EvmCraneliftBackend {
             opt_level,
             comments: CommentWriter::new(),
             functions: Vec::new(),
+            function_sizes: HashMap::new(),
         }
     }
 
@@ -100,6 +102,7 @@ impl EvmCraneliftBackend {
             }
         };
         self.module.get().clear_context(&mut self.ctx);
+        self.function_sizes.clear();
         Ok(aot)
     }
 }
@@ -146,6 +149,10 @@ impl Backend for EvmCraneliftBackend {
         let _ = name;
     }
 
+    fn set_debug_file(&mut self, path: Option<&Path>) {
+        let _ = path;
+    }
+
     fn set_is_dumping(&mut self, yes: bool) {
         self.ctx.set_disasm(yes);
     }
@@ -164,10 +171,21 @@ impl Backend for EvmCraneliftBackend {
         self.opt_level = level;
     }
 
+    fn set_function_opt_level(&mut self, id: Self::FuncId, level: OptimizationLevel) {
+        // Not supported, the optimization level is set for the whole module.
+        let _ = (id, level);
+    }
+
     fn is_aot(&self) -> bool {
         self.module.is_aot()
     }
 
+    fn codegen_settings(&self) -> String {
+        let isa = self.module.get().isa();
+        let isa_flags = isa.isa_flags().iter().map(ToString::to_string).collect::<Vec<_>>();
+        format!("cranelift {} {} {}", isa.triple(), isa.flags(), isa_flags.join(","))
+    }
+
     fn function_name_is_unique(&self, name: &str) -> bool {
         self.module.get().get_name(name).is_none()
     }
@@ -237,6 +255,9 @@ impl Backend for EvmCraneliftBackend {
         // function below.
         for &id in &self.functions {
             self.module.get_mut().define_function(id, &mut self.ctx)?;
+            if let Some(code) = self.ctx.compiled_code() {
+                self.function_sizes.insert(id, code.code_buffer().len());
+            }
         }
         self.functions.clear();
 
@@ -260,10 +281,19 @@ impl Backend for EvmCraneliftBackend {
         Ok(())
     }
 
+    fn set_gdb_jit_registration(&mut self, yes: bool) {
+        // Not supported.
+        let _ = yes;
+    }
+
     fn jit_function(&mut self, id: Self::FuncId) -> Result<usize> {
         self.module.get_finalized_function(id).map(|ptr| ptr as usize)
     }
 
+    fn function_size(&mut self, id: Self::FuncId) -> Result<Option<usize>> {
+        Ok(self.function_sizes.get(&id).copied())
+    }
+
     unsafe fn free_function(&mut self, id: Self::FuncId) -> Result<()> {
         // This doesn't exist yet.
         let _ = id;
@@ -360,6 +390,15 @@ impl<'a> Builder for EvmCraneliftBuilder<'a> {
         self.comments.add_comment(inst, comment);
     }
 
+    fn set_debug_location(&mut self, line: u32, column: u32) {
+        let _ = column;
+        self.bcx.set_srcloc(codegen::ir::SourceLoc::new(line));
+    }
+
+    fn clear_debug_location(&mut self) {
+        self.bcx.set_srcloc(codegen::ir::SourceLoc::default());
+    }
+
     fn fn_param(&mut self, index: usize) -> Self::Value {
         let block = self.current_block().unwrap();
         self.bcx.block_params(block)[index]
@@ -470,6 +509,12 @@ impl<'a> Builder for EvmCraneliftBuilder<'a> {
         self.bcx.ins().load(ty, MemFlags::new().with_notrap(), ptr, 0)
     }
 
+    fn load_atomic(&mut self, ty: Self::Type, ptr: Self::Value, name: &str) -> Self::Value {
+        let _ = name;
+        // Cranelift's atomic loads are sequentially consistent, which is stronger than needed.
+        self.bcx.ins().atomic_load(ty, MemFlags::trusted(), ptr)
+    }
+
     fn store(&mut self, value: Self::Value, ptr: Self::Value) {
         self.bcx.ins().store(MemFlags::trusted(), value, ptr, 0);
     }
@@ -478,6 +523,11 @@ impl<'a> Builder for EvmCraneliftBuilder<'a> {
         self.bcx.ins().store(MemFlags::new().with_notrap(), value, ptr, 0);
     }
 
+    fn store_atomic(&mut self, value: Self::Value, ptr: Self::Value) {
+        // Cranelift's atomic stores are sequentially consistent, which is stronger than needed.
+        self.bcx.ins().atomic_store(MemFlags::trusted(), value, ptr);
+    }
+
     fn nop(&mut self) {
         self.bcx.ins().nop();
     }
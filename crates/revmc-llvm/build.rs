@@ -0,0 +1,23 @@
+#![allow(missing_docs)]
+
+use std::{env, process::Command};
+
+fn main() {
+    println!("cargo:rerun-if-changed=src/llvm_ext.cpp");
+
+    // Set by `llvm-sys`.
+    let llvm_config = env::var("DEP_LLVM_18_CONFIG_PATH").expect("missing llvm-config path");
+    let output = Command::new(&llvm_config)
+        .arg("--cxxflags")
+        .output()
+        .unwrap_or_else(|e| panic!("failed to run {llvm_config}: {e}"));
+    assert!(output.status.success(), "{llvm_config} --cxxflags failed");
+    let cxxflags = String::from_utf8(output.stdout).expect("invalid llvm-config output");
+
+    let mut build = cc::Build::new();
+    build.cpp(true).file("src/llvm_ext.cpp").warnings(false);
+    for flag in cxxflags.split_whitespace() {
+        build.flag(flag);
+    }
+    build.compile("revmc_llvm_ext");
+}
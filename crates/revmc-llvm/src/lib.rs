@@ -8,7 +8,12 @@ extern crate tracing;
 use inkwell::{
     attributes::{Attribute, AttributeLoc},
     basic_block::BasicBlock,
+    debug_info::{
+        debug_metadata_version, AsDIScope, DICompileUnit, DIFlags, DIFlagsConstants,
+        DWARFEmissionKind, DWARFSourceLanguage, DebugInfoBuilder,
+    },
     execution_engine::ExecutionEngine,
+    memory_buffer::MemoryBuffer,
     module::{FlagBehavior, Module},
     passes::PassBuilderOptions,
     support::error_handling::install_fatal_error_handler,
@@ -23,7 +28,7 @@ use inkwell::{
         BasicMetadataValueEnum, BasicValue, BasicValueEnum, FunctionValue, InstructionValue,
         PointerValue,
     },
-    AddressSpace, IntPredicate, OptimizationLevel,
+    AddressSpace, AtomicOrdering, IntPredicate, OptimizationLevel,
 };
 use revmc_backend::{
     eyre, Backend, BackendTypes, Builder, Error, IntCC, Result, TailCallKind, TypeMethods, U256,
@@ -32,20 +37,25 @@ use rustc_hash::FxHashMap;
 use std::{
     borrow::Cow,
     iter,
-    path::Path,
+    path::{Path, PathBuf},
     sync::{Once, OnceLock},
 };
 
 pub use inkwell::{self, context::Context};
 
 mod dh;
+mod listeners;
 pub mod orc;
+mod passes;
 
 mod utils;
 pub(crate) use utils::*;
 
 const DEFAULT_WEIGHT: u32 = 20000;
 
+/// The named metadata holding the cache key of modules written as bitcode.
+const CACHE_KEY_METADATA: &str = "revmc.cache_key";
+
 /// Executes the given closure with a thread-local LLVM context.
 #[inline]
 pub fn with_llvm_context<R>(f: impl FnOnce(&Context) -> R) -> R {
@@ -62,6 +72,8 @@ pub struct EvmLlvmBackend<'ctx> {
     cx: &'ctx Context,
     _dh: dh::DiagnosticHandlerGuard<'ctx>,
     bcx: inkwell::builder::Builder<'ctx>,
+    /// Must be dropped before `module`.
+    debug_info: Option<DebugInfo<'ctx>>,
     module: Module<'ctx>,
     exec_engine: Option<ExecutionEngine<'ctx>>,
     machine: TargetMachine,
@@ -77,10 +89,20 @@ pub struct EvmLlvmBackend<'ctx> {
 
     aot: bool,
     debug_assertions: bool,
+    debug_file: Option<PathBuf>,
     opt_level: OptimizationLevel,
+    pass_pipeline: Option<String>,
+    time_passes: bool,
     /// Separate from `functions` to have always increasing IDs.
     function_counter: u32,
     functions: FxHashMap<u32, (String, FunctionValue<'ctx>)>,
+    function_opt_levels: FxHashMap<u32, OptimizationLevel>,
+    /// Modules loaded from bitcode in `jit_bitcode`.
+    imported_modules: Vec<Module<'ctx>>,
+    gdb_jit_registration: bool,
+    /// Registered with the execution engine. Must be dropped after it, and after the modules,
+    /// which keep it alive.
+    jit_symbols: listeners::SymbolListener,
 }
 
 impl<'ctx> EvmLlvmBackend<'ctx> {
@@ -137,6 +159,11 @@ impl<'ctx> EvmLlvmBackend<'ctx> {
             Some(module.create_jit_execution_engine(opt_level).map_err(error_msg)?)
         };
 
+        let jit_symbols = listeners::SymbolListener::new();
+        if let Some(exec_engine) = &exec_engine {
+            jit_symbols.register(exec_engine);
+        }
+
         let bcx = cx.create_builder();
 
         let ty_void = cx.void_type();
@@ -151,6 +178,7 @@ impl<'ctx> EvmLlvmBackend<'ctx> {
             cx,
             _dh: dh::DiagnosticHandlerGuard::new(cx),
             bcx,
+            debug_info: None,
             module,
             exec_engine,
             machine,
@@ -164,9 +192,16 @@ impl<'ctx> EvmLlvmBackend<'ctx> {
             ty_ptr,
             aot,
             debug_assertions: cfg!(debug_assertions),
+            debug_file: None,
             opt_level,
+            pass_pipeline: None,
+            time_passes: false,
             function_counter: 0,
             functions: FxHashMap::default(),
+            function_opt_levels: FxHashMap::default(),
+            imported_modules: Vec::new(),
+            gdb_jit_registration: false,
+            jit_symbols,
         })
     }
 
@@ -176,6 +211,143 @@ impl<'ctx> EvmLlvmBackend<'ctx> {
         self.cx
     }
 
+    /// Sets a custom LLVM pass pipeline, overriding the default pipeline of the optimization level.
+    ///
+    /// The pipeline has the same format as the `-passes` argument of `opt`, for example
+    /// `default<O2>,function(gvn)`. See <https://llvm.org/docs/NewPassManager.html>.
+    ///
+    /// Functions with an overridden optimization level that is different from the backend's one
+    /// use the default pipeline of their level instead.
+    pub fn set_pass_pipeline(&mut self, pipeline: Option<String>) {
+        self.pass_pipeline = pipeline;
+    }
+
+    /// Sets whether to time the passes run when optimizing the module.
+    ///
+    /// When enabled, an `INFO` event with the time spent in each pass, and the function or module
+    /// it ran on, is emitted under the `revmc_llvm::passes` target.
+    ///
+    /// Defaults to `false`.
+    pub fn set_time_passes(&mut self, yes: bool) {
+        self.time_passes = yes;
+    }
+
+    /// Writes the module as LLVM bitcode to the given writer.
+    ///
+    /// `cache_key` is stored in the module and checked when loading it with
+    /// [`jit_bitcode`](Self::jit_bitcode).
+    pub fn write_bitcode<W: std::io::Write>(&mut self, cache_key: &str, w: W) -> Result<()> {
+        let module = self.module.clone();
+        Self::write_module_bitcode(&module, cache_key, w)
+    }
+
+    /// Writes the function `id`, and only the functions and globals it references, as LLVM
+    /// bitcode to the given writer.
+    ///
+    /// `cache_key` is stored in the module and checked when loading it with
+    /// [`jit_bitcode`](Self::jit_bitcode).
+    pub fn write_function_bitcode<W: std::io::Write>(
+        &mut self,
+        id: u32,
+        cache_key: &str,
+        w: W,
+    ) -> Result<()> {
+        let name = self.id_to_name(id);
+        let module = self.module.clone();
+        // Deleting a function can leave the functions and globals it referenced unused.
+        loop {
+            let mut deleted = false;
+            for function in module.get_functions() {
+                let unused =
+                    function.as_global_value().as_pointer_value().get_first_use().is_none();
+                if unused && function.get_name().to_bytes() != name.as_bytes() {
+                    unsafe { function.delete() };
+                    deleted = true;
+                }
+            }
+            for global in module.get_globals() {
+                let unused = global.as_pointer_value().get_first_use().is_none();
+                if unused && !global.get_name().to_bytes().starts_with(b"llvm.") {
+                    unsafe { global.delete() };
+                    deleted = true;
+                }
+            }
+            if !deleted {
+                break;
+            }
+        }
+        Self::write_module_bitcode(&module, cache_key, w)
+    }
+
+    fn write_module_bitcode<W: std::io::Write>(
+        module: &Module<'ctx>,
+        cache_key: &str,
+        mut w: W,
+    ) -> Result<()> {
+        let cx = module.get_context();
+        let key = cx.metadata_node(&[cx.metadata_string(cache_key).into()]);
+        module.add_global_metadata(CACHE_KEY_METADATA, &key).map_err(Error::msg)?;
+        let buffer = module.write_bitcode_to_memory();
+        w.write_all(buffer.as_slice())?;
+        Ok(())
+    }
+
+    /// (JIT) Loads a module from LLVM bitcode into the execution engine and returns the address of
+    /// the function `name` defined in it.
+    ///
+    /// The module is not optimized again. Returns an error if the module was not written with the
+    /// given `cache_key`. `resolve` provides the addresses of the functions declared in the
+    /// module, such as builtins.
+    ///
+    /// The module is kept alive until all functions are freed.
+    pub fn jit_bitcode(
+        &mut self,
+        bitcode: &[u8],
+        name: &str,
+        cache_key: &str,
+        resolve: impl Fn(&str) -> Option<usize>,
+    ) -> Result<usize> {
+        let buffer = MemoryBuffer::create_from_memory_range_copy(bitcode, name);
+        let module = Module::parse_bitcode_from_buffer(&buffer, self.cx).map_err(error_msg)?;
+        let found = match module.get_global_metadata(CACHE_KEY_METADATA).first() {
+            Some(node) => match node.get_node_values().first() {
+                Some(BasicMetadataValueEnum::MetadataValue(value)) => {
+                    value.get_string_value().map(|s| s.to_string_lossy().into_owned())
+                }
+                _ => None,
+            },
+            None => None,
+        };
+        match found {
+            Some(found) if found == cache_key => {}
+            Some(found) => {
+                return Err(eyre::eyre!(
+                    "bitcode cache key mismatch: expected {cache_key}, found {found}"
+                ))
+            }
+            None => return Err(eyre::eyre!("bitcode is missing the cache key")),
+        }
+        let exec_engine = self.exec_engine();
+        for function in module.get_functions() {
+            if function.count_basic_blocks() != 0 {
+                continue;
+            }
+            let Ok(function_name) = function.get_name().to_str() else { continue };
+            if function_name.starts_with("llvm.") {
+                continue;
+            }
+            if let Some(address) = resolve(function_name) {
+                exec_engine.add_global_mapping(&function, address);
+            }
+        }
+        exec_engine
+            .add_module(&module)
+            .map_err(|()| eyre::eyre!("failed to add module to the execution engine"))?;
+        let addr = exec_engine.get_function_address(name)?;
+        self.imported_modules.push(module);
+        Ok(addr)
+    }
+
     fn exec_engine(&self) -> &ExecutionEngine<'ctx> {
         assert!(!self.aot, "requested JIT execution engine on AOT");
         self.exec_engine.as_ref().expect("missing JIT execution engine")
@@ -197,6 +369,32 @@ impl<'ctx> EvmLlvmBackend<'ctx> {
         &self.functions[&id].0
     }
 
+    /// Attaches a debug info subprogram to `function` if debug info is enabled.
+    fn add_subprogram(&mut self, function: FunctionValue<'ctx>, name: &str) {
+        let Some(path) = &self.debug_file else { return };
+        let is_optimized = self.opt_level != OptimizationLevel::None;
+        let di = self
+            .debug_info
+            .get_or_insert_with(|| DebugInfo::new(self.cx, &self.module, path, is_optimized));
+        let (filename, directory) = split_path(path);
+        let file = di.builder.create_file(&filename, &directory);
+        let ty = di.builder.create_subroutine_type(file, None, &[], DIFlags::PUBLIC);
+        let subprogram = di.builder.create_function(
+            di.cu.as_debug_info_scope(),
+            name,
+            None,
+            file,
+            0,
+            ty,
+            false,
+            true,
+            0,
+            DIFlags::PUBLIC,
+            is_optimized,
+        );
+        function.set_subprogram(subprogram);
+    }
+
     // Delete IR to lower memory consumption.
     // For some reason this does not happen when `Drop`ping either the `Module` or the engine.
     fn clear_module(&mut self) {
@@ -207,6 +405,31 @@ impl<'ctx> EvmLlvmBackend<'ctx> {
             unsafe { global.delete() };
         }
         self.functions.clear();
+        self.function_opt_levels.clear();
+        self.jit_symbols.clear();
+    }
+
+    fn pass_pipeline(&self, level: OptimizationLevel) -> &str {
+        if level == self.opt_level {
+            if let Some(pipeline) = &self.pass_pipeline {
+                return pipeline;
+            }
+        }
+        // From `opt --help`, `-passes`.
+        match level {
+            OptimizationLevel::None => "default<O0>",
+            OptimizationLevel::Less => "default<O1>",
+            OptimizationLevel::Default => "default<O2>",
+            OptimizationLevel::Aggressive => "default<O3>",
+        }
+    }
+
+    fn run_passes(&self, pipeline: &str) -> Result<()> {
+        if self.time_passes {
+            return passes::run_timed_passes(&self.module, pipeline, &self.machine);
+        }
+        let opts = PassBuilderOptions::create();
+        self.module.run_passes(pipeline, &self.machine, opts).map_err(error_msg)
     }
 }
 
@@ -273,6 +496,10 @@ impl<'ctx> Backend for EvmLlvmBackend<'ctx> {
         self.debug_assertions = yes;
     }
 
+    fn set_debug_file(&mut self, path: Option<&Path>) {
+        self.debug_file = path.map(Path::to_path_buf);
+    }
+
     fn opt_level(&self) -> revmc_backend::OptimizationLevel {
         convert_opt_level_rev(self.opt_level)
     }
@@ -281,10 +508,29 @@ impl<'ctx> Backend for EvmLlvmBackend<'ctx> {
         self.opt_level = convert_opt_level(level);
     }
 
+    fn set_function_opt_level(
+        &mut self,
+        id: Self::FuncId,
+        level: revmc_backend::OptimizationLevel,
+    ) {
+        self.function_opt_levels.insert(id, convert_opt_level(level));
+    }
+
     fn is_aot(&self) -> bool {
         self.aot
     }
 
+    fn codegen_settings(&self) -> String {
+        format!(
+            "llvm-{} {} {} {} {:?}",
+            inkwell::support::get_llvm_version().0,
+            self.machine.get_triple().as_str().to_string_lossy(),
+            self.machine.get_cpu(),
+            self.machine.get_feature_string().to_string_lossy(),
+            self.pass_pipeline,
+        )
+    }
+
     fn function_name_is_unique(&self, name: &str) -> bool {
         self.module.get_function(name).is_none()
     }
@@ -305,6 +551,7 @@ impl<'ctx> Backend for EvmLlvmBackend<'ctx> {
         param_names: &[&str],
         linkage: revmc_backend::Linkage,
     ) -> Result<(Self::Builder<'_>, Self::FuncId)> {
+        self.bcx.unset_current_debug_location();
         let (id, function) = if let Some((&id, &(_, function))) =
             self.functions.iter().find(|(_k, (fname, _f))| fname == name)
         {
@@ -317,6 +564,8 @@ impl<'ctx> Backend for EvmLlvmBackend<'ctx> {
                 function.get_nth_param(i as u32).expect(name).set_name(name);
             }
 
+            self.add_subprogram(function, name);
+
             let entry = self.cx.append_basic_block(function, "entry");
             self.bcx.position_at_end(entry);
 
@@ -330,19 +579,55 @@ impl<'ctx> Backend for EvmLlvmBackend<'ctx> {
     }
 
     fn verify_module(&mut self) -> Result<()> {
+        if let Some(debug_info) = &self.debug_info {
+            debug_info.builder.finalize();
+        }
         self.module.verify().map_err(error_msg)
     }
 
     fn optimize_module(&mut self) -> Result<()> {
-        // From `opt --help`, `-passes`.
-        let passes = match self.opt_level {
-            OptimizationLevel::None => "default<O0>",
-            OptimizationLevel::Less => "default<O1>",
-            OptimizationLevel::Default => "default<O2>",
-            OptimizationLevel::Aggressive => "default<O3>",
-        };
-        let opts = PassBuilderOptions::create();
-        self.module.run_passes(passes, &self.machine, opts).map_err(error_msg)
+        let function_level =
+            |id: &u32| self.function_opt_levels.get(id).copied().unwrap_or(self.opt_level);
+        let mut levels = Vec::new();
+        for id in self.functions.keys() {
+            let level = function_level(id);
+            if !levels.contains(&level) {
+                levels.push(level);
+            }
+        }
+        if levels.len() <= 1 {
+            let level = levels.first().copied().unwrap_or(self.opt_level);
+            return self.run_passes(self.pass_pipeline(level));
+        }
+
+        // Optimize functions grouped by level, hiding the other functions from the optimizer with
+        // `optnone`, which also requires `noinline`.
+        // Functions that are not in `self.functions`, like the IR builtins shared by functions of
+        // different levels, are optimized once per group, so they end up optimized with the
+        // highest level. They can still be inlined into the functions of any group.
+        let kinds = ["optnone", "noinline"].map(Attribute::get_named_enum_kind_id);
+        for level in levels {
+            let _span = debug_span!("optimize_level", ?level).entered();
+            let mut marked = Vec::new();
+            for (id, &(_, function)) in &self.functions {
+                if function_level(id) == level {
+                    continue;
+                }
+                for kind in kinds {
+                    if function.get_enum_attribute(AttributeLoc::Function, kind).is_none() {
+                        let attr = self.cx.create_enum_attribute(kind, 0);
+                        function.add_attribute(AttributeLoc::Function, attr);
+                        marked.push((function, kind));
+                    }
+                }
+            }
+            let result = self.run_passes(self.pass_pipeline(level));
+            for (function, kind) in marked {
+                function.remove_enum_attribute(AttributeLoc::Function, kind);
+            }
+            result?;
+        }
+        Ok(())
     }
 
     fn write_object<W: std::io::Write>(&mut self, mut w: W) -> Result<()> {
@@ -354,12 +639,30 @@ impl<'ctx> Backend for EvmLlvmBackend<'ctx> {
         Ok(())
     }
 
+    fn set_gdb_jit_registration(&mut self, yes: bool) {
+        if yes == self.gdb_jit_registration {
+            return;
+        }
+        self.gdb_jit_registration = yes;
+        if let Some(exec_engine) = &self.exec_engine {
+            listeners::set_gdb_listener(exec_engine, yes);
+        }
+    }
+
     fn jit_function(&mut self, id: Self::FuncId) -> Result<usize> {
         let name = self.id_to_name(id);
         let addr = self.exec_engine().get_function_address(name)?;
         Ok(addr)
     }
 
+    fn function_size(&mut self, id: Self::FuncId) -> Result<Option<usize>> {
+        if self.aot {
+            return Ok(None);
+        }
+        let name = self.id_to_name(id);
+        Ok(self.jit_symbols.get(name).map(|(_, size)| size))
+    }
+
     unsafe fn free_function(&mut self, id: Self::FuncId) -> Result<()> {
         let name = self.id_to_name(id);
         let function = self.exec_engine().get_function_value(name)?;
@@ -369,14 +672,23 @@ impl<'ctx> Backend for EvmLlvmBackend<'ctx> {
     }
 
     unsafe fn free_all_functions(&mut self) -> Result<()> {
+        self.debug_info = None;
         self.clear_module();
         if let Some(exec_engine) = &self.exec_engine {
             exec_engine.remove_module(&self.module).map_err(|e| Error::msg(e.to_string()))?;
+            for module in self.imported_modules.drain(..) {
+                exec_engine.remove_module(&module).map_err(|e| Error::msg(e.to_string()))?;
+            }
         }
         self.module = create_module(self.cx, &self.machine)?;
         if self.exec_engine.is_some() {
-            self.exec_engine =
-                Some(self.module.create_jit_execution_engine(self.opt_level).map_err(error_msg)?);
+            let exec_engine =
+                self.module.create_jit_execution_engine(self.opt_level).map_err(error_msg)?;
+            self.jit_symbols.register(&exec_engine);
+            if self.gdb_jit_registration {
+                listeners::set_gdb_listener(&exec_engine, true);
+            }
+            self.exec_engine = Some(exec_engine);
         }
         Ok(())
     }
@@ -384,10 +696,52 @@ impl<'ctx> Backend for EvmLlvmBackend<'ctx> {
 
 impl Drop for EvmLlvmBackend<'_> {
     fn drop(&mut self) {
+        self.debug_info = None;
         self.clear_module();
     }
 }
 
+/// The debug info builder and compile unit of a module.
+#[derive(Debug)]
+struct DebugInfo<'ctx> {
+    builder: DebugInfoBuilder<'ctx>,
+    cu: DICompileUnit<'ctx>,
+}
+
+impl<'ctx> DebugInfo<'ctx> {
+    fn new(cx: &'ctx Context, module: &Module<'ctx>, path: &Path, is_optimized: bool) -> Self {
+        module.add_basic_value_flag(
+            "Debug Info Version",
+            FlagBehavior::Warning,
+            cx.i32_type().const_int(debug_metadata_version() as u64, false),
+        );
+        module.add_basic_value_flag(
+            "Dwarf Version",
+            FlagBehavior::Warning,
+            cx.i32_type().const_int(4, false),
+        );
+        let (filename, directory) = split_path(path);
+        let (builder, cu) = module.create_debug_info_builder(
+            true,
+            DWARFSourceLanguage::C,
+            &filename,
+            &directory,
+            "revmc",
+            is_optimized,
+            "",
+            0,
+            "",
+            DWARFEmissionKind::LineTablesOnly,
+            0,
+            false,
+            false,
+            "",
+            "",
+        );
+        Self { builder, cu }
+    }
+}
+
 /// Cached target information for the host machine.
 #[derive(Debug)]
 struct TargetInfo {
@@ -642,6 +996,18 @@ impl Builder for EvmLlvmBuilder<'_, '_> {
         ins.set_metadata(metadata, self.cx.get_kind_id("annotation")).unwrap();
     }
 
+    fn set_debug_location(&mut self, line: u32, column: u32) {
+        let Some(debug_info) = &self.debug_info else { return };
+        let Some(subprogram) = self.function.get_subprogram() else { return };
+        let scope = subprogram.as_debug_info_scope();
+        let loc = debug_info.builder.create_debug_location(self.cx, line, column, scope, None);
+        self.bcx.set_current_debug_location(loc);
+    }
+
+    fn clear_debug_location(&mut self) {
+        self.bcx.unset_current_debug_location();
+    }
+
     fn fn_param(&mut self, index: usize) -> Self::Value {
         self.function.get_nth_param(index as _).unwrap()
     }
@@ -709,6 +1075,13 @@ impl Builder for EvmLlvmBuilder<'_, '_> {
         value
     }
 
+    fn load_atomic(&mut self, ty: Self::Type, ptr: Self::Value, name: &str) -> Self::Value {
+        let value = self.load(ty, ptr, name);
+        let inst = value.as_instruction_value().unwrap();
+        inst.set_atomic_ordering(AtomicOrdering::Monotonic).unwrap();
+        value
+    }
+
     fn store(&mut self, value: Self::Value, ptr: Self::Value) {
         self.bcx.build_store(ptr.into_pointer_value(), value).unwrap();
     }
@@ -718,6 +1091,11 @@ impl Builder for EvmLlvmBuilder<'_, '_> {
         inst.set_alignment(1).unwrap();
     }
 
+    fn store_atomic(&mut self, value: Self::Value, ptr: Self::Value) {
+        let inst = self.bcx.build_store(ptr.into_pointer_value(), value).unwrap();
+        inst.set_atomic_ordering(AtomicOrdering::Monotonic).unwrap();
+    }
+
     fn nop(&mut self) {
         // LLVM doesn't have a NOP instruction.
     }
@@ -1068,6 +1446,9 @@ impl Builder for EvmLlvmBuilder<'_, '_> {
         }
 
         let before = self.current_block();
+        // Helper functions don't have debug info.
+        let debug_location = self.bcx.get_current_debug_location();
+        self.bcx.unset_current_debug_location();
 
         let func_ty = self.fn_type(ret, params);
         let function = self.module.add_function(name, func_ty, Some(convert_linkage(linkage)));
@@ -1079,6 +1460,9 @@ impl Builder for EvmLlvmBuilder<'_, '_> {
         if let Some(before) = before {
             self.bcx.position_at_end(before);
         }
+        if let Some(debug_location) = debug_location {
+            self.bcx.set_current_debug_location(debug_location);
+        }
 
         self.function = prev_function;
 
@@ -1315,6 +1699,12 @@ fn error_msg(msg: inkwell::support::LLVMString) -> revmc_backend::Error {
     revmc_backend::Error::msg(msg.to_string_lossy().trim_end().to_string())
 }
 
+fn split_path(path: &Path) -> (Cow<'_, str>, Cow<'_, str>) {
+    let filename = path.file_name().map(|s| s.to_string_lossy()).unwrap_or_default();
+    let directory = path.parent().map(|s| s.to_string_lossy()).unwrap_or_default();
+    (filename, directory)
+}
+
 fn fmt_ty(ty: BasicTypeEnum<'_>) -> impl std::fmt::Display {
     ty.print_to_string().to_str().unwrap().trim_matches('"').to_string()
 }
@@ -0,0 +1,112 @@
+//! JIT event listeners, implemented in `llvm_ext.cpp`.
+
+use inkwell::{
+    execution_engine::ExecutionEngine,
+    llvm_sys::{
+        execution_engine::{LLVMCreateGDBRegistrationListener, LLVMExecutionEngineRef},
+        prelude::LLVMJITEventListenerRef,
+    },
+};
+use rustc_hash::FxHashMap;
+use std::{cell::RefCell, ffi::c_void, fmt};
+
+type SymbolCallback =
+    unsafe extern "C" fn(opaque: *mut c_void, name: *const u8, len: usize, addr: u64, size: u64);
+
+extern "C" {
+    fn revmc_llvm_register_jit_event_listener(
+        ee: LLVMExecutionEngineRef,
+        listener: LLVMJITEventListenerRef,
+    );
+    fn revmc_llvm_unregister_jit_event_listener(
+        ee: LLVMExecutionEngineRef,
+        listener: LLVMJITEventListenerRef,
+    );
+    fn revmc_llvm_create_symbol_listener(
+        callback: SymbolCallback,
+        opaque: *mut c_void,
+    ) -> LLVMJITEventListenerRef;
+    fn revmc_llvm_dispose_symbol_listener(listener: LLVMJITEventListenerRef);
+}
+
+/// Records the address and size of the functions loaded by an execution engine.
+///
+/// The sizes are read from the objects as they are loaded in memory, so they describe the actual
+/// machine code of the functions.
+///
+/// Must outlive the execution engines it is registered with.
+pub(crate) struct SymbolListener {
+    listener: LLVMJITEventListenerRef,
+    /// Boxed to have a stable address for the callback.
+    symbols: Box<RefCell<FxHashMap<String, (usize, usize)>>>,
+}
+
+impl fmt::Debug for SymbolListener {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SymbolListener").field("symbols", &self.symbols).finish_non_exhaustive()
+    }
+}
+
+impl SymbolListener {
+    pub(crate) fn new() -> Self {
+        let symbols = Box::<RefCell<FxHashMap<_, _>>>::default();
+        let opaque = &*symbols as *const RefCell<_> as *mut c_void;
+        let listener = unsafe { revmc_llvm_create_symbol_listener(on_symbol, opaque) };
+        Self { listener, symbols }
+    }
+
+    /// Registers the listener with the given execution engine.
+    pub(crate) fn register(&self, ee: &ExecutionEngine<'_>) {
+        unsafe { revmc_llvm_register_jit_event_listener(ee.as_mut_ptr(), self.listener) };
+    }
+
+    /// Returns the address and size of the given function, if it has been loaded.
+    pub(crate) fn get(&self, name: &str) -> Option<(usize, usize)> {
+        self.symbols.borrow().get(name).copied()
+    }
+
+    pub(crate) fn clear(&self) {
+        self.symbols.borrow_mut().clear();
+    }
+}
+
+impl Drop for SymbolListener {
+    fn drop(&mut self) {
+        unsafe { revmc_llvm_dispose_symbol_listener(self.listener) };
+    }
+}
+
+/// Registers or unregisters LLVM's GDB JIT interface listener with the given execution engine.
+///
+/// The listener registers the objects loaded afterwards, including their debug info, with GDB, and
+/// unregisters them when they are freed.
+///
+/// See <https://sourceware.org/gdb/current/onlinedocs/gdb.html/JIT-Interface.html>.
+pub(crate) fn set_gdb_listener(ee: &ExecutionEngine<'_>, yes: bool) {
+    unsafe {
+        // A global that does not need to be disposed.
+        let listener = LLVMCreateGDBRegistrationListener();
+        if yes {
+            revmc_llvm_register_jit_event_listener(ee.as_mut_ptr(), listener);
+        } else {
+            revmc_llvm_unregister_jit_event_listener(ee.as_mut_ptr(), listener);
+        }
+    }
+}
+
+unsafe extern "C" fn on_symbol(
+    opaque: *mut c_void,
+    name: *const u8,
+    len: usize,
+    addr: u64,
+    size: u64,
+) {
+    let symbols = unsafe { &*(opaque as *const RefCell<FxHashMap<String, (usize, usize)>>) };
+    let name = unsafe { std::slice::from_raw_parts(name, len) };
+    let Ok(mut name) = std::str::from_utf8(name) else { return };
+    // Mach-O symbols have a leading underscore that is not part of the IR name.
+    if cfg!(target_vendor = "apple") {
+        name = name.strip_prefix('_').unwrap_or(name);
+    }
+    symbols.borrow_mut().insert(name.to_string(), (addr as usize, size as usize));
+}
@@ -0,0 +1,166 @@
+// LLVM functionality that is not exposed through the C API: JIT event listeners for the MCJIT
+// execution engine, and pass timing.
+
+#include "llvm-c/Error.h"
+#include "llvm-c/ExecutionEngine.h"
+#include "llvm-c/TargetMachine.h"
+#include "llvm/Analysis/LazyCallGraph.h"
+#include "llvm/Analysis/LoopInfo.h"
+#include "llvm/ExecutionEngine/ExecutionEngine.h"
+#include "llvm/ExecutionEngine/JITEventListener.h"
+#include "llvm/IR/Module.h"
+#include "llvm/IR/PassInstrumentation.h"
+#include "llvm/Object/ObjectFile.h"
+#include "llvm/Object/SymbolSize.h"
+#include "llvm/Passes/PassBuilder.h"
+#include "llvm/Passes/StandardInstrumentations.h"
+#include "llvm/Support/Error.h"
+#include "llvm/Target/TargetMachine.h"
+
+#include <chrono>
+#include <optional>
+#include <string>
+#include <vector>
+
+using namespace llvm;
+
+extern "C" {
+/// Called for every function symbol of an object loaded by the execution engine, with its load
+/// address and size.
+typedef void (*RevmcSymbolCallback)(void *opaque, const char *name, size_t name_len, uint64_t addr,
+                                    uint64_t size);
+
+/// Called after every pass that was not skipped, with the name of the IR unit it ran on and the
+/// time spent in it, in nanoseconds.
+typedef void (*RevmcPassCallback)(const char *pass, size_t pass_len, const char *ir, size_t ir_len,
+                                  uint64_t nanos);
+}
+
+namespace {
+/// Reports the function symbols of the objects loaded by the execution engine.
+///
+/// This is the same logic as LLVM's perf JIT event listener.
+class SymbolListener : public JITEventListener {
+public:
+  SymbolListener(RevmcSymbolCallback callback, void *opaque) : callback(callback), opaque(opaque) {}
+
+  void notifyObjectLoaded(ObjectKey key, const object::ObjectFile &obj,
+                          const RuntimeDyld::LoadedObjectInfo &info) override {
+    // The debug object has its sections relocated to their load addresses.
+    object::OwningBinary<object::ObjectFile> debugObjOwner = info.getObjectForDebug(obj);
+    const object::ObjectFile *debugObj = debugObjOwner.getBinary();
+    if (!debugObj)
+      return;
+
+    for (const auto &pair : object::computeSymbolSizes(*debugObj)) {
+      const object::SymbolRef &sym = pair.first;
+      Expected<object::SymbolRef::Type> type = sym.getType();
+      if (!type) {
+        consumeError(type.takeError());
+        continue;
+      }
+      if (*type != object::SymbolRef::ST_Function)
+        continue;
+      Expected<StringRef> name = sym.getName();
+      if (!name) {
+        consumeError(name.takeError());
+        continue;
+      }
+      Expected<uint64_t> addr = sym.getAddress();
+      if (!addr) {
+        consumeError(addr.takeError());
+        continue;
+      }
+      callback(opaque, name->data(), name->size(), *addr, pair.second);
+    }
+  }
+
+private:
+  RevmcSymbolCallback callback;
+  void *opaque;
+};
+
+/// Returns the name of the IR unit a pass ran on.
+std::string irName(const Any &ir) {
+  if (const auto *f = any_cast<const Function *>(&ir))
+    return (*f)->getName().str();
+  if (const auto *m = any_cast<const Module *>(&ir))
+    return (*m)->getName().str();
+  if (const auto *c = any_cast<const LazyCallGraph::SCC *>(&ir))
+    return (*c)->getName();
+  if (const auto *l = any_cast<const Loop *>(&ir))
+    return (*l)->getName().str();
+  return "";
+}
+} // namespace
+
+extern "C" {
+void revmc_llvm_register_jit_event_listener(LLVMExecutionEngineRef ee,
+                                            LLVMJITEventListenerRef listener) {
+  unwrap(ee)->RegisterJITEventListener(unwrap(listener));
+}
+
+void revmc_llvm_unregister_jit_event_listener(LLVMExecutionEngineRef ee,
+                                              LLVMJITEventListenerRef listener) {
+  unwrap(ee)->UnregisterJITEventListener(unwrap(listener));
+}
+
+LLVMJITEventListenerRef revmc_llvm_create_symbol_listener(RevmcSymbolCallback callback,
+                                                          void *opaque) {
+  return wrap(new SymbolListener(callback, opaque));
+}
+
+void revmc_llvm_dispose_symbol_listener(LLVMJITEventListenerRef listener) {
+  delete unwrap(listener);
+}
+
+/// Same as `LLVMRunPasses` with the default options, but reports the time spent in each pass to
+/// `callback`.
+LLVMErrorRef revmc_llvm_run_timed_passes(LLVMModuleRef m, const char *passes,
+                                         LLVMTargetMachineRef tm, RevmcPassCallback callback) {
+  using Clock = std::chrono::steady_clock;
+
+  Module *mod = unwrap(m);
+  TargetMachine *machine = reinterpret_cast<TargetMachine *>(tm);
+  PassInstrumentationCallbacks pic;
+  PassBuilder pb(machine, PipelineTuningOptions(), std::nullopt, &pic);
+
+  LoopAnalysisManager lam;
+  FunctionAnalysisManager fam;
+  CGSCCAnalysisManager cgam;
+  ModuleAnalysisManager mam;
+  pb.registerLoopAnalyses(lam);
+  pb.registerFunctionAnalyses(fam);
+  pb.registerCGSCCAnalyses(cgam);
+  pb.registerModuleAnalyses(mam);
+  pb.crossRegisterProxies(lam, fam, cgam, mam);
+
+  StandardInstrumentations si(mod->getContext(), false);
+  si.registerCallbacks(pic, &mam);
+
+  // Passes nest, e.g. function passes run inside of a module pass adaptor.
+  std::vector<Clock::time_point> starts;
+  auto report = [&](StringRef pass, const std::string &ir) {
+    auto elapsed = Clock::now() - starts.back();
+    starts.pop_back();
+    // Pass managers and adaptors only run other passes.
+    if (pass.contains("PassManager") || pass.contains("PassAdaptor"))
+      return;
+    uint64_t nanos = std::chrono::duration_cast<std::chrono::nanoseconds>(elapsed).count();
+    callback(pass.data(), pass.size(), ir.data(), ir.size(), nanos);
+  };
+  pic.registerBeforeNonSkippedPassCallback(
+      [&](StringRef, Any) { starts.push_back(Clock::now()); });
+  pic.registerAfterPassCallback(
+      [&](StringRef pass, Any ir, const PreservedAnalyses &) { report(pass, irName(ir)); });
+  // The IR unit may have been deleted by the pass.
+  pic.registerAfterPassInvalidatedCallback(
+      [&](StringRef pass, const PreservedAnalyses &) { report(pass, ""); });
+
+  ModulePassManager mpm;
+  if (Error err = pb.parsePassPipeline(mpm, passes))
+    return wrap(std::move(err));
+  mpm.run(*mod, mam);
+  return LLVMErrorSuccess;
+}
+}
@@ -0,0 +1,69 @@
+//! Pass timing, implemented in `llvm_ext.cpp`.
+
+use crate::{error_msg, utils::llvm_string};
+use inkwell::{
+    llvm_sys::{
+        error::{LLVMErrorRef, LLVMGetErrorMessage},
+        prelude::LLVMModuleRef,
+        target_machine::LLVMTargetMachineRef,
+    },
+    module::Module,
+    targets::TargetMachine,
+};
+use revmc_backend::Result;
+use std::{
+    ffi::{c_char, CString},
+    time::Duration,
+};
+
+type PassCallback = unsafe extern "C" fn(
+    pass: *const u8,
+    pass_len: usize,
+    ir: *const u8,
+    ir_len: usize,
+    nanos: u64,
+);
+
+extern "C" {
+    fn revmc_llvm_run_timed_passes(
+        module: LLVMModuleRef,
+        passes: *const c_char,
+        machine: LLVMTargetMachineRef,
+        callback: PassCallback,
+    ) -> LLVMErrorRef;
+}
+
+/// Runs the pass pipeline on the module, like [`Module::run_passes`] with the default options.
+///
+/// Emits an event with the time spent in every pass that was not skipped.
+pub(crate) fn run_timed_passes(
+    module: &Module<'_>,
+    pipeline: &str,
+    machine: &TargetMachine,
+) -> Result<()> {
+    let pipeline = CString::new(pipeline)?;
+    let err = unsafe {
+        revmc_llvm_run_timed_passes(
+            module.as_mut_ptr(),
+            pipeline.as_ptr(),
+            machine.as_mut_ptr(),
+            on_pass,
+        )
+    };
+    if err.is_null() {
+        return Ok(());
+    }
+    Err(error_msg(unsafe { llvm_string(LLVMGetErrorMessage(err)) }))
+}
+
+unsafe extern "C" fn on_pass(
+    pass: *const u8,
+    pass_len: usize,
+    ir: *const u8,
+    ir_len: usize,
+    nanos: u64,
+) {
+    let pass = String::from_utf8_lossy(unsafe { std::slice::from_raw_parts(pass, pass_len) });
+    let ir = String::from_utf8_lossy(unsafe { std::slice::from_raw_parts(ir, ir_len) });
+    info!(%pass, %ir, elapsed = ?Duration::from_nanos(nanos), "ran pass");
+}
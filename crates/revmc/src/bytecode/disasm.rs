@@ -0,0 +1,209 @@
+//! Annotated disassembly.
+
+use super::{Bytecode, InstData, InstFlags};
+use revm_interpreter::opcode as op;
+use std::fmt::{self, Write};
+
+/// The width of the opcode column. Longer opcodes, like `PUSH32`, push the remaining columns.
+const OPCODE_WIDTH: usize = 24;
+
+/// Annotated disassembly of an analyzed [`Bytecode`], returned by [`Bytecode::disassembly`].
+///
+/// Each line contains the instruction's program counter, base gas cost, stack inputs and outputs,
+/// opcode and immediate, resolved jump targets, and [`InstFlags`]. Sections are prefixed by a
+/// comment line with their total base gas cost, required stack height, and maximum stack growth;
+/// in EOF, each code section is also prefixed by its type.
+pub(crate) struct Disassembly<'b, 'a>(&'b Bytecode<'a>);
+
+impl<'a> Bytecode<'a> {
+    /// Returns the annotated disassembly of the bytecode.
+    ///
+    /// This should be called after [`analyze`](Self::analyze).
+    pub(crate) fn disassembly(&self) -> Disassembly<'_, 'a> {
+        Disassembly(self)
+    }
+}
+
+impl fmt::Display for Disassembly<'_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bytecode = self.0;
+        let is_eof = bytecode.is_eof();
+        let mut code_section = None;
+        let mut line = String::new();
+        for (inst, data) in bytecode.iter_all_insts() {
+            if is_eof {
+                let section = bytecode.pc_to_eof_section(data.pc as usize);
+                if code_section != Some(section) {
+                    code_section = Some(section);
+                    let ty = &bytecode.expect_eof().body.types_section[section];
+                    if inst != 0 {
+                        writeln!(f)?;
+                    }
+                    writeln!(
+                        f,
+                        "; code section {section}: inputs {}, outputs {:#x}, max stack height {}",
+                        ty.inputs, ty.outputs, ty.max_stack_size
+                    )?;
+                }
+            }
+
+            let section = &data.section;
+            if !section.is_empty() {
+                writeln!(
+                    f,
+                    "; section: gas {}, inputs {}, max growth {:+}",
+                    section.gas_cost, section.inputs, section.max_growth
+                )?;
+            }
+
+            line.clear();
+            let (inp, out) = data.stack_io();
+            let opcode = data.to_op_in(bytecode).to_string();
+            write!(
+                line,
+                "{:#06x}  {:>5}  {inp:>2} -> {out:<2}  {opcode:<OPCODE_WIDTH$}",
+                data.pc, data.base_gas
+            )?;
+            self.write_jump_targets(&mut line, data)?;
+            if !data.flags.is_empty() {
+                line.push_str("  [");
+                for (i, (name, _)) in data.flags.iter_names().enumerate() {
+                    if i > 0 {
+                        line.push_str(", ");
+                    }
+                    line.push_str(name);
+                }
+                line.push(']');
+            }
+            writeln!(f, "{}", line.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+impl Disassembly<'_, '_> {
+    fn write_jump_targets(&self, line: &mut String, data: &InstData) -> fmt::Result {
+        let bytecode = self.0;
+        if data.is_legacy_jump() {
+            line.push_str("  -> ");
+            if data.flags.contains(InstFlags::INVALID_JUMP) {
+                line.push_str("invalid");
+            } else if data.flags.contains(InstFlags::STATIC_JUMP) {
+                let target = bytecode.inst(data.data as usize);
+                write!(line, "{:#06x}", target.pc)?;
+            } else {
+                line.push_str("dynamic");
+            }
+        } else if data.is_eof_jump() && bytecode.is_eof() {
+            line.push_str("  -> ");
+            for (i, target) in bytecode.iter_rjump_targets(data) {
+                if i > 0 {
+                    line.push_str(", ");
+                }
+                write!(line, "{target:#06x}")?;
+            }
+        } else if matches!(data.opcode, op::CALLF | op::JUMPF) && bytecode.is_eof() {
+            if let Some(&[a, b]) = bytecode.get_imm(data) {
+                write!(line, "  -> code section {}", u16::from_be_bytes([a, b]))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use revm_primitives::{
+        eof::{EofBody, TypesSection},
+        Bytes, Eof, SpecId,
+    };
+    use std::borrow::Cow;
+
+    fn disassemble(code: &[u8]) -> String {
+        let mut bytecode = Bytecode::new(code, None, SpecId::CANCUN);
+        bytecode.analyze().unwrap();
+        bytecode.disassembly().to_string()
+    }
+
+    #[test]
+    fn legacy() {
+        #[rustfmt::skip]
+        let code = [
+            op::PUSH1, 5, op::JUMP,
+            op::INVALID, op::ADD,
+            op::JUMPDEST,
+            op::PUSH0, op::PUSH0, op::RETURN,
+        ];
+        let s = disassemble(&code);
+        let lines = s.lines().collect::<Vec<_>>();
+        assert!(lines[0].starts_with("; section: gas "), "{s}");
+        assert!(lines[1].starts_with("0x0000"), "{s}");
+        assert!(lines[1].contains("PUSH1 0x05"), "{s}");
+        assert!(lines[2].contains("JUMP"), "{s}");
+        assert!(lines[2].contains("-> 0x0005"), "{s}");
+        assert!(lines[2].contains("STATIC_JUMP"), "{s}");
+        assert!(lines[3].contains("INVALID"), "{s}");
+        assert!(lines[4].contains("ADD"), "{s}");
+        assert!(lines[4].contains("DEAD_CODE"), "{s}");
+        assert!(lines[5].starts_with("; section: gas 5, inputs 0"), "{s}");
+        assert!(lines[5].ends_with("max growth +2"), "{s}");
+        assert!(lines[6].starts_with("0x0005"), "{s}");
+    }
+
+    #[test]
+    fn dynamic_jump() {
+        let code = [op::CALLDATASIZE, op::JUMP, op::JUMPDEST, op::STOP];
+        let s = disassemble(&code);
+        assert!(s.contains("-> dynamic"), "{s}");
+    }
+    #[test]
+    fn eof() {
+        #[rustfmt::skip]
+        let code: [&[u8]; 3] = [
+            &[
+                op::CALLF, 0, 1,
+                op::PUSH0, op::RJUMPI, 0, 3,
+                op::JUMPF, 0, 2,
+                op::STOP,
+            ],
+            &[op::RETF],
+            &[op::STOP],
+        ];
+        let body = EofBody {
+            types_section: vec![
+                TypesSection { inputs: 0, outputs: 0x80, max_stack_size: 1 },
+                TypesSection { inputs: 0, outputs: 0, max_stack_size: 0 },
+                TypesSection { inputs: 0, outputs: 0x80, max_stack_size: 0 },
+            ],
+            code_section: code.into_iter().map(Bytes::from_static).collect(),
+            container_section: vec![],
+            data_section: Bytes::new(),
+            is_data_filled: true,
+        };
+        let eof = Eof::decode(body.into_eof().raw).unwrap();
+        let mut bytecode = Bytecode::new(&[], Some(Cow::Owned(eof)), SpecId::OSAKA);
+        bytecode.analyze().unwrap();
+        let s = bytecode.disassembly().to_string();
+        let lines = s.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], "; code section 0: inputs 0, outputs 0x80, max stack height 1", "{s}");
+        assert!(lines[1].starts_with("; section: gas 11, inputs 0"), "{s}");
+        assert!(lines[2].starts_with("0x0000"), "{s}");
+        assert!(lines[2].contains("CALLF"), "{s}");
+        assert!(lines[2].contains("-> code section 1"), "{s}");
+        assert!(lines[4].contains("RJUMPI"), "{s}");
+        assert!(lines[4].contains("-> 0x000a"), "{s}");
+        assert!(lines[6].contains("JUMPF"), "{s}");
+        assert!(lines[6].contains("-> code section 2"), "{s}");
+        assert!(lines[7].contains("EOF_JUMPDEST"), "{s}");
+        assert_eq!(lines[8], "", "{s}");
+        assert_eq!(lines[9], "; code section 1: inputs 0, outputs 0x0, max stack height 0", "{s}");
+        assert!(lines[11].starts_with("0x000b"), "{s}");
+        assert!(lines[11].contains("RETF"), "{s}");
+        assert_eq!(
+            lines[13], "; code section 2: inputs 0, outputs 0x80, max stack height 0",
+            "{s}"
+        );
+        assert!(lines[14].starts_with("0x000c"), "{s}");
+    }
+}
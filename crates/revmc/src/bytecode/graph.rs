@@ -0,0 +1,381 @@
+//! Control-flow graph.
+
+use super::{Bytecode, Inst, InstFlags, Opcode};
+use revm_interpreter::opcode as op;
+use rustc_hash::FxHashMap;
+use std::io;
+
+/// The control-flow graph of an analyzed bytecode.
+///
+/// Dead code is not part of the graph.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ControlFlowGraph {
+    /// Whether the bytecode is EOF.
+    pub is_eof: bool,
+    /// The basic blocks, in program order. The entry block is the first one.
+    pub blocks: Vec<BasicBlock>,
+    /// The edges between the blocks.
+    pub edges: Vec<Edge>,
+}
+
+/// A basic block in a [`ControlFlowGraph`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct BasicBlock {
+    /// The EOF code section this block belongs to, if any.
+    pub code_section: Option<usize>,
+    /// The program counter of the first instruction.
+    pub start_pc: u32,
+    /// The program counter of the last instruction.
+    pub end_pc: u32,
+    /// The number of instructions.
+    pub len: usize,
+    /// The total base gas cost of the instructions.
+    pub gas_cost: u64,
+    /// The opcode of the last instruction.
+    pub terminator: u8,
+}
+
+/// An edge in a [`ControlFlowGraph`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Edge {
+    /// The index of the source block.
+    pub from: usize,
+    /// The index of the destination block.
+    pub to: usize,
+    /// The kind of edge.
+    pub kind: EdgeKind,
+}
+
+/// The kind of an [`Edge`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum EdgeKind {
+    /// Execution continues with the next instruction.
+    Fallthrough,
+    /// A jump whose target is known at compile time: `JUMP`, `JUMPI`, `RJUMP*` and `JUMPF`.
+    Jump,
+    /// A `JUMP` or `JUMPI` whose target is only known at runtime. These have an edge to every
+    /// reachable `JUMPDEST`, like the jump table in the compiled function.
+    DynamicJump,
+    /// An EOF `CALLF` to the first block of the called code section.
+    Call,
+    /// An EOF `RETF` to the instruction after each `CALLF` of its code section.
+    Return,
+}
+
+impl ControlFlowGraph {
+    /// Returns the edges going out of the given block.
+    pub fn successors(&self, block: usize) -> impl Iterator<Item = &Edge> + '_ {
+        self.edges.iter().filter(move |e| e.from == block)
+    }
+
+    /// Returns the edges coming into the given block.
+    pub fn predecessors(&self, block: usize) -> impl Iterator<Item = &Edge> + '_ {
+        self.edges.iter().filter(move |e| e.to == block)
+    }
+
+    /// Writes the graph in the Graphviz DOT format.
+    ///
+    /// Each block is labeled with its program counter range, number of instructions, base gas
+    /// cost, and terminator. In EOF, the blocks of each code section are grouped in a cluster.
+    pub fn write_dot<W: io::Write>(&self, mut w: W) -> io::Result<()> {
+        writeln!(w, "digraph cfg {{")?;
+        writeln!(w, "    node [shape=box, fontname=monospace];")?;
+        let mut code_section = None;
+        for (i, block) in self.blocks.iter().enumerate() {
+            if block.code_section != code_section {
+                if code_section.is_some() {
+                    writeln!(w, "    }}")?;
+                }
+                code_section = block.code_section;
+                if let Some(section) = code_section {
+                    writeln!(w, "    subgraph cluster_{section} {{")?;
+                    writeln!(w, "        label=\"code section {section}\";")?;
+                }
+            }
+            let BasicBlock { code_section: _, start_pc, end_pc, len, gas_cost, terminator } =
+                *block;
+            let terminator = Opcode { opcode: terminator, immediate: None };
+            writeln!(
+                w,
+                "    bb{i} [label=\"{start_pc:#06x}..={end_pc:#06x}\\n\
+                 {len} instructions, gas {gas_cost}\\n{terminator}\"];"
+            )?;
+        }
+        if code_section.is_some() {
+            writeln!(w, "    }}")?;
+        }
+        for &Edge { from, to, kind } in &self.edges {
+            let attrs = match kind {
+                EdgeKind::Fallthrough => "",
+                EdgeKind::Jump => " [color=blue]",
+                EdgeKind::DynamicJump => " [color=red, style=dashed]",
+                EdgeKind::Call => " [color=darkgreen, style=dotted, label=\"call\"]",
+                EdgeKind::Return => " [color=darkgreen, style=dotted, label=\"return\"]",
+            };
+            writeln!(w, "    bb{from} -> bb{to}{attrs};")?;
+        }
+        writeln!(w, "}}")
+    }
+}
+
+impl Bytecode<'_> {
+    /// Builds the control-flow graph of the bytecode.
+    ///
+    /// This must be called after [`analyze`](Self::analyze).
+    #[instrument(name = "cfg", level = "debug", skip_all)]
+    pub(crate) fn control_flow_graph(&self) -> ControlFlowGraph {
+        let is_eof = self.is_eof();
+
+        // Find the first instruction of each block.
+        let mut leaders = vec![false; self.insts.len()];
+        let mut prev = None;
+        for (inst, data) in self.iter_insts() {
+            if !matches!(prev, Some(prev) if prev + 1 == inst)
+                || data.is_reachable_jumpdest(is_eof, self.has_dynamic_jumps())
+            {
+                leaders[inst] = true;
+            }
+            if (data.is_branching(is_eof) || (is_eof && data.opcode == op::CALLF))
+                && inst + 1 < leaders.len()
+            {
+                leaders[inst + 1] = true;
+            }
+            prev = Some(inst);
+        }
+        if is_eof {
+            for section in 0..self.expect_eof().body.code_section.len() {
+                leaders[self.eof_section_inst(section)] = true;
+            }
+        }
+
+        // Build the blocks.
+        let mut blocks = Vec::<BasicBlock>::new();
+        let mut block_ends = Vec::<Inst>::new();
+        let mut inst_to_block = FxHashMap::default();
+        for (inst, data) in self.iter_insts() {
+            if leaders[inst] || blocks.is_empty() {
+                inst_to_block.insert(inst, blocks.len());
+                blocks.push(BasicBlock {
+                    code_section: is_eof.then(|| self.pc_to_eof_section(data.pc as usize)),
+                    start_pc: data.pc,
+                    end_pc: data.pc,
+                    len: 0,
+                    gas_cost: 0,
+                    terminator: data.opcode,
+                });
+                block_ends.push(inst);
+            }
+            let block = blocks.last_mut().unwrap();
+            block.end_pc = data.pc;
+            block.len += 1;
+            block.gas_cost += data.base_gas as u64;
+            block.terminator = data.opcode;
+            *block_ends.last_mut().unwrap() = inst;
+        }
+
+        // Connect them.
+        let mut edges = Vec::new();
+        let dynamic_targets = || {
+            self.iter_insts()
+                .filter(|(_, data)| data.is_reachable_jumpdest(is_eof, true))
+                .filter_map(|(inst, _)| inst_to_block.get(&inst).copied())
+        };
+        for (from, &last) in block_ends.iter().enumerate() {
+            let data = self.inst(last);
+            let mut edge = |to: Option<&usize>, kind| {
+                if let Some(&to) = to {
+                    edges.push(Edge { from, to, kind });
+                }
+            };
+            let fallthrough = inst_to_block.get(&(last + 1));
+            match data.opcode {
+                op::JUMP | op::JUMPI if !is_eof => {
+                    if data.flags.contains(InstFlags::INVALID_JUMP) {
+                        // Always halts.
+                    } else if data.flags.contains(InstFlags::STATIC_JUMP) {
+                        edge(inst_to_block.get(&(data.data as usize)), EdgeKind::Jump);
+                    } else {
+                        for to in dynamic_targets() {
+                            edge(Some(&to), EdgeKind::DynamicJump);
+                        }
+                    }
+                    if data.opcode == op::JUMPI {
+                        edge(fallthrough, EdgeKind::Fallthrough);
+                    }
+                }
+                op::RJUMP | op::RJUMPI | op::RJUMPV if is_eof => {
+                    for (_, target) in self.iter_rjump_target_insts(data) {
+                        edge(inst_to_block.get(&target), EdgeKind::Jump);
+                    }
+                    if data.opcode != op::RJUMP {
+                        edge(fallthrough, EdgeKind::Fallthrough);
+                    }
+                }
+                op::CALLF | op::JUMPF if is_eof => {
+                    let imm = self.get_imm(data).unwrap();
+                    let section = u16::from_be_bytes(imm.try_into().unwrap()) as usize;
+                    let target = self.eof_section_inst(section);
+                    let kind =
+                        if data.opcode == op::CALLF { EdgeKind::Call } else { EdgeKind::Jump };
+                    edge(inst_to_block.get(&target), kind);
+                }
+                op::RETF if is_eof => {
+                    let section = self.pc_to_eof_section(data.pc as usize);
+                    for &caller in self.eof_section_called_by(section) {
+                        edge(inst_to_block.get(&(caller + 1)), EdgeKind::Return);
+                    }
+                }
+                _ if data.is_diverging(is_eof) => {}
+                _ => edge(fallthrough, EdgeKind::Fallthrough),
+            }
+        }
+
+        ControlFlowGraph { is_eof, blocks, edges }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use revm_primitives::{
+        eof::{EofBody, TypesSection},
+        Bytes, Eof, SpecId,
+    };
+    use std::borrow::Cow;
+
+    fn cfg(code: &[u8]) -> ControlFlowGraph {
+        let mut bytecode = Bytecode::new(code, None, SpecId::CANCUN);
+        bytecode.analyze().unwrap();
+        bytecode.control_flow_graph()
+    }
+
+    #[test]
+    fn static_jumps() {
+        #[rustfmt::skip]
+        let code = [
+            // 0
+            op::PUSH0, op::CALLDATALOAD, op::PUSH1, 9, op::JUMPI,
+            // 5
+            op::PUSH1, 10, op::JUMP,
+            // 8
+            op::INVALID,
+            // 9
+            op::JUMPDEST,
+            // 10
+            op::JUMPDEST, op::STOP,
+        ];
+        let cfg = cfg(&code);
+        let ranges = cfg.blocks.iter().map(|b| (b.start_pc, b.end_pc)).collect::<Vec<_>>();
+        assert_eq!(ranges, [(0, 4), (5, 7), (8, 8), (9, 9), (10, 11)]);
+        assert_eq!(
+            cfg.edges,
+            [
+                Edge { from: 0, to: 3, kind: EdgeKind::Jump },
+                Edge { from: 0, to: 1, kind: EdgeKind::Fallthrough },
+                Edge { from: 1, to: 4, kind: EdgeKind::Jump },
+                Edge { from: 3, to: 4, kind: EdgeKind::Fallthrough },
+            ]
+        );
+        assert_eq!(cfg.predecessors(4).count(), 2);
+        assert_eq!(cfg.successors(2).count(), 0);
+
+        let mut dot = Vec::new();
+        cfg.write_dot(&mut dot).unwrap();
+        let dot = String::from_utf8(dot).unwrap();
+        assert!(dot.starts_with("digraph cfg {"), "{dot}");
+        assert!(dot.contains("bb0 -> bb3 [color=blue];"), "{dot}");
+    }
+
+    #[test]
+    fn dynamic_jumps() {
+        #[rustfmt::skip]
+        let code = [
+            op::CALLDATASIZE, op::JUMP,
+            op::JUMPDEST, op::STOP,
+            op::JUMPDEST, op::STOP,
+        ];
+        let cfg = cfg(&code);
+        assert_eq!(cfg.blocks.len(), 3);
+        assert_eq!(
+            cfg.edges,
+            [
+                Edge { from: 0, to: 1, kind: EdgeKind::DynamicJump },
+                Edge { from: 0, to: 2, kind: EdgeKind::DynamicJump },
+            ]
+        );
+    }
+    #[test]
+    fn eof() {
+        #[rustfmt::skip]
+        let code: [&[u8]; 3] = [
+            &[
+                // 0
+                op::PUSH0, op::RJUMPI, 0, 4,
+                // 4
+                op::CALLF, 0, 1,
+                // 7
+                op::STOP,
+                // 8
+                op::PUSH0, op::RJUMPV, 0, 0, 3,
+                // 13
+                op::JUMPF, 0, 2,
+                // 16
+                op::RJUMP, 0xff, 0xf4,
+            ],
+            // 19
+            &[op::RETF],
+            // 20
+            &[op::STOP],
+        ];
+        let body = EofBody {
+            types_section: vec![
+                TypesSection { inputs: 0, outputs: 0x80, max_stack_size: 1 },
+                TypesSection { inputs: 0, outputs: 0, max_stack_size: 0 },
+                TypesSection { inputs: 0, outputs: 0x80, max_stack_size: 0 },
+            ],
+            code_section: code.into_iter().map(Bytes::from_static).collect(),
+            container_section: vec![],
+            data_section: Bytes::new(),
+            is_data_filled: true,
+        };
+        // Decode the encoded container so that the code sections are contiguous.
+        let eof = Eof::decode(body.into_eof().raw).unwrap();
+        let mut bytecode = Bytecode::new(&[], Some(Cow::Owned(eof)), SpecId::OSAKA);
+        bytecode.analyze().unwrap();
+        let cfg = bytecode.control_flow_graph();
+        assert!(cfg.is_eof);
+        let blocks =
+            cfg.blocks.iter().map(|b| (b.code_section, b.start_pc, b.end_pc)).collect::<Vec<_>>();
+        assert_eq!(
+            blocks,
+            [
+                (Some(0), 0, 1),
+                (Some(0), 4, 4),
+                (Some(0), 7, 7),
+                (Some(0), 8, 9),
+                (Some(0), 13, 13),
+                (Some(0), 16, 16),
+                (Some(1), 19, 19),
+                (Some(2), 20, 20),
+            ]
+        );
+        assert_eq!(
+            cfg.edges,
+            [
+                Edge { from: 0, to: 3, kind: EdgeKind::Jump },
+                Edge { from: 0, to: 1, kind: EdgeKind::Fallthrough },
+                Edge { from: 1, to: 6, kind: EdgeKind::Call },
+                Edge { from: 3, to: 5, kind: EdgeKind::Jump },
+                Edge { from: 3, to: 4, kind: EdgeKind::Fallthrough },
+                Edge { from: 4, to: 7, kind: EdgeKind::Jump },
+                Edge { from: 5, to: 2, kind: EdgeKind::Jump },
+                Edge { from: 6, to: 2, kind: EdgeKind::Return },
+            ]
+        );
+    }
+}
@@ -8,6 +8,11 @@ use revmc_backend::{eyre::ensure, Result};
 use rustc_hash::FxHashMap;
 use std::{borrow::Cow, fmt};
 
+mod disasm;
+
+mod graph;
+pub use graph::{BasicBlock, ControlFlowGraph, Edge, EdgeKind};
+
 mod sections;
 use sections::{Section, SectionAnalysis};
 
@@ -448,6 +453,14 @@ impl<'a> Bytecode<'a> {
         self.eof.is_some()
     }
 
+    /// Returns the raw bytecode, which is the whole container in EOF.
+    pub(crate) fn raw_code(&self) -> &[u8] {
+        match &self.eof {
+            Some(eof) => &eof.raw,
+            None => self.code,
+        }
+    }
+
     /// Returns `true` if the bytecode is small.
     ///
     /// This is arbitrarily chosen to speed up compilation for larger contracts.
@@ -552,6 +565,14 @@ impl<'a> Bytecode<'a> {
         self.eof.as_deref().expect("EOF container not set")
     }
 
+    /// Returns the 1-based line of the given instruction in the [`Display`](fmt::Display) output.
+    ///
+    /// Used as the debug location of the instruction.
+    pub(crate) fn inst_display_line(&self, inst: Inst) -> u32 {
+        // Header and separator lines.
+        inst as u32 + 3
+    }
+
     /// Returns the name for a basic block.
     pub(crate) fn op_block_name(&self, mut inst: usize, name: &str) -> String {
         use std::fmt::Write;
@@ -581,6 +602,7 @@ impl<'a> Bytecode<'a> {
 
 impl fmt::Display for Bytecode<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // NOTE: Keep in sync with `inst_display_line`.
         let header = format!("{:^6} | {:^6} | {:^80} | {}", "ic", "pc", "opcode", "instruction");
         writeln!(f, "{header}")?;
         writeln!(f, "{}", "-".repeat(header.len()))?;
@@ -0,0 +1,236 @@
+//! Parallel ahead-of-time compilation of many contracts.
+
+use super::EvmCompiler;
+use crate::{llvm::EvmLlvmBackend, Linker, Result};
+use revm_primitives::{keccak256, SpecId, B256};
+use revmc_backend::{eyre::eyre, OptimizationLevel, Target};
+use rustc_hash::FxHashSet;
+use std::{
+    fs,
+    io::{self, Write},
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    thread,
+};
+
+/// [`compile_many`] options.
+#[derive(Clone, Debug)]
+pub struct CompileManyOptions {
+    /// The directory to write the output files to.
+    pub out_dir: PathBuf,
+    /// The prefix of the output file, module, and symbol names. Default: "revmc".
+    pub name: String,
+    /// The number of shards, each compiled on its own thread with its own LLVM context.
+    ///
+    /// Default: the available parallelism.
+    pub shards: usize,
+    /// The target to compile for. Default: the host machine.
+    pub target: Target,
+    /// The optimization level. Default: [`OptimizationLevel::Aggressive`].
+    pub opt_level: OptimizationLevel,
+    /// Whether to bundle each shard's object file into a static library with [`Linker::archive`].
+    /// Default: false.
+    pub archive: bool,
+}
+
+impl Default for CompileManyOptions {
+    fn default() -> Self {
+        Self {
+            out_dir: PathBuf::from("."),
+            name: "revmc".to_string(),
+            shards: thread::available_parallelism().map_or(1, NonZeroUsize::get),
+            target: Target::Native,
+            opt_level: OptimizationLevel::Aggressive,
+            archive: false,
+        }
+    }
+}
+
+/// The result of [`compile_many`].
+#[derive(Clone, Debug, Default)]
+pub struct CompileManyManifest {
+    /// The files written, one per shard.
+    pub files: Vec<PathBuf>,
+    /// The compiled functions, in input order, without duplicates.
+    pub entries: Vec<CompileManyEntry>,
+}
+
+/// A function compiled by [`compile_many`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CompileManyEntry {
+    /// The hash of the bytecode.
+    pub code_hash: B256,
+    /// The spec ID the bytecode was compiled with.
+    pub spec_id: SpecId,
+    /// The name of the exported symbol.
+    pub symbol: String,
+    /// The index of the file in [`CompileManyManifest::files`] which defines the symbol.
+    pub file: usize,
+}
+
+impl CompileManyManifest {
+    /// Returns the symbol name of the given bytecode hash, compiled with the given spec ID.
+    pub fn symbol(&self, code_hash: B256, spec_id: SpecId) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|e| e.code_hash == code_hash && e.spec_id == spec_id)
+            .map(|e| e.symbol.as_str())
+    }
+
+    /// Writes the manifest to the given file.
+    pub fn write_to_file(&self, path: &Path) -> Result<()> {
+        let file = fs::File::create(path)?;
+        let mut writer = io::BufWriter::new(file);
+        self.write(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Writes the manifest to the given writer.
+    ///
+    /// Each line contains the code hash, the spec ID, the symbol name, and the file, separated by
+    /// tabs.
+    pub fn write<W: io::Write>(&self, mut w: W) -> Result<()> {
+        for entry in &self.entries {
+            let CompileManyEntry { code_hash, spec_id, symbol, file } = entry;
+            let file = self.files[*file].display();
+            writeln!(w, "{code_hash}\t{spec_id:?}\t{symbol}\t{file}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Compiles many EVM bytecodes ahead of time, in parallel.
+///
+/// The inputs are deduplicated by code hash and spec ID, and split into
+/// [`shards`](CompileManyOptions::shards). Each shard is compiled on its own thread with its own
+/// LLVM context into a single module named `<name>_<shard>`, and written to
+/// `<out_dir>/<name>_<shard>.o`, or `.a` if [`archive`](CompileManyOptions::archive) is set.
+///
+/// Each function is exported as `<name>_<spec_id>_<code_hash>`; the returned manifest maps inputs
+/// to symbols and files.
+///
+/// `configure` is called on each shard's compiler before any functions are translated.
+#[instrument(level = "debug", skip_all)]
+pub fn compile_many<F>(
+    inputs: &[(&[u8], SpecId)],
+    options: &CompileManyOptions,
+    configure: F,
+) -> Result<CompileManyManifest>
+where
+    F: Fn(&mut EvmCompiler<EvmLlvmBackend<'_>>) + Sync,
+{
+    let mut seen = FxHashSet::default();
+    let mut unique = Vec::with_capacity(inputs.len());
+    for &(code, spec_id) in inputs {
+        let code_hash = keccak256(code);
+        if seen.insert((code_hash, spec_id)) {
+            let symbol = format!("{}_{spec_id:?}_{code_hash:x}", options.name);
+            unique.push((code, spec_id, code_hash, symbol));
+        }
+    }
+    if unique.is_empty() {
+        return Ok(CompileManyManifest::default());
+    }
+
+    fs::create_dir_all(&options.out_dir)?;
+    let chunk_size = unique.len().div_ceil(options.shards.max(1));
+    let shards = unique.chunks(chunk_size).collect::<Vec<_>>();
+    debug!(functions = unique.len(), shards = shards.len(), "compiling");
+
+    let configure = &configure;
+    let files = thread::scope(|s| {
+        let handles = shards
+            .iter()
+            .enumerate()
+            .map(|(i, &shard)| {
+                thread::Builder::new()
+                    .name(format!("{}_{i}", options.name))
+                    .spawn_scoped(s, move || compile_shard(i, shard, options, configure))
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap_or_else(|e| std::panic::resume_unwind(e)))
+            .collect::<Result<Vec<_>>>()
+    })?;
+
+    let entries = shards
+        .iter()
+        .enumerate()
+        .flat_map(|(file, shard)| {
+            shard.iter().map(move |(_, spec_id, code_hash, symbol)| CompileManyEntry {
+                code_hash: *code_hash,
+                spec_id: *spec_id,
+                symbol: symbol.clone(),
+                file,
+            })
+        })
+        .collect();
+    Ok(CompileManyManifest { files, entries })
+}
+
+#[instrument(name = "shard", level = "debug", skip(shard, options, configure))]
+fn compile_shard<F>(
+    i: usize,
+    shard: &[(&[u8], SpecId, B256, String)],
+    options: &CompileManyOptions,
+    configure: &F,
+) -> Result<PathBuf>
+where
+    F: Fn(&mut EvmCompiler<EvmLlvmBackend<'_>>) + Sync,
+{
+    let name = format!("{}_{i}", options.name);
+    let cx = crate::llvm::inkwell::context::Context::create();
+    let backend = EvmLlvmBackend::new_for_target(&cx, true, options.opt_level, &options.target)?;
+    let mut compiler = EvmCompiler::new(backend);
+    compiler.set_module_name(&name);
+    configure(&mut compiler);
+
+    for (code, spec_id, _, symbol) in shard {
+        compiler
+            .translate(symbol, *code, *spec_id)
+            .map_err(|e| e.wrap_err(format!("failed to compile {symbol}")))?;
+    }
+
+    let object = options.out_dir.join(format!("{name}.o"));
+    compiler.write_object_to_file(&object)?;
+    if !options.archive {
+        return Ok(object);
+    }
+
+    let archive = options.out_dir.join(format!("{name}.a"));
+    let _ = fs::remove_file(&archive);
+    Linker::new().archive(&archive, [&object]).map_err(|e| eyre!("failed to archive: {e}"))?;
+    fs::remove_file(&object)?;
+    Ok(archive)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use revm_interpreter::opcode as op;
+
+    #[test]
+    fn basic() {
+        let tmp = tempfile::tempdir().expect("could not create temp dir");
+        let a = [op::PUSH1, 1, op::STOP];
+        let b = [op::PUSH1, 2, op::STOP];
+        let inputs = [
+            (&a[..], SpecId::CANCUN),
+            (&b[..], SpecId::CANCUN),
+            (&a[..], SpecId::CANCUN),
+            (&a[..], SpecId::SHANGHAI),
+        ];
+        let options =
+            CompileManyOptions { out_dir: tmp.path().into(), shards: 2, ..Default::default() };
+        let manifest = compile_many(&inputs, &options, |_| {}).unwrap();
+
+        assert_eq!(manifest.files.len(), 2);
+        assert!(manifest.files.iter().all(|file| file.exists()));
+        assert_eq!(manifest.entries.len(), 3);
+        let symbol = manifest.symbol(keccak256(a), SpecId::CANCUN).unwrap();
+        assert_eq!(symbol, format!("revmc_CANCUN_{:x}", keccak256(a)));
+        assert_ne!(manifest.symbol(keccak256(a), SpecId::SHANGHAI), Some(symbol));
+    }
+}
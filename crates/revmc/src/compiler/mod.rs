@@ -1,20 +1,25 @@
 //! EVM bytecode compiler implementation.
 
-use crate::{Backend, Builder, Bytecode, EvmCompilerFn, EvmContext, EvmStack, Result};
+use crate::{
+    Backend, Builder, Bytecode, ControlFlowGraph, EvmCompilerFn, EvmContext, EvmStack, Result,
+};
+use alloy_primitives::Keccak256;
 use revm_interpreter::{Contract, Gas};
-use revm_primitives::{Bytes, Env, Eof, SpecId, EOF_MAGIC_BYTES};
+use revm_primitives::{keccak256, Bytes, Env, Eof, SpecId, B256, EOF_MAGIC_BYTES};
 use revmc_backend::{
     eyre::{ensure, eyre},
     Attribute, FunctionAttributeLocation, Linkage, OptimizationLevel,
 };
 use revmc_builtins::Builtins;
 use revmc_context::RawEvmCompilerFn;
+use rustc_hash::FxHashMap;
 use std::{
     borrow::Cow,
     fs,
     io::{self, Write},
     mem,
     path::{Path, PathBuf},
+    time::{Duration, Instant},
 };
 
 // TODO: Somehow have a config to tell the backend to assume that stack stores are unobservable,
@@ -28,6 +33,12 @@ use std::{
 // TODO: Test on big-endian hardware.
 // It probably doesn't work when loading Rust U256 into native endianness.
 
+#[cfg(feature = "llvm")]
+mod batch;
+#[cfg(feature = "llvm")]
+pub use batch::{compile_many, CompileManyEntry, CompileManyManifest, CompileManyOptions};
+
+mod perf;
 mod translate;
 use translate::{FcxConfig, FunctionCx};
 
@@ -57,6 +68,14 @@ pub struct EvmCompiler<B: Backend> {
     dump_assembly: bool,
     dump_unopt_assembly: bool,
 
+    perf_map: bool,
+    jitdump: bool,
+    function_names: FxHashMap<B::FuncId, String>,
+    /// The cache keys of the translated functions, for `module_cache_key`.
+    function_cache_keys: FxHashMap<B::FuncId, B256>,
+    function_opt_levels: FxHashMap<B::FuncId, OptimizationLevel>,
+    timings: CompileTimings,
+
     finalized: bool,
 }
 
@@ -71,6 +90,12 @@ impl<B: Backend> EvmCompiler<B> {
             builtins: Builtins::new(),
             dump_assembly: true,
             dump_unopt_assembly: false,
+            perf_map: false,
+            jitdump: false,
+            function_names: FxHashMap::default(),
+            function_cache_keys: FxHashMap::default(),
+            function_opt_levels: FxHashMap::default(),
+            timings: CompileTimings::default(),
             finalized: false,
         }
     }
@@ -122,6 +147,43 @@ impl<B: Backend> EvmCompiler<B> {
         self.dump_unopt_assembly = yes;
     }
 
+    /// (JIT) Writes an entry to the `perf` map file, `/tmp/perf-<pid>.map`, for every function
+    /// returned by [`jit_function`](Self::jit_function).
+    ///
+    /// This allows `perf` to symbolize JIT-compiled functions.
+    ///
+    /// Defaults to `false`.
+    pub fn perf_map(&mut self, yes: bool) {
+        self.perf_map = yes;
+    }
+
+    /// (JIT) Writes a `jitdump` record, including the machine code, for every function returned by
+    /// [`jit_function`](Self::jit_function).
+    ///
+    /// Records are written to `$JITDUMPDIR/jit-<pid>.dump`, or `/tmp/jit-<pid>.dump` if the
+    /// variable is not set. Use `perf record -k 1` and `perf inject --jit` to make use of it.
+    ///
+    /// Only supported on Linux.
+    ///
+    /// Defaults to `false`.
+    pub fn jitdump(&mut self, yes: bool) {
+        self.jitdump = yes;
+    }
+
+    /// (JIT) Registers JIT-compiled code with the GDB JIT compilation interface.
+    ///
+    /// This allows GDB to resolve the symbols of JIT-compiled functions, and to use their
+    /// [debug info](Self::debug_info) if enabled. The code stays registered until the module is
+    /// [cleared](Self::clear), as [`free_function`](Self::free_function) does not release the
+    /// code of a single function with the LLVM backend.
+    ///
+    /// Only supported by the LLVM backend. Only code compiled after this is enabled is registered.
+    ///
+    /// Defaults to `false`.
+    pub fn gdb_jit_registration(&mut self, yes: bool) {
+        self.backend.set_gdb_jit_registration(yes);
+    }
+
     /// Returns the optimization level.
     pub fn opt_level(&self) -> OptimizationLevel {
         self.backend.opt_level()
@@ -136,6 +198,18 @@ impl<B: Backend> EvmCompiler<B> {
         self.backend.set_opt_level(level);
     }
 
+    /// Overrides the optimization level of a translated function.
+    ///
+    /// Must be called before the module is finalized. Note that some backends may not support
+    /// per-function optimization levels, in which case this is ignored.
+    ///
+    /// With the LLVM backend, helper functions shared by functions with different levels are
+    /// optimized once for each level, so they are effectively optimized with the highest one.
+    pub fn set_function_opt_level(&mut self, id: B::FuncId, level: OptimizationLevel) {
+        self.backend.set_function_opt_level(id, level);
+        self.function_opt_levels.insert(id, level);
+    }
+
     /// Sets whether to enable debug assertions.
     ///
     /// These are useful for debugging, but they do a moderate performance penalty due to the
@@ -147,6 +221,17 @@ impl<B: Backend> EvmCompiler<B> {
         self.config.debug_assertions = yes;
     }
 
+    /// Sets whether to emit debug info.
+    ///
+    /// This maps the generated machine code back to the EVM instructions, allowing debuggers and
+    /// profilers to show the originating instruction. Line numbers refer to the `bytecode.txt` file
+    /// written to the [dump directory](Self::set_dump_to).
+    ///
+    /// Defaults to `false`.
+    pub fn debug_info(&mut self, yes: bool) {
+        self.config.debug_info = yes;
+    }
+
     /// Sets whether to enable frame pointers.
     ///
     /// This is useful for profiling and debugging, but it incurs a very slight performance penalty.
@@ -220,12 +305,175 @@ impl<B: Backend> EvmCompiler<B> {
     /// are implemented as builtins.
     ///
     /// Use with care, as executing a function with gas disabled may result in an infinite loop.
+    /// See [`execution_limits`](Self::execution_limits) for a way to bound its execution.
     ///
     /// Defaults to `true`.
     pub fn gas_metering(&mut self, yes: bool) {
         self.config.gas_metering = yes;
     }
 
+    /// Sets whether to check the [`ExecutionLimits`] passed in the context.
+    ///
+    /// This adds a check at every loop back-edge, dynamic jump, and EOF code section entry, which
+    /// halts execution with [`FatalExternalError`] if the instruction budget is exhausted or
+    /// execution has been cancelled. No checks are performed if the context has no limits.
+    /// Use [`EvmCompilerFn::call_with_limits`] to get the reason as an error.
+    ///
+    /// This can be used to bound the execution of functions compiled without gas metering, or to
+    /// abort them from another thread.
+    ///
+    /// Defaults to `false`.
+    ///
+    /// [`ExecutionLimits`]: crate::ExecutionLimits
+    /// [`FatalExternalError`]: crate::interpreter::InstructionResult::FatalExternalError
+    pub fn execution_limits(&mut self, yes: bool) {
+        self.config.execution_limits = yes;
+    }
+
+    /// Sets whether to identify resume points with stable indexes instead of block addresses.
+    ///
+    /// Block addresses are only valid in the process that compiled the function, so this is
+    /// required to resume a [`SuspendedFrame`] in a different process, which must load code
+    /// compiled with the same configuration.
+    ///
+    /// Defaults to `false`.
+    ///
+    /// [`SuspendedFrame`]: crate::SuspendedFrame
+    pub fn stable_resume_points(&mut self, yes: bool) {
+        self.config.stable_resume_points = yes;
+    }
+
+    /// Returns the time spent in each compilation phase since the compiler was created, or since
+    /// the last call to [`reset_timings`](Self::reset_timings).
+    pub fn timings(&self) -> CompileTimings {
+        self.timings
+    }
+
+    /// Resets the [`timings`](Self::timings).
+    pub fn reset_timings(&mut self) {
+        self.timings = CompileTimings::default();
+    }
+
+    /// Returns the size of the machine code of the given function in bytes, if known.
+    ///
+    /// This is only available after the function has been JIT-compiled.
+    pub fn function_size(&mut self, id: B::FuncId) -> Result<Option<usize>> {
+        self.backend.function_size(id)
+    }
+
+    /// Returns the current configuration, for running test cases against.
+    #[cfg(any(feature = "__fuzzing", test))]
+    pub(crate) fn test_config(&self) -> crate::tests::TestConfig {
+        crate::tests::TestConfig {
+            opt_level: None,
+            gas_metering: self.config.gas_metering,
+            local_stack: self.config.local_stack,
+            inspect_stack_length: self.config.inspect_stack_length,
+            debug_assertions: self.config.debug_assertions,
+            frame_pointers: self.config.frame_pointers,
+        }
+    }
+
+    /// Returns a key that uniquely identifies the compiled code of the given EVM bytecode with the
+    /// current configuration.
+    ///
+    /// This is the hash of the bytecode, the spec ID, all the settings that affect code
+    /// generation, including the backend's target and pass pipeline, and the version of this
+    /// crate, and can be used to cache compilation artifacts.
+    ///
+    /// The key assumes the function is compiled with the compiler's
+    /// [optimization level](Self::opt_level). Use [`module_cache_key`](Self::module_cache_key)
+    /// for functions with an [overridden level](Self::set_function_opt_level).
+    pub fn cache_key<'a>(&self, input: impl Into<EvmCompilerInput<'a>>, spec_id: SpecId) -> B256 {
+        let code = match input.into() {
+            EvmCompilerInput::Code(code) => code,
+            EvmCompilerInput::Eof(eof) => &eof.raw[..],
+        };
+        self.code_cache_key(code, spec_id)
+    }
+
+    /// Returns a key that uniquely identifies the module with the functions translated so far, such
+    /// as the LLVM bitcode written by `write_module_bitcode`.
+    ///
+    /// This combines the [cache keys](Self::cache_key) and names of all the functions with their
+    /// optimization levels.
+    pub fn module_cache_key(&self) -> B256 {
+        let mut functions = self
+            .function_cache_keys
+            .iter()
+            .map(|(id, key)| {
+                let opt_level = self.function_opt_levels.get(id).copied();
+                (&self.function_names[id], key, opt_level.unwrap_or_else(|| self.opt_level()))
+            })
+            .collect::<Vec<_>>();
+        functions.sort_unstable_by(|a, b| a.0.cmp(b.0));
+        let mut hasher = Keccak256::new();
+        hasher.update(self.name.as_deref().unwrap_or_default());
+        for (name, key, opt_level) in functions {
+            hasher.update((name.len() as u64).to_le_bytes());
+            hasher.update(name);
+            hasher.update(key);
+            hasher.update([opt_level as u8]);
+        }
+        hasher.finalize()
+    }
+
+    /// Returns the key that uniquely identifies the compiled code of the translated function `id`,
+    /// such as the LLVM bitcode written by `write_function_bitcode`.
+    ///
+    /// This is the function's [cache key](Self::cache_key), combined with its
+    /// [overridden optimization level](Self::set_function_opt_level), if any.
+    pub fn function_cache_key(&self, id: B::FuncId) -> Option<B256> {
+        let key = *self.function_cache_keys.get(&id)?;
+        match self.function_opt_levels.get(&id) {
+            Some(&opt_level) if opt_level != self.opt_level() => {
+                let mut hasher = Keccak256::new();
+                hasher.update(key);
+                hasher.update([opt_level as u8]);
+                Some(hasher.finalize())
+            }
+            _ => Some(key),
+        }
+    }
+
+    fn code_cache_key(&self, code: &[u8], spec_id: SpecId) -> B256 {
+        let FcxConfig {
+            comments: _,
+            debug_info,
+            debug_assertions,
+            frame_pointers,
+            validate_eof: _,
+            local_stack,
+            inspect_stack_length,
+            stack_bound_checks,
+            gas_metering,
+            execution_limits,
+            stable_resume_points,
+        } = self.config;
+        let mut hasher = Keccak256::new();
+        hasher.update(keccak256(code));
+        hasher.update([
+            spec_id as u8,
+            self.opt_level() as u8,
+            debug_info as u8,
+            debug_assertions as u8,
+            frame_pointers as u8,
+            local_stack as u8,
+            inspect_stack_length as u8,
+            stack_bound_checks as u8,
+            gas_metering as u8,
+            execution_limits as u8,
+            stable_resume_points as u8,
+        ]);
+        let settings = self.backend.codegen_settings();
+        hasher.update((settings.len() as u64).to_le_bytes());
+        hasher.update(settings);
+        // Compiled code depends on the layout of the context and the builtins.
+        hasher.update(env!("CARGO_PKG_VERSION"));
+        hasher.update((mem::size_of::<EvmContext<'_>>() as u64).to_le_bytes());
+        hasher.finalize()
+    }
+
     /// Translates the given EVM bytecode into an internal function.
     ///
     /// NOTE: `name` must be unique for each function, as it is used as the name of the final
@@ -269,8 +517,13 @@ impl<B: Backend> EvmCompiler<B> {
     pub unsafe fn jit_function(&mut self, id: B::FuncId) -> Result<EvmCompilerFn> {
         ensure!(self.is_jit(), "cannot JIT functions during AOT compilation");
         self.finalize()?;
+        let start = Instant::now();
         let addr = self.backend.jit_function(id)?;
+        self.timings.codegen += start.elapsed();
         debug_assert!(addr != 0);
+        if self.perf_map || self.jitdump {
+            self.register_jit_function(id, addr)?;
+        }
         Ok(EvmCompilerFn::new(unsafe { std::mem::transmute::<usize, RawEvmCompilerFn>(addr) }))
     }
 
@@ -287,7 +540,10 @@ impl<B: Backend> EvmCompiler<B> {
     pub fn write_object<W: io::Write>(&mut self, w: W) -> Result<()> {
         ensure!(self.is_aot(), "cannot write AOT object during JIT compilation");
         self.finalize()?;
-        self.backend.write_object(w)
+        let start = Instant::now();
+        let res = self.backend.write_object(w);
+        self.timings.codegen += start.elapsed();
+        res
     }
 
     /// (JIT) Frees the memory associated with a single function.
@@ -316,10 +572,34 @@ impl<B: Backend> EvmCompiler<B> {
     /// none of the `fn` pointers are called afterwards.
     pub unsafe fn clear(&mut self) -> Result<()> {
         self.builtins.clear();
+        self.function_names.clear();
+        self.function_cache_keys.clear();
+        self.function_opt_levels.clear();
         self.finalized = false;
         self.backend.free_all_functions()
     }
 
+    /// Parses and analyzes the given EVM bytecode, and returns its annotated disassembly.
+    ///
+    /// Each instruction is printed with its program counter, base gas cost, stack inputs and
+    /// outputs, immediate, resolved jump targets, and analysis flags, such as static jumps and dead
+    /// code. Sections are prefixed by their total base gas cost, required stack height, and maximum
+    /// stack growth.
+    pub fn disassemble(&mut self, input: EvmCompilerInput<'_>, spec_id: SpecId) -> Result<String> {
+        let bytecode = self.parse(input, spec_id)?;
+        Ok(bytecode.disassembly().to_string())
+    }
+
+    /// Parses and analyzes the given EVM bytecode, and returns its control-flow graph.
+    pub fn control_flow_graph(
+        &mut self,
+        input: EvmCompilerInput<'_>,
+        spec_id: SpecId,
+    ) -> Result<ControlFlowGraph> {
+        let bytecode = self.parse(input, spec_id)?;
+        Ok(bytecode.control_flow_graph())
+    }
+
     /// Parses the given EVM bytecode. Not public API.
     #[doc(hidden)] // Not public API.
     pub fn parse<'a>(
@@ -327,6 +607,7 @@ impl<B: Backend> EvmCompiler<B> {
         input: EvmCompilerInput<'a>,
         spec_id: SpecId,
     ) -> Result<Bytecode<'a>> {
+        let start = Instant::now();
         let bytecode;
         let eof;
         match input {
@@ -349,6 +630,7 @@ impl<B: Backend> EvmCompiler<B> {
 
         let mut bytecode = Bytecode::new(bytecode, eof, spec_id);
         bytecode.analyze()?;
+        self.timings.parse += start.elapsed();
         if let Some(dump_dir) = &self.dump_dir() {
             Self::dump_bytecode(dump_dir, &bytecode)?;
         }
@@ -367,17 +649,60 @@ impl<B: Backend> EvmCompiler<B> {
         })
     }
 
+    /// Translates the given already-parsed EVM bytecode into an internal function. Not public API.
+    #[doc(hidden)] // Not public API.
+    pub fn translate_parsed(&mut self, name: &str, bytecode: &Bytecode<'_>) -> Result<B::FuncId> {
+        ensure!(!self.finalized, "cannot compile more functions after finalizing the module");
+        self.translate_inner(name, bytecode)
+    }
+
     #[instrument(name = "translate", level = "debug", skip_all)]
     fn translate_inner(&mut self, name: &str, bytecode: &Bytecode<'_>) -> Result<B::FuncId> {
         ensure!(self.backend.function_name_is_unique(name), "function name `{name}` is not unique");
         let linkage = Linkage::Public;
+        let debug_file = self
+            .config
+            .debug_info
+            .then(|| self.dump_dir().unwrap_or_default().join("bytecode.txt"));
+        self.backend.set_debug_file(debug_file.as_deref());
+        let start = Instant::now();
         let (bcx, id) = Self::make_builder(&mut self.backend, &self.config, name, linkage)?;
         FunctionCx::translate(bcx, self.config, &mut self.builtins, bytecode)?;
+        self.timings.translate += start.elapsed();
+        self.function_names.insert(id, name.to_string());
+        self.function_cache_keys
+            .insert(id, self.code_cache_key(bytecode.raw_code(), bytecode.spec_id));
         Ok(id)
     }
 
     #[instrument(level = "debug", skip_all)]
-    fn finalize(&mut self) -> Result<()> {
+    fn register_jit_function(&mut self, id: B::FuncId, addr: usize) -> Result<()> {
+        let Some(size) = self.backend.function_size(id)? else {
+            debug!("unknown size of function {id:?}, skipping profiler registration");
+            return Ok(());
+        };
+        let function_name = self.function_names.get(&id).map(String::as_str).unwrap_or("unknown");
+        let name = match &self.name {
+            Some(module_name) => format!("{module_name}::{function_name}"),
+            None => function_name.to_string(),
+        };
+        if self.perf_map {
+            perf::write_perf_map_entry(addr, size, &name)?;
+        }
+        if self.jitdump {
+            // SAFETY: `addr` points to the finalized machine code of the function, and `size` is
+            // the size of that code as loaded by the backend.
+            unsafe { perf::write_jitdump_entry(addr, size, &name)? };
+        }
+        Ok(())
+    }
+
+    /// Verifies and optimizes the module. Not public API.
+    ///
+    /// This is called automatically when JIT-compiling a function or writing an object.
+    #[doc(hidden)] // Not public API.
+    #[instrument(level = "debug", skip_all)]
+    pub fn finalize(&mut self) -> Result<()> {
         if self.finalized {
             return Ok(());
         }
@@ -484,14 +809,24 @@ impl<B: Backend> EvmCompiler<B> {
         self.backend.dump_disasm(path)
     }
 
+    /// Verifies the module. Not public API.
+    #[doc(hidden)] // Not public API.
     #[instrument(level = "debug", skip_all)]
-    fn verify_module(&mut self) -> Result<()> {
-        self.backend.verify_module()
+    pub fn verify_module(&mut self) -> Result<()> {
+        let start = Instant::now();
+        let res = self.backend.verify_module();
+        self.timings.verify += start.elapsed();
+        res
     }
 
+    /// Optimizes the module. Not public API.
+    #[doc(hidden)] // Not public API.
     #[instrument(level = "debug", skip_all)]
-    fn optimize_module(&mut self) -> Result<()> {
-        self.backend.optimize_module()
+    pub fn optimize_module(&mut self) -> Result<()> {
+        let start = Instant::now();
+        let res = self.backend.optimize_module();
+        self.timings.optimize += start.elapsed();
+        res
     }
 
     #[instrument(level = "debug", skip_all)]
@@ -503,6 +838,13 @@ impl<B: Backend> EvmCompiler<B> {
             writer.flush()?;
         }
 
+        {
+            let file = fs::File::create(dump_dir.join("bytecode.asm.txt"))?;
+            let mut writer = io::BufWriter::new(file);
+            write!(writer, "{}", bytecode.disassembly())?;
+            writer.flush()?;
+        }
+
         {
             let file = fs::File::create(dump_dir.join("bytecode.dbg.txt"))?;
             let mut writer = io::BufWriter::new(file);
@@ -525,6 +867,83 @@ impl<B: Backend> EvmCompiler<B> {
     }
 }
 
+#[cfg(feature = "llvm")]
+impl EvmCompiler<crate::llvm::EvmLlvmBackend<'_>> {
+    /// Finalizes the module and writes the whole module as LLVM bitcode to the given writer.
+    ///
+    /// The bitcode contains the optimized code of all the functions translated into the module,
+    /// and can be loaded again with [`jit_bitcode`](Self::jit_bitcode), skipping translation and
+    /// optimization. It is keyed by the [`module_cache_key`](Self::module_cache_key), which is
+    /// stored in the bitcode.
+    ///
+    /// Use [`write_function_bitcode`](Self::write_function_bitcode) to cache functions separately.
+    pub fn write_module_bitcode<W: io::Write>(&mut self, w: W) -> Result<()> {
+        self.finalize()?;
+        let cache_key = self.module_cache_key();
+        self.backend.write_bitcode(&cache_key.to_string(), w)
+    }
+
+    /// Finalizes the module and writes the function `id` as LLVM bitcode to the given writer.
+    ///
+    /// The bitcode only contains the optimized code of the function and what it references. It is
+    /// keyed by the [`function_cache_key`](Self::function_cache_key), which is stored in the
+    /// bitcode.
+    pub fn write_function_bitcode<W: io::Write>(&mut self, id: u32, w: W) -> Result<()> {
+        self.finalize()?;
+        let cache_key = self
+            .function_cache_key(id)
+            .ok_or_else(|| eyre!("function {id} was not translated into this module"))?;
+        self.backend.write_function_bitcode(id, &cache_key.to_string(), w)
+    }
+
+    /// (JIT) Loads LLVM bitcode written by [`write_module_bitcode`](Self::write_module_bitcode) or
+    /// [`write_function_bitcode`](Self::write_function_bitcode) and JITs the function `name`
+    /// defined in it.
+    ///
+    /// `cache_key` is the key the bitcode was saved with. Returns an error if it does not match
+    /// the key stored in the bitcode.
+    ///
+    /// # Safety
+    ///
+    /// The returned function pointer is owned by the backend, and must not be called after the
+    /// module is cleared.
+    pub unsafe fn jit_bitcode(
+        &mut self,
+        name: &str,
+        bitcode: &[u8],
+        cache_key: B256,
+    ) -> Result<EvmCompilerFn> {
+        ensure!(self.is_jit(), "cannot JIT functions during AOT compilation");
+        let addr = self.backend.jit_bitcode(bitcode, name, &cache_key.to_string(), |symbol| {
+            revmc_builtins::Builtin::ALL.iter().find(|b| b.name() == symbol).map(|b| b.addr())
+        })?;
+        debug_assert!(addr != 0);
+        Ok(EvmCompilerFn::new(unsafe { std::mem::transmute::<usize, RawEvmCompilerFn>(addr) }))
+    }
+}
+
+/// The time spent in each [`EvmCompiler`] phase, returned by [`EvmCompiler::timings`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CompileTimings {
+    /// Decoding, validating and analyzing bytecode.
+    pub parse: Duration,
+    /// Translating bytecode into the backend's IR.
+    pub translate: Duration,
+    /// Verifying the module.
+    pub verify: Duration,
+    /// Optimizing the module.
+    pub optimize: Duration,
+    /// Generating machine code, either with the JIT or into an object file.
+    pub codegen: Duration,
+}
+
+impl CompileTimings {
+    /// Returns the total time spent in all phases.
+    pub fn total(&self) -> Duration {
+        self.parse + self.translate + self.verify + self.optimize + self.codegen
+    }
+}
+
 /// [`EvmCompiler`] input.
 #[allow(missing_debug_implementations)]
 pub enum EvmCompilerInput<'a> {
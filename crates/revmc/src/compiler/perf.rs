@@ -0,0 +1,225 @@
+//! Profiler integration for JIT-compiled functions.
+//!
+//! See:
+//! - <https://github.com/torvalds/linux/blob/master/tools/perf/Documentation/jit-interface.txt>
+//! - <https://github.com/torvalds/linux/blob/master/tools/perf/Documentation/jitdump-specification.txt>
+
+use revmc_backend::{eyre::eyre, Result};
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+/// Appends an entry to `/tmp/perf-<pid>.map`.
+pub(super) fn write_perf_map_entry(addr: usize, size: usize, name: &str) -> Result<()> {
+    static PERF_MAP: Mutex<Option<io::BufWriter<fs::File>>> = Mutex::new(None);
+
+    let mut guard = PERF_MAP.lock().unwrap_or_else(|e| e.into_inner());
+    if guard.is_none() {
+        let path = format!("/tmp/perf-{}.map", std::process::id());
+        let file = fs::OpenOptions::new().create(true).append(true).open(path)?;
+        *guard = Some(io::BufWriter::new(file));
+    }
+    let file = guard.as_mut().unwrap();
+    write_perf_map_line(file, addr, size, name)?;
+    file.flush()?;
+    Ok(())
+}
+
+fn write_perf_map_line(w: &mut impl Write, addr: usize, size: usize, name: &str) -> io::Result<()> {
+    writeln!(w, "{addr:x} {size:x} {name}")
+}
+
+/// Appends a `JIT_CODE_LOAD` record to `$JITDUMPDIR/jit-<pid>.dump`, defaulting to `/tmp`.
+///
+/// # Safety
+///
+/// `addr` must point to `size` bytes of readable memory. The size must come from the loaded
+/// machine code itself, not from a separately emitted object.
+pub(super) unsafe fn write_jitdump_entry(addr: usize, size: usize, name: &str) -> Result<()> {
+    static JITDUMP: Mutex<Option<JitDump>> = Mutex::new(None);
+
+    let mut guard = JITDUMP.lock().unwrap_or_else(|e| e.into_inner());
+    if guard.is_none() {
+        *guard = Some(JitDump::new()?);
+    }
+    let jitdump = guard.as_mut().unwrap();
+    let code = unsafe { std::slice::from_raw_parts(addr as *const u8, size) };
+    jitdump.write_code_load(addr as u64, code, name)
+}
+
+const JITDUMP_MAGIC: u32 = 0x4A695444;
+const JITDUMP_VERSION: u32 = 1;
+const JIT_CODE_LOAD: u32 = 0;
+
+struct JitDump {
+    file: io::BufWriter<fs::File>,
+    code_index: u64,
+}
+
+impl JitDump {
+    fn new() -> Result<Self> {
+        if !cfg!(target_os = "linux") {
+            return Err(eyre!("jitdump is only supported on Linux"));
+        }
+        let elf_mach: u32 = if cfg!(target_arch = "x86_64") {
+            62
+        } else if cfg!(target_arch = "aarch64") {
+            183
+        } else {
+            return Err(eyre!("jitdump is not supported on this architecture"));
+        };
+
+        let dir =
+            std::env::var_os("JITDUMPDIR").map(PathBuf::from).unwrap_or_else(|| "/tmp".into());
+        Self::create(&dir.join(format!("jit-{}.dump", std::process::id())), elf_mach)
+    }
+
+    fn create(path: &Path, elf_mach: u32) -> Result<Self> {
+        let file =
+            fs::OpenOptions::new().create(true).truncate(true).read(true).write(true).open(path)?;
+        // `perf record` discovers the dump file through an executable mapping of it.
+        mark_file(&file)?;
+
+        let mut file = io::BufWriter::new(file);
+        let header_size = 40u32;
+        file.write_all(&JITDUMP_MAGIC.to_ne_bytes())?;
+        file.write_all(&JITDUMP_VERSION.to_ne_bytes())?;
+        file.write_all(&header_size.to_ne_bytes())?;
+        file.write_all(&elf_mach.to_ne_bytes())?;
+        file.write_all(&0u32.to_ne_bytes())?;
+        file.write_all(&std::process::id().to_ne_bytes())?;
+        file.write_all(&timestamp().to_ne_bytes())?;
+        file.write_all(&0u64.to_ne_bytes())?;
+        file.flush()?;
+        Ok(Self { file, code_index: 0 })
+    }
+
+    fn write_code_load(&mut self, addr: u64, code: &[u8], name: &str) -> Result<()> {
+        // Record header, pid, tid, vma, code_addr, code_size, code_index, name, code.
+        let total_size = 16 + 8 + 8 * 4 + name.len() + 1 + code.len();
+        let file = &mut self.file;
+        file.write_all(&JIT_CODE_LOAD.to_ne_bytes())?;
+        file.write_all(&(total_size as u32).to_ne_bytes())?;
+        file.write_all(&timestamp().to_ne_bytes())?;
+        file.write_all(&std::process::id().to_ne_bytes())?;
+        file.write_all(&thread_id().to_ne_bytes())?;
+        file.write_all(&addr.to_ne_bytes())?;
+        file.write_all(&addr.to_ne_bytes())?;
+        file.write_all(&(code.len() as u64).to_ne_bytes())?;
+        file.write_all(&self.code_index.to_ne_bytes())?;
+        file.write_all(name.as_bytes())?;
+        file.write_all(&[0])?;
+        file.write_all(code)?;
+        file.flush()?;
+        self.code_index += 1;
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn mark_file(file: &fs::File) -> Result<()> {
+    use std::os::fd::AsRawFd;
+
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+    let ptr = unsafe {
+        libc::mmap(
+            std::ptr::null_mut(),
+            page_size,
+            libc::PROT_READ | libc::PROT_EXEC,
+            libc::MAP_PRIVATE,
+            file.as_raw_fd(),
+            0,
+        )
+    };
+    if ptr == libc::MAP_FAILED {
+        return Err(io::Error::last_os_error().into());
+    }
+    // The mapping is intentionally leaked, as it must outlive the profiling session.
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn mark_file(_file: &fs::File) -> Result<()> {
+    Ok(())
+}
+
+/// Returns the `CLOCK_MONOTONIC` timestamp in nanoseconds, which is what `perf record -k 1` uses.
+#[cfg(target_os = "linux")]
+fn timestamp() -> u64 {
+    let mut ts = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts) };
+    ts.tv_sec as u64 * 1_000_000_000 + ts.tv_nsec as u64
+}
+
+#[cfg(not(target_os = "linux"))]
+fn timestamp() -> u64 {
+    0
+}
+
+#[cfg(target_os = "linux")]
+fn thread_id() -> u32 {
+    (unsafe { libc::syscall(libc::SYS_gettid) }) as u32
+}
+
+#[cfg(not(target_os = "linux"))]
+fn thread_id() -> u32 {
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn perf_map_line() {
+        let mut out = Vec::new();
+        write_perf_map_line(&mut out, 0x7f00_1234, 0x40, "module::f").unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "7f001234 40 module::f\n");
+    }
+
+    #[test]
+    fn jitdump() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("jit.dump");
+        let mut jitdump = JitDump::create(&path, 62).unwrap();
+        let code = [0x48, 0x8d, 0x47, 0x01, 0xc3];
+        jitdump.write_code_load(0x1000, &code, "f").unwrap();
+        jitdump.write_code_load(0x2000, &code[..1], "g").unwrap();
+        drop(jitdump);
+
+        let bytes = fs::read(&path).unwrap();
+        let u32_at = |i: usize| u32::from_ne_bytes(bytes[i..i + 4].try_into().unwrap());
+        let u64_at = |i: usize| u64::from_ne_bytes(bytes[i..i + 8].try_into().unwrap());
+
+        // Header.
+        assert_eq!(u32_at(0), JITDUMP_MAGIC);
+        assert_eq!(u32_at(4), JITDUMP_VERSION);
+        let header_size = u32_at(8) as usize;
+        assert_eq!(header_size, 40);
+        assert_eq!(u32_at(12), 62);
+        assert_eq!(u32_at(20), std::process::id());
+
+        // First record.
+        let r = header_size;
+        assert_eq!(u32_at(r), JIT_CODE_LOAD);
+        let record_size = u32_at(r + 4) as usize;
+        assert_eq!(record_size, 56 + 2 + code.len());
+        assert_eq!(u64_at(r + 24), 0x1000);
+        assert_eq!(u64_at(r + 32), 0x1000);
+        assert_eq!(u64_at(r + 40), code.len() as u64);
+        assert_eq!(u64_at(r + 48), 0);
+        assert_eq!(&bytes[r + 56..r + 58], b"f\0");
+        assert_eq!(&bytes[r + 58..r + record_size], code);
+
+        // Second record.
+        let r = r + record_size;
+        let record_size = u32_at(r + 4) as usize;
+        assert_eq!(u64_at(r + 24), 0x2000);
+        assert_eq!(u64_at(r + 48), 1);
+        assert_eq!(&bytes[r + 56..r + 58], b"g\0");
+        assert_eq!(r + record_size, bytes.len());
+    }
+}
@@ -2,7 +2,8 @@
 
 use super::default_attrs;
 use crate::{
-    Backend, Builder, Bytecode, EvmContext, Inst, InstData, InstFlags, IntCC, Result, I256_MIN,
+    Backend, Builder, Bytecode, EvmContext, ExecutionLimits, Inst, InstData, InstFlags, IntCC,
+    Result, I256_MIN,
 };
 use revm_interpreter::{
     opcode as op, Contract, FunctionReturnFrame, FunctionStack, InstructionResult,
@@ -21,6 +22,7 @@ const STACK_CAP: usize = 1024;
 #[derive(Clone, Copy, Debug)]
 pub(super) struct FcxConfig {
     pub(super) comments: bool,
+    pub(super) debug_info: bool,
     pub(super) debug_assertions: bool,
     pub(super) frame_pointers: bool,
     pub(super) validate_eof: bool,
@@ -29,6 +31,8 @@ pub(super) struct FcxConfig {
     pub(super) inspect_stack_length: bool,
     pub(super) stack_bound_checks: bool,
     pub(super) gas_metering: bool,
+    pub(super) execution_limits: bool,
+    pub(super) stable_resume_points: bool,
 }
 
 impl Default for FcxConfig {
@@ -36,12 +40,15 @@ impl Default for FcxConfig {
         Self {
             debug_assertions: cfg!(debug_assertions),
             comments: false,
+            debug_info: false,
             frame_pointers: cfg!(debug_assertions),
             validate_eof: true,
             local_stack: false,
             inspect_stack_length: false,
             stack_bound_checks: true,
             gas_metering: true,
+            execution_limits: false,
+            stable_resume_points: false,
         }
     }
 }
@@ -323,6 +330,9 @@ impl<'a, B: Backend> FunctionCx<'a, B> {
         for (inst, _) in bytecode.iter_insts() {
             fx.translate_inst(inst)?;
         }
+        if config.debug_info {
+            fx.bcx.clear_debug_location();
+        }
 
         // Finalize the dynamic jump table.
         fx.bcx.switch_to_block(unreachable_block);
@@ -481,6 +491,9 @@ impl<'a, B: Backend> FunctionCx<'a, B> {
         let opcode = data.opcode;
         let entry_block = self.inst_entries[inst];
         self.bcx.switch_to_block(entry_block);
+        if self.config.debug_info {
+            self.bcx.set_debug_location(self.bytecode.inst_display_line(inst), 0);
+        }
 
         let is_eof = self.bytecode.is_eof();
         let is_eof_enabled = self.bytecode.spec_id.is_enabled_in(SpecId::OSAKA);
@@ -963,10 +976,14 @@ impl<'a, B: Backend> FunctionCx<'a, B> {
                             op::JUMPDEST,
                             "jumping to non-JUMPDEST; target_inst={target_inst}",
                         );
+                        if target_inst <= inst {
+                            self.build_limits_check((inst - target_inst + 1) as u64);
+                        }
                         self.inst_entries[target_inst]
                     } else {
                         // Dynamic jump.
                         debug_assert!(self.bytecode.has_dynamic_jumps());
+                        self.build_limits_check(1);
                         let target = self.pop();
                         self.incoming_dynamic_jumps
                             .push((target, self.bcx.current_block().unwrap()));
@@ -1064,6 +1081,9 @@ impl<'a, B: Backend> FunctionCx<'a, B> {
 
             op::RJUMP | op::RJUMPI => {
                 let (_, target_inst) = self.bytecode.iter_rjump_target_insts(data).next().unwrap();
+                if target_inst <= inst {
+                    self.build_limits_check((inst - target_inst + 1) as u64);
+                }
                 let target = self.inst_entries[target_inst];
                 if opcode == op::RJUMP {
                     self.bcx.br(target);
@@ -1076,6 +1096,12 @@ impl<'a, B: Backend> FunctionCx<'a, B> {
                 goto_return!(no_branch);
             }
             op::RJUMPV => {
+                // Charge for the longest backward jump, if any.
+                let min_target_inst =
+                    self.bytecode.iter_rjump_target_insts(data).map(|(_, target)| target).min();
+                if let Some(target_inst) = min_target_inst.filter(|&target| target <= inst) {
+                    self.build_limits_check((inst - target_inst + 1) as u64);
+                }
                 let index = self.pop();
                 let default = self.inst_entries[inst + 1];
                 let targets = self
@@ -1336,6 +1362,9 @@ impl<'a, B: Backend> FunctionCx<'a, B> {
 
         let idx = u16::from_be_bytes(imm.try_into().unwrap()) as usize;
 
+        // Check the execution limits at the section entry.
+        self.build_limits_check(1);
+
         // Check stack max height.
         let types = self
             .expect_eof()
@@ -1407,7 +1436,8 @@ impl<'a, B: Backend> FunctionCx<'a, B> {
 
     /// Adds a resume point and returns its index.
     fn add_resume_at(&mut self, block: B::BasicBlock) -> Option<B::Value> {
-        let value = self.bcx.block_addr(block);
+        let value =
+            if self.config.stable_resume_points { None } else { self.bcx.block_addr(block) };
         if self.resume_blocks.is_empty() {
             self.resume_kind =
                 if value.is_some() { ResumeKind::Blocks } else { ResumeKind::Indexes };
@@ -1525,6 +1555,77 @@ impl<'a, B: Backend> FunctionCx<'a, B> {
         }
     }
 
+    /// Builds a check of the execution limits, charging `cost` instructions to the budget.
+    ///
+    /// The compiled function returns revm's `InstructionResult`, which has no variant for a halt
+    /// requested by the caller, and any other value would be an invalid enum discriminant. The
+    /// closest one, `FatalExternalError`, is also returned by builtins, so the `exceeded` flag is
+    /// set alongside it, and `EvmCompilerFn::call_with_limits` returns a dedicated error based on
+    /// both.
+    ///
+    /// ```ignore (pseudo-code)
+    /// if let Some(limits) = ecx.limits {
+    ///     let budget = limits.instruction_budget.load(Relaxed);
+    ///     let (budget, overflow) = budget.overflowing_sub(cost);
+    ///     if overflow || limits.cancelled.load(Relaxed) {
+    ///         limits.exceeded.store(true, Relaxed);
+    ///         return InstructionResult::FatalExternalError;
+    ///     }
+    ///     limits.instruction_budget.store(budget, Relaxed);
+    /// }
+    /// ```
+    fn build_limits_check(&mut self, cost: u64) {
+        if !self.config.execution_limits {
+            return;
+        }
+
+        let i64_type = self.bcx.type_int(64);
+        let limits_addr =
+            self.get_field(self.ecx, mem::offset_of!(EvmContext<'_>, limits), "ecx.limits.addr");
+        let limits = self.bcx.load(self.ptr_type, limits_addr, "ecx.limits");
+        let is_null = self.bcx.is_null(limits);
+        let check_block = self.create_block_after_current("limits");
+        let halt_block = self.create_block_after(check_block, "limits.halt");
+        let store_block = self.create_block_after(halt_block, "limits.store");
+        let contd_block = self.create_block_after(store_block, "limits.contd");
+        self.bcx.brif(is_null, contd_block, check_block);
+
+        self.bcx.switch_to_block(check_block);
+        let budget_addr = self.get_field(
+            limits,
+            mem::offset_of!(ExecutionLimits, instruction_budget),
+            "limits.instruction_budget.addr",
+        );
+        let budget = self.bcx.load_atomic(i64_type, budget_addr, "limits.instruction_budget");
+        let cost = self.bcx.iconst(i64_type, cost as i64);
+        let (budget, overflow) = self.bcx.usub_overflow(budget, cost);
+        let cancelled_addr = self.get_field(
+            limits,
+            mem::offset_of!(ExecutionLimits, cancelled),
+            "limits.cancelled.addr",
+        );
+        let cancelled = self.bcx.load_atomic(self.i8_type, cancelled_addr, "limits.cancelled");
+        let cancelled = self.bcx.icmp_imm(IntCC::NotEqual, cancelled, 0);
+        let exceeded = self.bcx.bitor(overflow, cancelled);
+        self.bcx.brif_cold(exceeded, halt_block, store_block, true);
+
+        self.bcx.switch_to_block(halt_block);
+        let exceeded_addr = self.get_field(
+            limits,
+            mem::offset_of!(ExecutionLimits, exceeded),
+            "limits.exceeded.addr",
+        );
+        let one = self.bcx.iconst(self.i8_type, 1);
+        self.bcx.store_atomic(one, exceeded_addr);
+        self.build_return_imm(InstructionResult::FatalExternalError);
+
+        self.bcx.switch_to_block(store_block);
+        self.bcx.store_atomic(budget, budget_addr);
+        self.bcx.br(contd_block);
+
+        self.bcx.switch_to_block(contd_block);
+    }
+
     /*
     /// Builds a check, failing if the condition is false.
     ///
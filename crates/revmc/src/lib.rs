@@ -13,7 +13,9 @@ mod bytecode;
 pub use bytecode::*;
 
 mod compiler;
-pub use compiler::{EvmCompiler, EvmCompilerInput};
+#[cfg(feature = "llvm")]
+pub use compiler::{compile_many, CompileManyEntry, CompileManyManifest, CompileManyOptions};
+pub use compiler::{CompileTimings, EvmCompiler, EvmCompilerInput};
 
 mod linker;
 pub use linker::Linker;
@@ -5,6 +5,7 @@ use std::path::{Path, PathBuf};
 pub struct Linker {
     cc: Option<PathBuf>,
     linker: Option<PathBuf>,
+    ar: Option<PathBuf>,
     cflags: Vec<String>,
 }
 
@@ -17,7 +18,7 @@ impl Default for Linker {
 impl Linker {
     /// Creates a new linker.
     pub fn new() -> Self {
-        Self { cc: None, linker: None, cflags: vec![] }
+        Self { cc: None, linker: None, ar: None, cflags: vec![] }
     }
 
     /// Sets the C compiler to use for linking. Default: "cc".
@@ -30,6 +31,11 @@ impl Linker {
         self.linker = linker;
     }
 
+    /// Sets the archiver to use for creating static libraries. Default: "ar".
+    pub fn ar(&mut self, ar: Option<PathBuf>) {
+        self.ar = ar;
+    }
+
     /// Sets the C compiler flags to use for linking.
     pub fn cflags(&mut self, cflags: impl IntoIterator<Item = impl Into<String>>) {
         self.cflags.extend(cflags.into_iter().map(Into::into));
@@ -84,6 +90,33 @@ impl Linker {
         }
         Ok(())
     }
+
+    /// Bundles the given object files into a static library at the given path.
+    #[instrument(level = "debug", skip_all)]
+    pub fn archive(
+        &self,
+        out: &Path,
+        objects: impl IntoIterator<Item = impl AsRef<std::ffi::OsStr>>,
+    ) -> std::io::Result<()> {
+        let ar = match &self.ar {
+            Some(ar) => ar.clone(),
+            None => std::env::var_os("AR").map(PathBuf::from).unwrap_or_else(|| "ar".into()),
+        };
+
+        let mut cmd = std::process::Command::new(ar);
+        cmd.arg("rcs").arg(out);
+        cmd.args(objects);
+        debug!(cmd=?cmd.get_program(), "archiving");
+        trace!(?cmd, "full archiving command");
+        let output = cmd.output()?;
+        if !output.status.success() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                format!("ar failed with {output:#?}"),
+            ));
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
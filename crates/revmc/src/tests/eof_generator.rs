@@ -0,0 +1,688 @@
+//! Structure-aware EOF container generator for differential fuzzing.
+//!
+//! The generated containers pass EOF validation: they have multiple code sections with exact type
+//! information, subcontainers referenced by `EOFCREATE`, and a data section. Control flow is built
+//! from balanced `RJUMP*` constructs, so that the stack height at every instruction is known
+//! statically, as required by EOF stack validation.
+
+use super::{
+    generator::{COMPUTE_OPS, MAX_DEPTH, MAX_HEIGHT, MAX_LOOP_ITERATIONS, MAX_OFFSET, MAX_SIZE},
+    OTHER_ADDR,
+};
+use crate::{format_bytecode, stack_io};
+use arbitrary::{Arbitrary, Result, Unstructured};
+use revm_interpreter::opcode as op;
+use revm_primitives::{
+    eof::{EofBody, TypesSection},
+    Bytes, SpecId,
+};
+use std::{fmt, mem};
+
+/// The maximum number of code sections.
+const MAX_SECTIONS: usize = 4;
+/// The maximum number of statements in a code section.
+const MAX_STATEMENTS: usize = 64;
+/// The maximum number of inputs and outputs of a code section.
+const MAX_IO: u8 = 3;
+/// The maximum number of subcontainers.
+const MAX_CONTAINERS: usize = 2;
+/// The maximum size of the data section.
+const MAX_DATA: usize = 96;
+/// The maximum number of `RJUMPV` cases.
+const MAX_CASES: usize = 4;
+/// The `outputs` value of non-returning code sections.
+const NON_RETURNING: u8 = 0x80;
+
+/// Opcodes that are not allowed in EOF code.
+const EOF_BANNED_OPS: &[u8] = &[op::PC, op::GAS, op::CODESIZE, op::EXTCODESIZE, op::EXTCODEHASH];
+
+/// An EOF container generated by [`EofGenerator`].
+#[derive(Clone)]
+pub struct GeneratedEofContainer {
+    pub bytecode: Bytes,
+    pub spec_id: SpecId,
+}
+
+impl fmt::Debug for GeneratedEofContainer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GeneratedEofContainer")
+            .field("bytecode", &format_bytecode(&self.bytecode, self.spec_id))
+            .field("spec_id", &self.spec_id)
+            .finish()
+    }
+}
+
+impl<'a> Arbitrary<'a> for GeneratedEofContainer {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let bytecode = EofGenerator::default().generate(u)?.into_eof().raw;
+        Ok(Self { bytecode, spec_id: SpecId::OSAKA })
+    }
+}
+
+/// Generates valid EOF containers from unstructured data.
+///
+/// Section 0 may end with a `JUMPF` to the last section, which is then non-returning. All the
+/// other sections are returning, and each one is called by the previous section, so that every
+/// section is reachable. Sections only call sections with a higher index, which rules out
+/// recursion.
+#[derive(Default)]
+pub struct EofGenerator {
+    types: Vec<TypesSection>,
+    n_containers: usize,
+    data_len: usize,
+
+    // Code section state.
+    section: usize,
+    code: Vec<u8>,
+    /// The statically known stack height.
+    height: usize,
+    /// The maximum stack height of the current section.
+    max_height: usize,
+    /// Values at or below this height belong to an enclosing construct, and must not be consumed.
+    floor: usize,
+    /// The current nesting depth of loops and branches.
+    depth: usize,
+    statements: usize,
+    /// The position of each label.
+    labels: Vec<Option<usize>>,
+    /// The position of each relative jump immediate, the position its offset is relative to,
+    /// and the label.
+    fixups: Vec<(usize, usize, usize)>,
+}
+
+impl EofGenerator {
+    /// Generates a container body, consuming as much of the data as needed.
+    pub fn generate(mut self, u: &mut Unstructured<'_>) -> Result<EofBody> {
+        let n_sections = u.int_in_range(1..=MAX_SECTIONS)?;
+        let has_terminal = n_sections > 1 && u.arbitrary()?;
+        self.types.push(TypesSection { inputs: 0, outputs: NON_RETURNING, max_stack_size: 0 });
+        for i in 1..n_sections {
+            let inputs = u.int_in_range(0..=MAX_IO)?;
+            let outputs = if has_terminal && i == n_sections - 1 {
+                NON_RETURNING
+            } else {
+                u.int_in_range(0..=MAX_IO)?
+            };
+            self.types.push(TypesSection { inputs, outputs, max_stack_size: 0 });
+        }
+        self.n_containers = u.int_in_range(0..=MAX_CONTAINERS)?;
+        self.data_len = u.int_in_range(0..=MAX_DATA)?;
+        let data_section = Bytes::copy_from_slice(u.bytes(self.data_len.min(u.len()))?);
+        self.data_len = data_section.len();
+
+        let mut code_section = Vec::with_capacity(n_sections);
+        for section in 0..n_sections {
+            code_section.push(self.code_section(u, section)?);
+            self.types[section].max_stack_size = self.max_height as u16;
+        }
+        let container_section =
+            (0..self.n_containers).map(|_| initcode(u)).collect::<Result<Vec<_>>>()?;
+
+        Ok(EofBody {
+            types_section: self.types,
+            code_section,
+            container_section,
+            data_section,
+            is_data_filled: true,
+        })
+    }
+
+    fn code_section(&mut self, u: &mut Unstructured<'_>, section: usize) -> Result<Bytes> {
+        let ty = self.types[section];
+        self.section = section;
+        self.code.clear();
+        self.height = ty.inputs as usize;
+        self.max_height = self.height;
+        self.floor = 0;
+        self.depth = 0;
+        self.statements = 0;
+        self.labels.clear();
+        self.fixups.clear();
+
+        // Access the next section.
+        if self.is_returning(section + 1) {
+            self.callf(u, section + 1)?;
+        }
+        while !self.is_done(u) {
+            self.statement(u)?;
+        }
+        // Access the subcontainers last, since `EOFCREATE` suspends execution.
+        if section == 0 {
+            for container in 0..self.n_containers {
+                self.eofcreate(u, container)?;
+            }
+        }
+        self.epilogue(u)?;
+
+        for &(pos, base, label) in &self.fixups {
+            let target = self.labels[label].expect("unplaced label");
+            let offset = (target as isize - base as isize) as i16;
+            self.code[pos..pos + 2].copy_from_slice(&offset.to_be_bytes());
+        }
+        Ok(Bytes::copy_from_slice(&self.code))
+    }
+
+    fn is_done(&self, u: &Unstructured<'_>) -> bool {
+        u.is_empty() || self.statements >= MAX_STATEMENTS
+    }
+
+    fn is_returning(&self, section: usize) -> bool {
+        self.types.get(section).is_some_and(|ty| ty.outputs != NON_RETURNING)
+    }
+
+    fn statement(&mut self, u: &mut Unstructured<'_>) -> Result<()> {
+        self.statements += 1;
+        let can_nest = self.depth < MAX_DEPTH;
+        match u.int_in_range(0..=12)? {
+            0..=2 => self.compute(u)?,
+            3 => self.stack_op(u)?,
+            4 => self.memory_op(u)?,
+            5 => self.data_op(u)?,
+            6 => self.storage_op(u)?,
+            7 if can_nest => self.if_(u)?,
+            8 if can_nest => self.if_else(u)?,
+            9 if can_nest => self.switch(u)?,
+            10 if can_nest => self.loop_(u)?,
+            11 => {
+                let callees = self.section + 1..self.types.len();
+                let callees = callees.filter(|&i| self.is_returning(i)).collect::<Vec<_>>();
+                if callees.is_empty() {
+                    self.compute(u)?;
+                } else {
+                    let callee = *u.choose(&callees)?;
+                    self.callf(u, callee)?;
+                }
+            }
+            12 => self.ext_call(u)?,
+            _ => self.compute(u)?,
+        }
+        if self.height > MAX_HEIGHT {
+            self.pop_to(self.floor.max(MAX_HEIGHT / 2));
+        }
+        Ok(())
+    }
+
+    fn block(&mut self, u: &mut Unstructured<'_>) -> Result<()> {
+        let n = u.int_in_range(1..=8)?;
+        for _ in 0..n {
+            if self.is_done(u) {
+                break;
+            }
+            self.statement(u)?;
+        }
+        Ok(())
+    }
+
+    /// Runs `f` in a nested scope, which must not consume the current stack values, and restores
+    /// the stack height afterwards.
+    fn nested(
+        &mut self,
+        u: &mut Unstructured<'_>,
+        f: impl FnOnce(&mut Self, &mut Unstructured<'_>) -> Result<()>,
+    ) -> Result<()> {
+        let floor = mem::replace(&mut self.floor, self.height);
+        self.depth += 1;
+        f(self, u)?;
+        self.pop_to(self.floor);
+        self.depth -= 1;
+        self.floor = floor;
+        Ok(())
+    }
+
+    fn compute(&mut self, u: &mut Unstructured<'_>) -> Result<()> {
+        let ops = COMPUTE_OPS.iter().filter(|op| !EOF_BANNED_OPS.contains(op));
+        let opcode = *u.choose(&ops.copied().collect::<Vec<_>>())?;
+        let (inputs, _) = stack_io(opcode);
+        for _ in 0..inputs {
+            self.push_value(u)?;
+        }
+        self.op(opcode);
+        Ok(())
+    }
+
+    fn stack_op(&mut self, u: &mut Unstructured<'_>) -> Result<()> {
+        let available = self.height - self.floor;
+        match u.int_in_range(0..=5)? {
+            0 if self.height > 0 => {
+                let n = u.int_in_range(1..=self.height.min(16))? as u8;
+                self.op(op::DUP1 + n - 1);
+            }
+            1 if available > 1 => {
+                let n = u.int_in_range(1..=(available - 1).min(16))? as u8;
+                self.op(op::SWAP1 + n - 1);
+            }
+            2 if self.height > 0 => {
+                let imm = u.int_in_range(0..=(self.height - 1).min(32))? as u8;
+                self.op(op::DUPN);
+                self.code.push(imm);
+            }
+            3 if available > 1 => {
+                let imm = u.int_in_range(0..=(available - 2).min(32))? as u8;
+                self.op(op::SWAPN);
+                self.code.push(imm);
+            }
+            // Swaps the `n + 1`th and `n + m + 1`th items.
+            4 if available > 2 => {
+                let max = (available - 1).min(16);
+                let n = u.int_in_range(1..=max - 1)?;
+                let m = u.int_in_range(1..=max - n)?;
+                self.op(op::EXCHANGE);
+                self.code.push((((n - 1) << 4) | (m - 1)) as u8);
+            }
+            5 if available > 0 => self.op(op::POP),
+            _ => self.push_value(u)?,
+        }
+        Ok(())
+    }
+
+    fn memory_op(&mut self, u: &mut Unstructured<'_>) -> Result<()> {
+        let opcode = *u.choose(&[
+            op::MSTORE,
+            op::MSTORE8,
+            op::MLOAD,
+            op::KECCAK256,
+            op::CALLDATACOPY,
+            op::RETURNDATACOPY,
+            op::MCOPY,
+            op::LOG0,
+            op::LOG1,
+            op::LOG2,
+        ])?;
+        // Arguments are pushed in reverse order.
+        match opcode {
+            op::MSTORE | op::MSTORE8 => {
+                self.push_value(u)?;
+                self.push_offset(u)?;
+            }
+            op::MLOAD => self.push_offset(u)?,
+            op::KECCAK256 => {
+                self.push_size(u)?;
+                self.push_offset(u)?;
+            }
+            op::CALLDATACOPY | op::MCOPY => {
+                self.push_size(u)?;
+                self.push_offset(u)?;
+                self.push_offset(u)?;
+            }
+            // Out of bounds unless the size is zero, since there is no return data.
+            op::RETURNDATACOPY => {
+                self.push(&[0]);
+                self.push(&[0]);
+                self.push_offset(u)?;
+            }
+            _ => {
+                for _ in op::LOG0..opcode {
+                    self.push_value(u)?;
+                }
+                self.push_size(u)?;
+                self.push_offset(u)?;
+            }
+        }
+        self.op(opcode);
+        Ok(())
+    }
+
+    fn data_op(&mut self, u: &mut Unstructured<'_>) -> Result<()> {
+        match u.int_in_range(0..=4)? {
+            0 if self.data_len >= 32 => {
+                let offset = u.int_in_range(0..=self.data_len - 32)? as u16;
+                self.op(op::DATALOADN);
+                self.code.extend_from_slice(&offset.to_be_bytes());
+            }
+            0 | 1 => {
+                self.push_data_offset(u)?;
+                self.op(op::DATALOAD);
+            }
+            2 => self.op(op::DATASIZE),
+            3 => {
+                self.push_size(u)?;
+                self.push_data_offset(u)?;
+                self.push_offset(u)?;
+                self.op(op::DATACOPY);
+            }
+            _ => {
+                self.push_data_offset(u)?;
+                self.op(op::RETURNDATALOAD);
+            }
+        }
+        Ok(())
+    }
+
+    fn storage_op(&mut self, u: &mut Unstructured<'_>) -> Result<()> {
+        let opcode = *u.choose(&[op::SLOAD, op::SSTORE, op::TLOAD, op::TSTORE])?;
+        if matches!(opcode, op::SSTORE | op::TSTORE) {
+            self.push_value(u)?;
+        }
+        // Include the slots of the default test storage.
+        let slot = *u.choose(&[0, 1, 2, 3, 69])?;
+        self.push(&[slot]);
+        self.op(opcode);
+        Ok(())
+    }
+
+    /// ```text
+    ///     <cond> RJUMPI end
+    ///     <body>
+    /// end:
+    /// ```
+    fn if_(&mut self, u: &mut Unstructured<'_>) -> Result<()> {
+        let end = self.new_label();
+        self.push_value(u)?;
+        self.rjump(op::RJUMPI, end);
+        self.nested(u, Self::block)?;
+        self.place_label(end);
+        Ok(())
+    }
+
+    /// ```text
+    ///     <cond> RJUMPI then
+    ///     <else>
+    ///     RJUMP end
+    /// then:
+    ///     <then>
+    /// end:
+    /// ```
+    fn if_else(&mut self, u: &mut Unstructured<'_>) -> Result<()> {
+        let then = self.new_label();
+        let end = self.new_label();
+        self.push_value(u)?;
+        self.rjump(op::RJUMPI, then);
+        self.nested(u, Self::block)?;
+        self.rjump(op::RJUMP, end);
+        self.place_label(then);
+        self.nested(u, Self::block)?;
+        self.place_label(end);
+        Ok(())
+    }
+
+    /// ```text
+    ///     <index> RJUMPV case_0 .. case_n
+    ///     <default>
+    ///     RJUMP end
+    /// case_0:
+    ///     <case_0>
+    ///     RJUMP end
+    ///     ..
+    /// case_n:
+    ///     <case_n>
+    /// end:
+    /// ```
+    fn switch(&mut self, u: &mut Unstructured<'_>) -> Result<()> {
+        let n_cases = u.int_in_range(1..=MAX_CASES)?;
+        let cases = (0..n_cases).map(|_| self.new_label()).collect::<Vec<_>>();
+        let end = self.new_label();
+
+        // Sometimes out of bounds, to take the default case.
+        self.push(&[u.int_in_range(0..=n_cases as u8)?]);
+        self.op(op::RJUMPV);
+        self.code.push(n_cases as u8 - 1);
+        let table = self.code.len();
+        let base = table + n_cases * 2;
+        for (i, &case) in cases.iter().enumerate() {
+            self.fixups.push((table + i * 2, base, case));
+        }
+        self.code.resize(base, 0);
+
+        self.nested(u, Self::block)?;
+        for &case in &cases {
+            self.rjump(op::RJUMP, end);
+            self.place_label(case);
+            self.nested(u, Self::block)?;
+        }
+        self.place_label(end);
+        Ok(())
+    }
+
+    /// A loop with a bounded counter:
+    ///
+    /// ```text
+    ///     PUSH1 n
+    /// loop:
+    ///     <body>
+    ///     PUSH1 1 SWAP1 SUB
+    ///     DUP1 RJUMPI loop
+    ///     POP
+    /// ```
+    fn loop_(&mut self, u: &mut Unstructured<'_>) -> Result<()> {
+        let iterations = u.int_in_range(1..=MAX_LOOP_ITERATIONS)?;
+        self.push(&[iterations]);
+        let start = self.new_label();
+        self.place_label(start);
+        self.nested(u, Self::block)?;
+        self.push(&[1]);
+        self.op(op::SWAP1);
+        self.op(op::SUB);
+        self.op(op::DUP1);
+        self.rjump(op::RJUMPI, start);
+        self.op(op::POP);
+        Ok(())
+    }
+
+    fn callf(&mut self, u: &mut Unstructured<'_>, section: usize) -> Result<()> {
+        let TypesSection { inputs, outputs, .. } = self.types[section];
+        for _ in 0..inputs {
+            self.push_value(u)?;
+        }
+        self.code.push(op::CALLF);
+        self.code.extend_from_slice(&(section as u16).to_be_bytes());
+        self.set_height(self.height - inputs as usize + outputs as usize);
+        Ok(())
+    }
+
+    fn ext_call(&mut self, u: &mut Unstructured<'_>) -> Result<()> {
+        let opcode = *u.choose(&[op::EXTCALL, op::EXTDELEGATECALL, op::EXTSTATICCALL])?;
+        if opcode == op::EXTCALL {
+            self.push(&[0]);
+        }
+        self.push_size(u)?;
+        self.push_offset(u)?;
+        self.push(&OTHER_ADDR[..]);
+        self.op(opcode);
+        Ok(())
+    }
+
+    fn eofcreate(&mut self, u: &mut Unstructured<'_>, container: usize) -> Result<()> {
+        self.push_size(u)?;
+        self.push_offset(u)?;
+        self.push_value(u)?;
+        self.push(&[0]);
+        self.op(op::EOFCREATE);
+        self.code.push(container as u8);
+        Ok(())
+    }
+
+    fn epilogue(&mut self, u: &mut Unstructured<'_>) -> Result<()> {
+        let ty = self.types[self.section];
+        if ty.outputs != NON_RETURNING {
+            // `RETF` requires the exact number of outputs on the stack.
+            let outputs = ty.outputs as usize;
+            self.pop_to(outputs);
+            while self.height < outputs {
+                self.push_value(u)?;
+            }
+            self.code.push(op::RETF);
+            return Ok(());
+        }
+
+        let terminal = self.types.len() - 1;
+        if self.section == 0 && terminal != 0 && !self.is_returning(terminal) {
+            for _ in self.height..self.types[terminal].inputs as usize {
+                self.push_value(u)?;
+            }
+            self.code.push(op::JUMPF);
+            self.code.extend_from_slice(&(terminal as u16).to_be_bytes());
+            return Ok(());
+        }
+
+        match u.int_in_range(0..=3)? {
+            0 => self.op(op::STOP),
+            1 => self.op(op::INVALID),
+            kind => {
+                if self.height > 0 {
+                    self.push(&[0]);
+                    self.op(op::MSTORE);
+                }
+                self.push(&[32]);
+                self.push(&[0]);
+                self.op(if kind == 2 { op::RETURN } else { op::REVERT });
+            }
+        }
+        Ok(())
+    }
+
+    /// Pushes a random value, biased towards edge cases.
+    fn push_value(&mut self, u: &mut Unstructured<'_>) -> Result<()> {
+        match u.int_in_range(0..=4)? {
+            0 => self.op(op::PUSH0),
+            1 => self.push(&[u.int_in_range(0..=32)?]),
+            2 => {
+                let mut value = [0; 32];
+                match u.int_in_range(0..=3)? {
+                    0 => value = [0xff; 32],
+                    1 => value[0] = 0x80,
+                    2 => {
+                        value = [0xff; 32];
+                        value[0] = 0x7f;
+                    }
+                    _ => value[u.int_in_range(0..=31)?] = 1,
+                }
+                self.push(&value);
+            }
+            _ => {
+                let len = u.int_in_range(1..=32)?;
+                let value = u.arbitrary::<[u8; 32]>()?;
+                self.push(&value[..len]);
+            }
+        }
+        Ok(())
+    }
+
+    /// Pushes a memory offset, often word-aligned.
+    fn push_offset(&mut self, u: &mut Unstructured<'_>) -> Result<()> {
+        let mut offset = u.int_in_range(0..=MAX_OFFSET)?;
+        if u.arbitrary()? {
+            offset &= !31;
+        }
+        self.push(&offset.to_be_bytes());
+        Ok(())
+    }
+
+    /// Pushes a data section offset, sometimes out of bounds.
+    fn push_data_offset(&mut self, u: &mut Unstructured<'_>) -> Result<()> {
+        let offset = u.int_in_range(0..=self.data_len as u16 + 32)?;
+        self.push(&offset.to_be_bytes());
+        Ok(())
+    }
+
+    /// Pushes a memory region size.
+    fn push_size(&mut self, u: &mut Unstructured<'_>) -> Result<()> {
+        let size = u.int_in_range(0..=MAX_SIZE)?;
+        self.push(&[size]);
+        Ok(())
+    }
+
+    fn push(&mut self, value: &[u8]) {
+        debug_assert!(!value.is_empty() && value.len() <= 32);
+        self.code.push(op::PUSH1 + value.len() as u8 - 1);
+        self.code.extend_from_slice(value);
+        self.set_height(self.height + 1);
+    }
+
+    /// Emits `RJUMP` or `RJUMPI` to the given label.
+    fn rjump(&mut self, opcode: u8, label: usize) {
+        self.op(opcode);
+        let pos = self.code.len();
+        self.fixups.push((pos, pos + 2, label));
+        self.code.extend_from_slice(&[0, 0]);
+    }
+
+    fn new_label(&mut self) -> usize {
+        self.labels.push(None);
+        self.labels.len() - 1
+    }
+
+    fn place_label(&mut self, label: usize) {
+        self.labels[label] = Some(self.code.len());
+    }
+
+    fn pop_to(&mut self, height: usize) {
+        while self.height > height {
+            self.op(op::POP);
+        }
+    }
+
+    /// Emits an instruction without its immediate.
+    fn op(&mut self, opcode: u8) {
+        let (inputs, outputs) = stack_io(opcode);
+        debug_assert!(self.height >= inputs as usize, "stack underflow at {opcode:#x}");
+        self.code.push(opcode);
+        self.set_height(self.height - inputs as usize + outputs as usize);
+    }
+
+    fn set_height(&mut self, height: usize) {
+        self.height = height;
+        self.max_height = self.max_height.max(height);
+    }
+}
+
+/// Returns an initcode container, which either reverts or returns a runtime container.
+fn initcode(u: &mut Unstructured<'_>) -> Result<Bytes> {
+    let returns = u.arbitrary()?;
+    let (code, container_section) = if returns {
+        let runtime = container(&[op::STOP], 0, vec![]);
+        (&[op::PUSH0, op::PUSH0, op::RETURNCONTRACT, 0][..], vec![runtime])
+    } else {
+        (&[op::PUSH0, op::PUSH0, op::REVERT][..], vec![])
+    };
+    Ok(container(code, 2, container_section))
+}
+
+fn container(code: &[u8], max_stack_size: u16, container_section: Vec<Bytes>) -> Bytes {
+    EofBody {
+        types_section: vec![TypesSection { inputs: 0, outputs: NON_RETURNING, max_stack_size }],
+        code_section: vec![Bytes::copy_from_slice(code)],
+        container_section,
+        data_section: Bytes::new(),
+        is_data_filled: true,
+    }
+    .into_eof()
+    .raw
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        tests::{
+            generator::{run_seeded, seeded_data},
+            run_test_case, TestCase,
+        },
+        Backend, EvmCompiler,
+    };
+
+    fn container(seed: u64) -> GeneratedEofContainer {
+        GeneratedEofContainer::arbitrary(&mut Unstructured::new(&seeded_data(seed))).unwrap()
+    }
+
+    #[test]
+    fn valid() {
+        for seed in 0..64 {
+            let container = container(seed);
+            let eof = revm_primitives::Eof::decode(container.bytecode.clone()).unwrap();
+            if let Err(e) = revm_interpreter::analysis::validate_eof_inner(&eof, None) {
+                panic!("{seed}: {e:?}\n{container:#?}");
+            }
+        }
+    }
+
+    matrix_tests!(vs_interpreter = run_vs_interpreter);
+
+    fn run_vs_interpreter<B: Backend>(compiler: &mut EvmCompiler<B>) {
+        run_seeded(compiler, |container: &GeneratedEofContainer, compiler| {
+            run_test_case(
+                &TestCase::what_interpreter_says(&container.bytecode, container.spec_id),
+                compiler,
+            );
+        });
+    }
+}
@@ -0,0 +1,525 @@
+//! A host whose state is generated from fuzz input, and a runner that drives full call/resume
+//! cycles through both the interpreter and compiled code.
+
+use super::{
+    def_env, new_interpreter,
+    runner::{assert_actions, MemDisplay},
+    GeneratedEofContainer, GeneratedProgram, DEF_ADDR, DEF_BN, DEF_CALLER, OTHER_ADDR,
+};
+use crate::{format_bytecode, Backend, EvmCompiler, EvmCompilerFn};
+use arbitrary::{Arbitrary, Result, Unstructured};
+use revm_interpreter::{
+    opcode as op, AccountLoad, CallOutcome, CreateOutcome, Eip7702CodeLoad, Gas, Host,
+    InstructionResult, Interpreter, InterpreterAction, InterpreterResult, SStoreResult,
+    SelfDestructResult, SharedMemory, StateLoad, EMPTY_SHARED_MEMORY,
+};
+use revm_primitives::{
+    keccak256, spec_to_generic, Address, Bytes, Env, HashMap, HashSet, Log, SpecId, B256,
+    KECCAK_EMPTY, U256,
+};
+use similar_asserts::assert_eq;
+use std::{fmt, mem};
+
+/// The maximum number of call/resume cycles of a single run.
+const MAX_FRAMES: usize = 64;
+/// The maximum number of generated accounts.
+const MAX_ACCOUNTS: usize = 8;
+/// The maximum number of generated storage slots of an account.
+const MAX_SLOTS: usize = 8;
+/// The maximum size of generated code and return data.
+const MAX_BYTES: usize = 64;
+
+/// The state of a [`FuzzHost`], generated from fuzz input.
+#[derive(Clone, Debug, Default)]
+pub struct FuzzHostState {
+    pub accounts: HashMap<Address, FuzzAccount>,
+    pub block_hashes: HashMap<u64, B256>,
+    /// Accounts and storage slots that are warm from the start, like with an access list.
+    pub warm_accounts: HashSet<Address>,
+    pub warm_slots: HashSet<(Address, U256)>,
+    /// The outcomes of the sub-calls and creations, in order. Cycled through if exhausted.
+    pub outcomes: Vec<FuzzOutcome>,
+}
+
+/// An account in a [`FuzzHostState`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FuzzAccount {
+    pub balance: U256,
+    pub code: Bytes,
+    pub storage: HashMap<U256, U256>,
+}
+
+/// The outcome of a sub-call or creation.
+#[derive(Clone, Debug)]
+pub struct FuzzOutcome {
+    pub result: InstructionResult,
+    pub output: Bytes,
+    /// The percentage of the gas limit that is used.
+    pub gas_used_percent: u8,
+    /// The address of the created account, if successful.
+    pub created: Address,
+}
+
+impl<'a> Arbitrary<'a> for FuzzHostState {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let mut state = Self::default();
+        for _ in 0..u.int_in_range(0..=MAX_ACCOUNTS)? {
+            let address = arbitrary_address(u)?;
+            let mut account = FuzzAccount {
+                balance: arbitrary_word(u)?,
+                code: arbitrary_bytes(u)?,
+                storage: HashMap::default(),
+            };
+            for _ in 0..u.int_in_range(0..=MAX_SLOTS)? {
+                account.storage.insert(arbitrary_slot(u)?, arbitrary_word(u)?);
+            }
+            if u.arbitrary()? {
+                state.warm_accounts.insert(address);
+            }
+            for &slot in account.storage.keys() {
+                if u.arbitrary()? {
+                    state.warm_slots.insert((address, slot));
+                }
+            }
+            state.accounts.insert(address, account);
+        }
+        for _ in 0..u.int_in_range(0..=4)? {
+            // Around the default block number.
+            let current = DEF_BN.to::<u64>();
+            let number = u.int_in_range(current - 260..=current)?;
+            state.block_hashes.insert(number, B256::from(u.arbitrary::<[u8; 32]>()?));
+        }
+        for _ in 0..u.int_in_range(0..=4)? {
+            let result = *u.choose(&[
+                InstructionResult::Stop,
+                InstructionResult::Return,
+                InstructionResult::Revert,
+                InstructionResult::OutOfGas,
+            ])?;
+            state.outcomes.push(FuzzOutcome {
+                result,
+                output: arbitrary_bytes(u)?,
+                gas_used_percent: u.int_in_range(0..=100)?,
+                created: arbitrary_address(u)?,
+            });
+        }
+        Ok(state)
+    }
+}
+
+/// Addresses are biased towards the ones that programs are likely to access.
+fn arbitrary_address(u: &mut Unstructured<'_>) -> Result<Address> {
+    Ok(match u.int_in_range(0..=3)? {
+        0 => *u.choose(&[DEF_ADDR, DEF_CALLER, OTHER_ADDR])?,
+        // Small values pushed by generated programs.
+        1 | 2 => Address::with_last_byte(u.int_in_range(0..=32)?),
+        _ => Address::from(u.arbitrary::<[u8; 20]>()?),
+    })
+}
+
+/// Slots are biased towards the ones that generated programs access.
+fn arbitrary_slot(u: &mut Unstructured<'_>) -> Result<U256> {
+    Ok(if u.arbitrary()? {
+        U256::from(*u.choose(&[0u8, 1, 2, 3, 69])?)
+    } else {
+        arbitrary_word(u)?
+    })
+}
+
+fn arbitrary_word(u: &mut Unstructured<'_>) -> Result<U256> {
+    Ok(match u.int_in_range(0..=2)? {
+        0 => U256::ZERO,
+        1 => U256::from(u.arbitrary::<u64>()?),
+        _ => U256::from_be_bytes(u.arbitrary::<[u8; 32]>()?),
+    })
+}
+
+fn arbitrary_bytes(u: &mut Unstructured<'_>) -> Result<Bytes> {
+    let len = u.int_in_range(0..=MAX_BYTES)?;
+    Ok(Bytes::copy_from_slice(u.bytes(len.min(u.len()))?))
+}
+
+/// A host with warm/cold access tracking over a [`FuzzHostState`].
+#[derive(Clone, Debug)]
+pub struct FuzzHost {
+    pub env: Env,
+    pub state: FuzzHostState,
+    /// The storage values at the start of the transaction.
+    pub original_storage: HashMap<(Address, U256), U256>,
+    pub transient_storage: HashMap<(Address, U256), U256>,
+    pub logs: Vec<Log>,
+    pub selfdestructs: Vec<(Address, Address)>,
+    next_outcome: usize,
+}
+
+impl FuzzHost {
+    pub fn new(mut state: FuzzHostState) -> Self {
+        let original_storage = state
+            .accounts
+            .iter()
+            .flat_map(|(&address, account)| {
+                account.storage.iter().map(move |(&slot, &value)| ((address, slot), value))
+            })
+            .collect();
+        state.warm_accounts.extend([DEF_ADDR, DEF_CALLER]);
+        Self {
+            env: def_env().clone(),
+            state,
+            original_storage,
+            transient_storage: HashMap::default(),
+            logs: Vec::new(),
+            selfdestructs: Vec::new(),
+            next_outcome: 0,
+        }
+    }
+
+    /// Marks the account as warm, returning whether it was cold.
+    fn touch(&mut self, address: Address) -> bool {
+        self.state.warm_accounts.insert(address)
+    }
+
+    /// Returns the next scripted outcome of a sub-call or creation with the given gas limit.
+    fn next_outcome(&mut self, gas_limit: u64) -> (InterpreterResult, Option<Address>) {
+        let outcome = if self.state.outcomes.is_empty() {
+            FuzzOutcome {
+                result: InstructionResult::Stop,
+                output: Bytes::new(),
+                gas_used_percent: 0,
+                created: Address::ZERO,
+            }
+        } else {
+            let i = self.next_outcome % self.state.outcomes.len();
+            self.state.outcomes[i].clone()
+        };
+        self.next_outcome += 1;
+
+        let mut gas = Gas::new(gas_limit);
+        if outcome.result == InstructionResult::OutOfGas {
+            gas.spend_all();
+        } else {
+            let _ = gas.record_cost(gas_limit / 100 * outcome.gas_used_percent as u64);
+        }
+        let created = outcome.result.is_ok().then_some(outcome.created);
+        (InterpreterResult { result: outcome.result, output: outcome.output, gas }, created)
+    }
+
+    /// Resumes the interpreter after a call or creation action with the next outcome.
+    ///
+    /// Returns the final result if the action is a return.
+    fn insert_outcome(
+        &mut self,
+        interpreter: &mut Interpreter,
+        memory: &mut SharedMemory,
+        action: InterpreterAction,
+    ) -> Option<InterpreterResult> {
+        match action {
+            InterpreterAction::Call { inputs } => {
+                let (result, _) = self.next_outcome(inputs.gas_limit);
+                let outcome = CallOutcome::new(result, inputs.return_memory_offset.clone());
+                interpreter.insert_call_outcome(memory, outcome);
+            }
+            InterpreterAction::Create { inputs } => {
+                let (result, address) = self.next_outcome(inputs.gas_limit);
+                interpreter.insert_create_outcome(CreateOutcome::new(result, address));
+            }
+            InterpreterAction::EOFCreate { inputs } => {
+                let (result, address) = self.next_outcome(inputs.gas_limit);
+                interpreter.insert_eofcreate_outcome(CreateOutcome::new(result, address));
+            }
+            InterpreterAction::Return { result } => return Some(result),
+            InterpreterAction::None => unreachable!("no action"),
+        }
+        None
+    }
+}
+
+impl Host for FuzzHost {
+    fn env(&self) -> &Env {
+        &self.env
+    }
+
+    fn env_mut(&mut self) -> &mut Env {
+        &mut self.env
+    }
+
+    fn load_account_delegated(&mut self, address: Address) -> Option<AccountLoad> {
+        let is_cold = self.touch(address);
+        let is_empty = self
+            .state
+            .accounts
+            .get(&address)
+            .map_or(true, |account| account.balance.is_zero() && account.code.is_empty());
+        Some(AccountLoad { load: Eip7702CodeLoad::new_not_delegated((), is_cold), is_empty })
+    }
+
+    fn block_hash(&mut self, number: u64) -> Option<B256> {
+        Some(self.state.block_hashes.get(&number).copied().unwrap_or_default())
+    }
+
+    fn balance(&mut self, address: Address) -> Option<StateLoad<U256>> {
+        let is_cold = self.touch(address);
+        let balance = self.state.accounts.get(&address).map(|a| a.balance).unwrap_or_default();
+        Some(StateLoad::new(balance, is_cold))
+    }
+
+    fn code(&mut self, address: Address) -> Option<StateLoad<Bytes>> {
+        let is_cold = self.touch(address);
+        let code = self.state.accounts.get(&address).map(|a| a.code.clone()).unwrap_or_default();
+        Some(StateLoad::new(code, is_cold))
+    }
+
+    fn code_hash(&mut self, address: Address) -> Option<StateLoad<B256>> {
+        let is_cold = self.touch(address);
+        let hash = match self.state.accounts.get(&address) {
+            Some(account) if account.code.is_empty() => KECCAK_EMPTY,
+            Some(account) => keccak256(&account.code),
+            None => B256::ZERO,
+        };
+        Some(StateLoad::new(hash, is_cold))
+    }
+
+    fn sload(&mut self, address: Address, index: U256) -> Option<StateLoad<U256>> {
+        let is_cold = self.state.warm_slots.insert((address, index));
+        let value = self
+            .state
+            .accounts
+            .get(&address)
+            .and_then(|a| a.storage.get(&index).copied())
+            .unwrap_or_default();
+        Some(StateLoad::new(value, is_cold))
+    }
+
+    fn sstore(
+        &mut self,
+        address: Address,
+        index: U256,
+        value: U256,
+    ) -> Option<StateLoad<SStoreResult>> {
+        let is_cold = self.state.warm_slots.insert((address, index));
+        let original_value =
+            self.original_storage.get(&(address, index)).copied().unwrap_or_default();
+        let storage = &mut self.state.accounts.entry(address).or_default().storage;
+        let present_value = storage.insert(index, value).unwrap_or_default();
+        Some(StateLoad::new(
+            SStoreResult { original_value, present_value, new_value: value },
+            is_cold,
+        ))
+    }
+
+    fn tload(&mut self, address: Address, index: U256) -> U256 {
+        self.transient_storage.get(&(address, index)).copied().unwrap_or_default()
+    }
+
+    fn tstore(&mut self, address: Address, index: U256, value: U256) {
+        self.transient_storage.insert((address, index), value);
+    }
+
+    fn log(&mut self, log: Log) {
+        self.logs.push(log);
+    }
+
+    fn selfdestruct(
+        &mut self,
+        address: Address,
+        target: Address,
+    ) -> Option<StateLoad<SelfDestructResult>> {
+        let is_cold = self.touch(target);
+        let had_value = self.state.accounts.get(&address).is_some_and(|a| !a.balance.is_zero());
+        let target_exists = self.state.accounts.contains_key(&target);
+        let previously_destroyed = self.selfdestructs.iter().any(|&(a, _)| a == address);
+        self.selfdestructs.push((address, target));
+        Some(StateLoad::new(
+            SelfDestructResult { had_value, target_exists, previously_destroyed },
+            is_cold,
+        ))
+    }
+}
+
+/// A generated legacy program or EOF container together with the host state it runs against.
+#[derive(Clone)]
+pub struct HostTestCase {
+    pub bytecode: Bytes,
+    pub spec_id: SpecId,
+    pub state: FuzzHostState,
+}
+
+impl fmt::Debug for HostTestCase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HostTestCase")
+            .field("bytecode", &format_bytecode(&self.bytecode, self.spec_id))
+            .field("spec_id", &self.spec_id)
+            .field("state", &self.state)
+            .finish()
+    }
+}
+
+impl<'a> Arbitrary<'a> for HostTestCase {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let state = u.arbitrary()?;
+        // EOF containers reach `EOFCREATE`, legacy programs `CREATE` and `CREATE2`.
+        let (bytecode, spec_id) = if u.arbitrary()? {
+            let container = GeneratedEofContainer::arbitrary(u)?;
+            (container.bytecode, container.spec_id)
+        } else {
+            let program = GeneratedProgram::arbitrary(u)?;
+            (program.bytecode.into(), program.spec_id)
+        };
+        Ok(Self { bytecode, spec_id, state })
+    }
+}
+
+/// The observable results of a run.
+struct RunOutcome {
+    result: InterpreterResult,
+    /// The intermediate actions, with the final return.
+    actions: Vec<InterpreterAction>,
+    stack: Vec<U256>,
+    memory: Vec<u8>,
+    host: FuzzHost,
+}
+
+/// Runs the test case in the interpreter and as compiled code, resuming both after every call or
+/// creation with the same scripted outcome, and asserts that they are equivalent.
+pub fn run_host_test_case<B: Backend>(test_case: &HostTestCase, compiler: &mut EvmCompiler<B>) {
+    let HostTestCase { ref bytecode, spec_id, ref state } = *test_case;
+    compiler.inspect_stack_length(true);
+    compiler.local_stack(false);
+    compiler.validate_eof(false);
+    let f = unsafe { compiler.jit("test", bytecode, spec_id) }.unwrap();
+
+    let expected = run_interpreter(bytecode, spec_id, state);
+    let actual = run_compiled(bytecode, f, state);
+
+    let n = actual.actions.len().min(expected.actions.len());
+    for (actual, expected) in actual.actions[..n - 1].iter().zip(&expected.actions) {
+        assert_actions(actual, expected);
+    }
+
+    let (actual_result, expected_result) = (actual.result.result, expected.result.result);
+    if actual_result.is_error() || expected_result.is_error() {
+        // On halt, all the remaining gas is consumed, and the compiled code may halt earlier due
+        // to gas and stack sections, before the side effects of the rest of the section. Only
+        // compare the kind of result in that case.
+        if halts_early(actual_result) {
+            assert_eq!(
+                actual_result.is_error(),
+                expected_result.is_error(),
+                "return value mismatch: {actual_result:?} != {expected_result:?}"
+            );
+            return;
+        }
+        assert_eq!(actual_result, expected_result, "return value mismatch");
+        assert_eq!(actual.actions.len(), expected.actions.len(), "number of frames mismatch");
+        assert_host(&actual.host, &expected.host);
+        return;
+    }
+    assert_eq!(actual.actions.len(), expected.actions.len(), "number of frames mismatch");
+    assert_eq!(actual_result, expected_result, "return value mismatch");
+    assert_eq!(actual.result.output, expected.result.output, "output mismatch");
+    assert_eq!(actual.result.gas.spent(), expected.result.gas.spent(), "gas mismatch");
+    assert_eq!(actual.result.gas.refunded(), expected.result.gas.refunded(), "refund mismatch");
+    assert_eq!(actual.stack, expected.stack, "stack mismatch");
+    assert_eq!(MemDisplay(&actual.memory), MemDisplay(&expected.memory), "memory mismatch");
+    assert_host(&actual.host, &expected.host);
+}
+
+/// Returns whether the compiled code may halt with this result before the interpreter halts.
+fn halts_early(result: InstructionResult) -> bool {
+    matches!(
+        result,
+        InstructionResult::StackOverflow
+            | InstructionResult::StackUnderflow
+            | InstructionResult::OutOfGas
+            | InstructionResult::MemoryOOG
+            | InstructionResult::InvalidOperandOOG
+    )
+}
+
+fn assert_host(actual: &FuzzHost, expected: &FuzzHost) {
+    assert_eq!(actual.state.accounts, expected.state.accounts, "state mismatch");
+    assert_eq!(actual.state.warm_accounts, expected.state.warm_accounts, "warm accounts mismatch");
+    assert_eq!(actual.state.warm_slots, expected.state.warm_slots, "warm slots mismatch");
+    assert_eq!(actual.transient_storage, expected.transient_storage, "transient storage mismatch");
+    assert_eq!(actual.logs, expected.logs, "logs mismatch");
+    assert_eq!(actual.selfdestructs, expected.selfdestructs, "selfdestructs mismatch");
+}
+
+fn run_interpreter(bytecode: &[u8], spec_id: SpecId, state: &FuzzHostState) -> RunOutcome {
+    let table = spec_to_generic!(spec_id, op::make_instruction_table::<_, SPEC>());
+    run_frames(bytecode, state, |interpreter, memory, host| {
+        let action = interpreter.run(mem::replace(memory, EMPTY_SHARED_MEMORY), &table, host);
+        *memory = interpreter.take_memory();
+        action
+    })
+}
+
+fn run_compiled(bytecode: &[u8], f: EvmCompilerFn, state: &FuzzHostState) -> RunOutcome {
+    run_frames(bytecode, state, |interpreter, memory, host| unsafe {
+        f.call_with_interpreter_and_memory(interpreter, memory, host)
+    })
+}
+
+fn run_frames(
+    bytecode: &[u8],
+    state: &FuzzHostState,
+    mut run: impl FnMut(&mut Interpreter, &mut SharedMemory, &mut FuzzHost) -> InterpreterAction,
+) -> RunOutcome {
+    let mut interpreter = new_interpreter(bytecode);
+    let mut memory = interpreter.take_memory();
+    let mut host = FuzzHost::new(state.clone());
+    let mut actions = Vec::new();
+    let result = loop {
+        let action = run(&mut interpreter, &mut memory, &mut host);
+        actions.push(action.clone());
+        if let Some(result) = host.insert_outcome(&mut interpreter, &mut memory, action) {
+            break result;
+        }
+        if actions.len() >= MAX_FRAMES {
+            break InterpreterResult {
+                result: InstructionResult::CallTooDeep,
+                output: Bytes::new(),
+                gas: interpreter.gas,
+            };
+        }
+    };
+    RunOutcome {
+        result,
+        actions,
+        stack: interpreter.stack.data().clone(),
+        memory: memory.context_memory().to_vec(),
+        host,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::generator::{run_seeded, seeded_data};
+
+    fn test_case(seed: u64) -> HostTestCase {
+        HostTestCase::arbitrary(&mut Unstructured::new(&seeded_data(seed))).unwrap()
+    }
+
+    #[test]
+    fn reaches_creations() {
+        let (mut create, mut eofcreate) = (false, false);
+        for seed in 0..256 {
+            let HostTestCase { bytecode, spec_id, state } = test_case(seed);
+            for action in run_interpreter(&bytecode, spec_id, &state).actions {
+                match action {
+                    InterpreterAction::Create { .. } => create = true,
+                    InterpreterAction::EOFCreate { .. } => eofcreate = true,
+                    _ => {}
+                }
+            }
+        }
+        assert!(create, "no `CREATE` or `CREATE2` was executed");
+        assert!(eofcreate, "no `EOFCREATE` was executed");
+    }
+
+    matrix_tests!(vs_interpreter = run_vs_interpreter);
+
+    fn run_vs_interpreter<B: Backend>(compiler: &mut EvmCompiler<B>) {
+        run_seeded(compiler, run_host_test_case);
+    }
+}
@@ -0,0 +1,539 @@
+//! Structure-aware bytecode generator for differential fuzzing.
+//!
+//! Unlike arbitrary bytes, the generated programs are well-formed: every jump targets a
+//! `JUMPDEST`, instructions never underflow the stack, loops have bounded counters, and memory is
+//! accessed at realistic offsets. This lets the fuzzer reach loops, memory expansion, dynamic
+//! jumps, calls, and creations instead of halting on the first invalid instruction.
+
+use super::OTHER_ADDR;
+use crate::{format_bytecode, op_info_map, stack_io};
+use arbitrary::{Arbitrary, Result, Unstructured};
+use revm_interpreter::opcode as op;
+use revm_primitives::SpecId;
+use std::{fmt, mem};
+
+/// The maximum number of statements in a program.
+const MAX_STATEMENTS: usize = 256;
+/// The maximum nesting depth of loops and branches.
+pub(super) const MAX_DEPTH: usize = 3;
+/// The maximum number of iterations of a single loop.
+pub(super) const MAX_LOOP_ITERATIONS: u8 = 8;
+/// The stack height above which values are popped.
+pub(super) const MAX_HEIGHT: usize = 48;
+/// The maximum memory offset.
+pub(super) const MAX_OFFSET: u16 = 1024;
+/// The maximum size of a memory region.
+pub(super) const MAX_SIZE: u8 = 128;
+
+/// The specs to generate programs for. All of them support the generated control flow.
+const SPEC_IDS: &[SpecId] = &[
+    SpecId::PETERSBURG,
+    SpecId::ISTANBUL,
+    SpecId::BERLIN,
+    SpecId::LONDON,
+    SpecId::MERGE,
+    SpecId::SHANGHAI,
+    SpecId::CANCUN,
+];
+
+/// Opcodes whose inputs can be any value.
+#[rustfmt::skip]
+pub(super) const COMPUTE_OPS: &[u8] = &[
+    op::ADD, op::MUL, op::SUB, op::DIV, op::SDIV, op::MOD, op::SMOD, op::ADDMOD, op::MULMOD,
+    op::EXP, op::SIGNEXTEND,
+    op::LT, op::GT, op::SLT, op::SGT, op::EQ, op::ISZERO, op::AND, op::OR, op::XOR, op::NOT,
+    op::BYTE, op::SHL, op::SHR, op::SAR,
+    op::ADDRESS, op::BALANCE, op::ORIGIN, op::CALLER, op::CALLVALUE, op::CALLDATALOAD,
+    op::CALLDATASIZE, op::CODESIZE, op::GASPRICE, op::EXTCODESIZE, op::RETURNDATASIZE,
+    op::EXTCODEHASH, op::BLOCKHASH, op::COINBASE, op::TIMESTAMP, op::NUMBER, op::DIFFICULTY,
+    op::GASLIMIT, op::CHAINID, op::SELFBALANCE, op::BASEFEE, op::BLOBHASH, op::BLOBBASEFEE,
+    op::PC, op::MSIZE, op::GAS,
+];
+
+/// A legacy program generated by [`ProgramGenerator`].
+#[derive(Clone)]
+pub struct GeneratedProgram {
+    pub bytecode: Vec<u8>,
+    pub spec_id: SpecId,
+}
+
+impl fmt::Debug for GeneratedProgram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GeneratedProgram")
+            .field("bytecode", &format_bytecode(&self.bytecode, self.spec_id))
+            .field("spec_id", &self.spec_id)
+            .finish()
+    }
+}
+
+impl<'a> Arbitrary<'a> for GeneratedProgram {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let spec_id = *u.choose(SPEC_IDS)?;
+        let bytecode = ProgramGenerator::new(spec_id).generate(u)?;
+        Ok(Self { bytecode, spec_id })
+    }
+}
+
+/// Generates well-formed legacy programs from unstructured data.
+pub struct ProgramGenerator {
+    spec_id: SpecId,
+    code: Vec<u8>,
+    /// The statically known stack height.
+    height: usize,
+    /// Values at or below this height belong to an enclosing loop or branch, and must not be
+    /// consumed.
+    floor: usize,
+    /// The current nesting depth of loops and branches.
+    depth: usize,
+    statements: usize,
+    /// The program counter of each label's `JUMPDEST`.
+    labels: Vec<Option<usize>>,
+    /// The position of each `PUSH2` label immediate, the label, and the mask XORed into it.
+    fixups: Vec<(usize, usize, u16)>,
+}
+
+impl ProgramGenerator {
+    pub fn new(spec_id: SpecId) -> Self {
+        Self {
+            spec_id,
+            code: Vec::new(),
+            height: 0,
+            floor: 0,
+            depth: 0,
+            statements: 0,
+            labels: Vec::new(),
+            fixups: Vec::new(),
+        }
+    }
+
+    /// Generates a program, consuming as much of the data as needed.
+    pub fn generate(mut self, u: &mut Unstructured<'_>) -> Result<Vec<u8>> {
+        while !self.is_done(u) {
+            self.statement(u)?;
+        }
+        self.epilogue(u)?;
+
+        for &(pos, label, mask) in &self.fixups {
+            let pc = self.labels[label].expect("unplaced label") as u16 ^ mask;
+            self.code[pos..pos + 2].copy_from_slice(&pc.to_be_bytes());
+        }
+        Ok(self.code)
+    }
+
+    fn is_done(&self, u: &Unstructured<'_>) -> bool {
+        u.is_empty() || self.statements >= MAX_STATEMENTS
+    }
+
+    fn statement(&mut self, u: &mut Unstructured<'_>) -> Result<()> {
+        self.statements += 1;
+        let can_nest = self.depth < MAX_DEPTH;
+        match u.int_in_range(0..=12)? {
+            0..=2 => self.compute(u)?,
+            3 => self.stack_op(u)?,
+            4 | 5 => self.memory_op(u)?,
+            6 => self.storage_op(u)?,
+            7 if can_nest => self.loop_(u)?,
+            8 if can_nest => self.branch(u, false)?,
+            9 if can_nest => self.branch(u, true)?,
+            10 => self.dynamic_jump(u)?,
+            11 => self.call(u)?,
+            12 => self.create(u)?,
+            _ => self.compute(u)?,
+        }
+        if self.height > MAX_HEIGHT {
+            self.pop_to(self.floor.max(MAX_HEIGHT / 2));
+        }
+        Ok(())
+    }
+
+    fn block(&mut self, u: &mut Unstructured<'_>) -> Result<()> {
+        let n = u.int_in_range(1..=8)?;
+        for _ in 0..n {
+            if self.is_done(u) {
+                break;
+            }
+            self.statement(u)?;
+        }
+        Ok(())
+    }
+
+    /// Runs `f` in a nested scope, which must not consume the current stack values, and restores
+    /// the stack height afterwards.
+    fn nested(
+        &mut self,
+        u: &mut Unstructured<'_>,
+        f: impl FnOnce(&mut Self, &mut Unstructured<'_>) -> Result<()>,
+    ) -> Result<()> {
+        let floor = mem::replace(&mut self.floor, self.height);
+        self.depth += 1;
+        f(self, u)?;
+        self.pop_to(self.floor);
+        self.depth -= 1;
+        self.floor = floor;
+        Ok(())
+    }
+
+    fn compute(&mut self, u: &mut Unstructured<'_>) -> Result<()> {
+        let opcode = self.choose_enabled(u, COMPUTE_OPS)?;
+        let (inputs, _) = stack_io(opcode);
+        for _ in 0..inputs {
+            self.push_value(u)?;
+        }
+        self.op(opcode);
+        Ok(())
+    }
+
+    fn stack_op(&mut self, u: &mut Unstructured<'_>) -> Result<()> {
+        let available = self.height - self.floor;
+        match u.int_in_range(0..=2)? {
+            0 if self.height > 0 => {
+                let n = u.int_in_range(1..=self.height.min(16))? as u8;
+                self.op(op::DUP1 + n - 1);
+            }
+            1 if available > 1 => {
+                let n = u.int_in_range(1..=(available - 1).min(16))? as u8;
+                self.op(op::SWAP1 + n - 1);
+            }
+            2 if available > 0 => self.op(op::POP),
+            _ => self.push_value(u)?,
+        }
+        Ok(())
+    }
+
+    fn memory_op(&mut self, u: &mut Unstructured<'_>) -> Result<()> {
+        let ops: &[u8] = &[
+            op::MSTORE,
+            op::MSTORE8,
+            op::MLOAD,
+            op::KECCAK256,
+            op::CALLDATACOPY,
+            op::CODECOPY,
+            op::RETURNDATACOPY,
+            op::MCOPY,
+            op::LOG0,
+            op::LOG1,
+            op::LOG2,
+        ];
+        let opcode = self.choose_enabled(u, ops)?;
+        // Arguments are pushed in reverse order.
+        match opcode {
+            op::MSTORE | op::MSTORE8 => {
+                self.push_value(u)?;
+                self.push_offset(u)?;
+            }
+            op::MLOAD => self.push_offset(u)?,
+            op::KECCAK256 => {
+                self.push_size(u)?;
+                self.push_offset(u)?;
+            }
+            op::CALLDATACOPY | op::CODECOPY | op::MCOPY => {
+                self.push_size(u)?;
+                self.push_offset(u)?;
+                self.push_offset(u)?;
+            }
+            // Out of bounds unless the size is zero, since there is no return data.
+            op::RETURNDATACOPY => {
+                self.push(&[0]);
+                self.push(&[0]);
+                self.push_offset(u)?;
+            }
+            _ => {
+                for _ in op::LOG0..opcode {
+                    self.push_value(u)?;
+                }
+                self.push_size(u)?;
+                self.push_offset(u)?;
+            }
+        }
+        self.op(opcode);
+        Ok(())
+    }
+
+    fn storage_op(&mut self, u: &mut Unstructured<'_>) -> Result<()> {
+        let opcode = self.choose_enabled(u, &[op::SLOAD, op::SSTORE, op::TLOAD, op::TSTORE])?;
+        if matches!(opcode, op::SSTORE | op::TSTORE) {
+            self.push_value(u)?;
+        }
+        // Include the slots of the default test storage.
+        let slot = *u.choose(&[0, 1, 2, 3, 69])?;
+        self.push(&[slot]);
+        self.op(opcode);
+        Ok(())
+    }
+
+    /// A loop with a bounded counter:
+    ///
+    /// ```text
+    ///     PUSH1 n
+    /// loop:
+    ///     JUMPDEST
+    ///     <body>
+    ///     PUSH1 1 SWAP1 SUB
+    ///     DUP1 PUSH2 loop JUMPI
+    ///     POP
+    /// ```
+    fn loop_(&mut self, u: &mut Unstructured<'_>) -> Result<()> {
+        let iterations = u.int_in_range(1..=MAX_LOOP_ITERATIONS)?;
+        self.push(&[iterations]);
+        let start = self.new_label();
+        self.place_label(start);
+        self.nested(u, Self::block)?;
+        self.push(&[1]);
+        self.op(op::SWAP1);
+        self.op(op::SUB);
+        self.op(op::DUP1);
+        self.push_label(start, None);
+        self.op(op::JUMPI);
+        self.op(op::POP);
+        Ok(())
+    }
+
+    /// A conditional forward jump over a block, with a static or computed target.
+    fn branch(&mut self, u: &mut Unstructured<'_>, dynamic: bool) -> Result<()> {
+        let end = self.new_label();
+        self.push_value(u)?;
+        if u.arbitrary()? {
+            self.op(op::ISZERO);
+        }
+        let mask = if dynamic { Some(u.arbitrary()?) } else { None };
+        self.push_label(end, mask);
+        self.op(op::JUMPI);
+        self.nested(u, Self::block)?;
+        self.place_label(end);
+        Ok(())
+    }
+
+    /// An unconditional jump with a computed target over some dead code.
+    fn dynamic_jump(&mut self, u: &mut Unstructured<'_>) -> Result<()> {
+        let target = self.new_label();
+        self.push_label(target, Some(u.arbitrary()?));
+        self.op(op::JUMP);
+        for _ in 0..u.int_in_range(0..=2)? {
+            self.code.push(*u.choose(&[op::INVALID, op::ADD, op::STOP])?);
+        }
+        self.place_label(target);
+        Ok(())
+    }
+
+    fn call(&mut self, u: &mut Unstructured<'_>) -> Result<()> {
+        let opcode = self.choose_enabled(u, &[op::CALL, op::STATICCALL, op::DELEGATECALL])?;
+        self.push_size(u)?;
+        self.push_offset(u)?;
+        self.push_size(u)?;
+        self.push_offset(u)?;
+        if opcode == op::CALL {
+            self.push(&[0]);
+        }
+        if u.arbitrary()? {
+            self.push(&OTHER_ADDR[..]);
+        } else {
+            self.push_value(u)?;
+        }
+        let gas = u.arbitrary::<u16>()?;
+        self.push(&gas.to_be_bytes());
+        self.op(opcode);
+        Ok(())
+    }
+
+    /// Creates a contract with a region of memory as the init code, without value.
+    fn create(&mut self, u: &mut Unstructured<'_>) -> Result<()> {
+        let opcode = self.choose_enabled(u, &[op::CREATE, op::CREATE2])?;
+        if opcode == op::CREATE2 {
+            self.push_value(u)?;
+        }
+        self.push_size(u)?;
+        self.push_offset(u)?;
+        self.push(&[0]);
+        self.op(opcode);
+        Ok(())
+    }
+
+    fn epilogue(&mut self, u: &mut Unstructured<'_>) -> Result<()> {
+        match u.int_in_range(0..=3)? {
+            0 => {}
+            1 => self.op(op::STOP),
+            kind => {
+                if self.height > 0 {
+                    self.push(&[0]);
+                    self.op(op::MSTORE);
+                }
+                self.push(&[32]);
+                self.push(&[0]);
+                self.op(if kind == 2 { op::RETURN } else { op::REVERT });
+            }
+        }
+        Ok(())
+    }
+
+    fn choose_enabled(&self, u: &mut Unstructured<'_>, ops: &[u8]) -> Result<u8> {
+        let info = op_info_map(self.spec_id);
+        let mut enabled = ops.iter().copied().filter(|&op| {
+            let info = info[op as usize];
+            !info.is_unknown() && !info.is_disabled()
+        });
+        let n = enabled.clone().count();
+        let i = u.choose_index(n)?;
+        Ok(enabled.nth(i).unwrap())
+    }
+
+    /// Pushes a random value, biased towards edge cases.
+    fn push_value(&mut self, u: &mut Unstructured<'_>) -> Result<()> {
+        match u.int_in_range(0..=4)? {
+            0 if self.spec_id.is_enabled_in(SpecId::SHANGHAI) => self.op(op::PUSH0),
+            0 | 1 => self.push(&[u.int_in_range(0..=32)?]),
+            2 => {
+                let mut value = [0; 32];
+                match u.int_in_range(0..=3)? {
+                    0 => value = [0xff; 32],
+                    1 => value[0] = 0x80,
+                    2 => {
+                        value = [0xff; 32];
+                        value[0] = 0x7f;
+                    }
+                    _ => value[u.int_in_range(0..=31)?] = 1,
+                }
+                self.push(&value);
+            }
+            _ => {
+                let len = u.int_in_range(1..=32)?;
+                let value = u.arbitrary::<[u8; 32]>()?;
+                self.push(&value[..len]);
+            }
+        }
+        Ok(())
+    }
+
+    /// Pushes a memory offset, often word-aligned.
+    fn push_offset(&mut self, u: &mut Unstructured<'_>) -> Result<()> {
+        let mut offset = u.int_in_range(0..=MAX_OFFSET)?;
+        if u.arbitrary()? {
+            offset &= !31;
+        }
+        self.push(&offset.to_be_bytes());
+        Ok(())
+    }
+
+    /// Pushes a memory region size.
+    fn push_size(&mut self, u: &mut Unstructured<'_>) -> Result<()> {
+        let size = u.int_in_range(0..=MAX_SIZE)?;
+        self.push(&[size]);
+        Ok(())
+    }
+
+    fn push(&mut self, value: &[u8]) {
+        debug_assert!(!value.is_empty() && value.len() <= 32);
+        self.code.push(op::PUSH1 + value.len() as u8 - 1);
+        self.code.extend_from_slice(value);
+        self.height += 1;
+    }
+
+    fn push_label(&mut self, label: usize, mask: Option<u16>) {
+        self.fixups.push((self.code.len() + 1, label, mask.unwrap_or(0)));
+        self.push(&[0, 0]);
+        if let Some(mask) = mask {
+            self.push(&mask.to_be_bytes());
+            self.op(op::XOR);
+        }
+    }
+
+    fn new_label(&mut self) -> usize {
+        self.labels.push(None);
+        self.labels.len() - 1
+    }
+
+    fn place_label(&mut self, label: usize) {
+        self.labels[label] = Some(self.code.len());
+        self.code.push(op::JUMPDEST);
+    }
+
+    fn pop_to(&mut self, height: usize) {
+        while self.height > height {
+            self.op(op::POP);
+        }
+    }
+
+    fn op(&mut self, opcode: u8) {
+        let (inputs, outputs) = stack_io(opcode);
+        debug_assert!(self.height >= inputs as usize, "stack underflow at {opcode:#x}");
+        self.code.push(opcode);
+        self.height = self.height - inputs as usize + outputs as usize;
+    }
+}
+
+/// Returns deterministic pseudo-random data to generate programs from in tests.
+#[cfg(test)]
+pub(super) fn seeded_data(seed: u64) -> Vec<u8> {
+    let mut x = seed.wrapping_mul(0x9e3779b97f4a7c15) | 1;
+    (0..4096)
+        .map(|_| {
+            x ^= x << 13;
+            x ^= x >> 7;
+            x ^= x << 17;
+            x as u8
+        })
+        .collect()
+}
+
+/// Runs `f` on inputs generated from a fixed set of seeds, clearing the compiler after each one.
+#[cfg(test)]
+pub(super) fn run_seeded<B: crate::Backend, T: for<'a> Arbitrary<'a>>(
+    compiler: &mut crate::EvmCompiler<B>,
+    mut f: impl FnMut(&T, &mut crate::EvmCompiler<B>),
+) {
+    for seed in 0..16 {
+        let data = seeded_data(seed);
+        let input = T::arbitrary(&mut Unstructured::new(&data)).unwrap();
+        f(&input, compiler);
+        unsafe { compiler.clear() }.unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bytecode::InstFlags,
+        tests::{run_test_case, run_test_case_with_config, TestCase, TestConfig},
+        Backend, Bytecode, EvmCompiler,
+    };
+
+    fn program(seed: u64) -> GeneratedProgram {
+        GeneratedProgram::arbitrary(&mut Unstructured::new(&seeded_data(seed))).unwrap()
+    }
+
+    #[test]
+    fn well_formed() {
+        for seed in 0..64 {
+            let GeneratedProgram { bytecode, spec_id } = program(seed);
+            let mut analyzed = Bytecode::new(&bytecode, None, spec_id);
+            analyzed.analyze().unwrap();
+            for (_, inst) in analyzed.iter_insts() {
+                let flags = InstFlags::INVALID_JUMP | InstFlags::UNKNOWN | InstFlags::DISABLED;
+                assert!(!inst.flags.intersects(flags), "{seed}: {inst:?}\n{analyzed}");
+            }
+        }
+    }
+
+    matrix_tests!(vs_interpreter = run_vs_interpreter);
+
+    fn run_vs_interpreter<B: Backend>(compiler: &mut EvmCompiler<B>) {
+        run_seeded(compiler, |program: &GeneratedProgram, compiler| {
+            run_test_case(
+                &TestCase::what_interpreter_says(&program.bytecode, program.spec_id),
+                compiler,
+            );
+        });
+    }
+
+    matrix_tests!(configs = run_configs);
+
+    fn run_configs<B: Backend>(compiler: &mut EvmCompiler<B>) {
+        run_seeded(compiler, |(config, program): &(TestConfig, GeneratedProgram), compiler| {
+            run_test_case_with_config(
+                &TestCase::what_interpreter_says(&program.bytecode, program.spec_id),
+                config,
+                compiler,
+            );
+        });
+    }
+}
@@ -0,0 +1,100 @@
+use super::{eof_body, new_interpreter, TestHost, DEF_SPEC};
+use crate::{Backend, EvmCompiler, EvmCompilerFn, ExecutionLimits, LimitExceeded};
+use revm_interpreter::{opcode as op, InstructionResult, InterpreterAction};
+use revm_primitives::{Bytes, SpecId};
+
+matrix_tests!(budget = |compiler| run_budget(compiler, LOOP, DEF_SPEC));
+matrix_tests!(budget_eof = |compiler| run_budget(compiler, &eof_loop(), SpecId::OSAKA));
+matrix_tests!(budget_dynamic = |compiler| run_budget(compiler, DYNAMIC_LOOP, DEF_SPEC));
+matrix_tests!(cancelled = |compiler| run_cancelled(compiler, LOOP, DEF_SPEC));
+matrix_tests!(cancelled_eof = |compiler| run_cancelled(compiler, &eof_loop(), SpecId::OSAKA));
+matrix_tests!(disabled = run_disabled);
+
+#[rustfmt::skip]
+const LOOP: &[u8] = &[
+    op::JUMPDEST,
+    op::PUSH0,
+    op::JUMP,
+];
+
+#[rustfmt::skip]
+const DYNAMIC_LOOP: &[u8] = &[
+    op::JUMPDEST,
+    op::PUSH0,
+    op::MLOAD,
+    op::JUMP,
+];
+
+#[rustfmt::skip]
+const EOF_LOOP: &[u8] = &[
+    op::RJUMP, 0xff, 0xfd,
+];
+
+/// `tests::eof` adds a subcontainer, which this code does not access.
+fn eof_loop() -> Bytes {
+    eof_body(&[EOF_LOOP], vec![]).into_eof().raw
+}
+
+const BUDGET: u64 = 1_000;
+
+fn run_budget<B: Backend>(compiler: &mut EvmCompiler<B>, code: &[u8], spec_id: SpecId) {
+    let f = jit(compiler, code, spec_id);
+    let limits = ExecutionLimits::with_instruction_budget(BUDGET);
+    assert_eq!(run(f, code, &limits), Err(LimitExceeded::InstructionBudget));
+    assert_eq!(limits.exceeded(), Some(LimitExceeded::InstructionBudget));
+    assert!(limits.instruction_budget() < BUDGET);
+
+    // Resetting the limits allows running again.
+    limits.reset(BUDGET);
+    assert_eq!(limits.exceeded(), None);
+    assert_eq!(run(f, code, &limits), Err(LimitExceeded::InstructionBudget));
+    assert_eq!(limits.exceeded(), Some(LimitExceeded::InstructionBudget));
+}
+
+fn run_cancelled<B: Backend>(compiler: &mut EvmCompiler<B>, code: &[u8], spec_id: SpecId) {
+    let f = jit(compiler, code, spec_id);
+    let limits = ExecutionLimits::new();
+    let result = std::thread::scope(|s| {
+        s.spawn(|| {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            limits.cancel();
+        });
+        run(f, code, &limits)
+    });
+    assert_eq!(result, Err(LimitExceeded::Cancelled));
+    assert!(limits.is_cancelled());
+    assert_eq!(limits.exceeded(), Some(LimitExceeded::Cancelled));
+}
+
+fn run_disabled<B: Backend>(compiler: &mut EvmCompiler<B>) {
+    // Without the option, the limits are ignored.
+    let code = &[op::PUSH1, 0x03, op::JUMP, op::JUMPDEST, op::STOP];
+    let f = unsafe { compiler.jit("limits", &code[..], DEF_SPEC) }.unwrap();
+    let limits = ExecutionLimits::with_instruction_budget(0);
+    limits.cancel();
+    assert_eq!(run(f, code, &limits), Ok(InstructionResult::Stop));
+    assert_eq!(limits.exceeded(), None);
+}
+
+fn jit<B: Backend>(compiler: &mut EvmCompiler<B>, code: &[u8], spec_id: SpecId) -> EvmCompilerFn {
+    // Done manually in `fn eof`.
+    compiler.validate_eof(false);
+    compiler.gas_metering(false);
+    compiler.execution_limits(true);
+    unsafe { compiler.jit("limits", code, spec_id) }.unwrap()
+}
+
+fn run(
+    f: EvmCompilerFn,
+    code: &[u8],
+    limits: &ExecutionLimits,
+) -> Result<InstructionResult, LimitExceeded> {
+    let mut interpreter = new_interpreter(code);
+    let mut memory = interpreter.take_memory();
+    let mut host = TestHost::new();
+    let action = unsafe { f.call_with_limits(&mut interpreter, &mut memory, &mut host, limits) }?;
+    let InterpreterAction::Return { result } = action else {
+        panic!("unexpected action: {action:?}");
+    };
+    Ok(result.result)
+}
@@ -1,7 +1,7 @@
 use super::with_evm_context;
 use crate::{Backend, EvmCompiler};
-use revm_interpreter::InstructionResult;
-use revm_primitives::SpecId;
+use revm_interpreter::{opcode as op, InstructionResult};
+use revm_primitives::{SpecId, U256};
 
 matrix_tests!(translate_then_compile);
 
@@ -22,3 +22,240 @@ fn translate_then_compile<B: Backend>(compiler: &mut EvmCompiler<B>) {
         assert_eq!(r, InstructionResult::Stop);
     });
 }
+
+matrix_tests!(debug_info = debug_info);
+
+fn debug_info<B: Backend>(compiler: &mut EvmCompiler<B>) {
+    let bytecode: &[u8] = &[op::PUSH1, 1, op::PUSH1, 2, op::ADD, op::POP, op::STOP];
+    let dump_dir = std::env::temp_dir().join(format!(
+        "revmc-debug-info-{}-{:?}",
+        std::process::id(),
+        compiler.opt_level()
+    ));
+    compiler.set_dump_to(Some(dump_dir.clone()));
+    compiler.debug_info(true);
+    let f = unsafe { compiler.jit("test", bytecode, SpecId::CANCUN) }.unwrap();
+    with_evm_context(bytecode, |ecx, stack, stack_len| {
+        let r = unsafe { f.call(Some(stack), Some(stack_len), ecx) };
+        assert_eq!(r, InstructionResult::Stop);
+    });
+
+    // The line of each instruction's debug location is its line in `bytecode.txt`.
+    let listing = std::fs::read_to_string(dump_dir.join("bytecode.txt")).unwrap();
+    let ir = std::fs::read_to_string(dump_dir.join("unopt.ll")).unwrap();
+    assert!(ir.contains("!DIFile(filename: \"bytecode.txt\""), "{ir}");
+    assert!(ir.contains("!DISubprogram(name: \"test\""), "{ir}");
+    for (inst, opcode) in [(0, "PUSH1"), (2, "ADD")] {
+        let line = inst + 3;
+        assert!(listing.lines().nth(line - 1).unwrap().contains(opcode), "{listing}");
+        assert!(ir.contains(&format!("!DILocation(line: {line}, ")), "{ir}");
+    }
+    std::fs::remove_dir_all(&dump_dir).unwrap();
+}
+
+matrix_tests!(cache_key = cache_key);
+
+fn cache_key<B: Backend>(compiler: &mut EvmCompiler<B>) {
+    let code: &[u8] = &[op::PUSH1, 1, op::PUSH1, 2, op::ADD, op::STOP];
+    let key = compiler.cache_key(code, SpecId::CANCUN);
+    assert_eq!(compiler.cache_key(code, SpecId::CANCUN), key);
+    assert_ne!(compiler.cache_key(&code[..4], SpecId::CANCUN), key);
+    assert_ne!(compiler.cache_key(code, SpecId::SHANGHAI), key);
+    compiler.gas_metering(false);
+    assert_ne!(compiler.cache_key(code, SpecId::CANCUN), key);
+    compiler.gas_metering(true);
+    assert_eq!(compiler.cache_key(code, SpecId::CANCUN), key);
+
+    let empty_module_key = compiler.module_cache_key();
+    let id = compiler.translate("test", code, SpecId::CANCUN).unwrap();
+    let module_key = compiler.module_cache_key();
+    assert_ne!(module_key, empty_module_key);
+    let opt_level = match compiler.opt_level() {
+        crate::OptimizationLevel::None => crate::OptimizationLevel::Aggressive,
+        _ => crate::OptimizationLevel::None,
+    };
+    compiler.set_function_opt_level(id, opt_level);
+    assert_ne!(compiler.module_cache_key(), module_key);
+}
+
+matrix_tests!(timings = timings);
+
+fn timings<B: Backend>(compiler: &mut EvmCompiler<B>) {
+    let bytecode: &[u8] = &[op::PUSH1, 1, op::PUSH1, 2, op::ADD, op::STOP];
+    compiler.reset_timings();
+    let id = compiler.translate("test", bytecode, SpecId::CANCUN).unwrap();
+    let timings = compiler.timings();
+    assert!(!timings.translate.is_zero());
+    assert!(timings.verify.is_zero() && timings.optimize.is_zero());
+
+    let _ = unsafe { compiler.jit_function(id) }.unwrap();
+    let timings = compiler.timings();
+    assert!(!timings.verify.is_zero());
+    assert_eq!(
+        timings.total(),
+        timings.parse + timings.translate + timings.verify + timings.optimize + timings.codegen
+    );
+}
+
+matrix_tests!(function_size = function_size);
+
+fn function_size<B: Backend>(compiler: &mut EvmCompiler<B>) {
+    let small: &[u8] = &[op::STOP];
+    let large: &[u8] = &[op::PUSH1, 1, op::PUSH1, 2, op::ADD, op::PUSH0, op::MSTORE, op::STOP];
+    let small_id = compiler.translate("small", small, SpecId::CANCUN).unwrap();
+    let large_id = compiler.translate("large", large, SpecId::CANCUN).unwrap();
+    let small_fn = unsafe { compiler.jit_function(small_id) }.unwrap();
+    let large_fn = unsafe { compiler.jit_function(large_id) }.unwrap();
+
+    let small_size = compiler.function_size(small_id).unwrap().expect("missing size");
+    let large_size = compiler.function_size(large_id).unwrap().expect("missing size");
+    assert!(small_size > 0 && small_size < large_size, "{small_size} >= {large_size}");
+
+    // The functions do not overlap in memory.
+    let small_addr = small_fn.into_inner() as usize;
+    let large_addr = large_fn.into_inner() as usize;
+    assert!(
+        small_addr + small_size <= large_addr || large_addr + large_size <= small_addr,
+        "{small_addr:#x}+{small_size:#x} overlaps {large_addr:#x}+{large_size:#x}"
+    );
+}
+
+#[cfg(feature = "llvm")]
+#[test]
+fn cache_key_pass_pipeline() {
+    crate::llvm::with_llvm_context(|cx| {
+        let code: &[u8] = &[op::PUSH1, 1, op::PUSH1, 2, op::ADD, op::STOP];
+        let opt_level = crate::OptimizationLevel::Aggressive;
+        let new_backend = || crate::EvmLlvmBackend::new(cx, false, opt_level).unwrap();
+        let key = EvmCompiler::new(new_backend()).cache_key(code, SpecId::CANCUN);
+        assert_eq!(EvmCompiler::new(new_backend()).cache_key(code, SpecId::CANCUN), key);
+        let mut backend = new_backend();
+        backend.set_pass_pipeline(Some("default<O1>".into()));
+        assert_ne!(EvmCompiler::new(backend).cache_key(code, SpecId::CANCUN), key);
+    });
+}
+
+#[cfg(feature = "llvm")]
+#[test]
+fn function_opt_levels() {
+    crate::tests::with_llvm_backend_jit(crate::OptimizationLevel::Aggressive, |compiler| {
+        // `BYTE` with a dynamic index calls an IR builtin, which is shared by both functions and
+        // optimized once for each level.
+        #[rustfmt::skip]
+        let bytecode: &[u8] = &[
+            op::PUSH1, 0xab,
+            op::PUSH1, 33, op::CALLDATASIZE, op::SUB,
+            op::BYTE,
+            op::STOP,
+        ];
+        let optimized = compiler.translate("optimized", bytecode, SpecId::CANCUN).unwrap();
+        let unoptimized = compiler.translate("unoptimized", bytecode, SpecId::CANCUN).unwrap();
+        compiler.set_function_opt_level(unoptimized, crate::OptimizationLevel::None);
+        for id in [optimized, unoptimized] {
+            let f = unsafe { compiler.jit_function(id) }.unwrap();
+            with_evm_context(bytecode, |ecx, stack, stack_len| {
+                let r = unsafe { f.call(Some(stack), Some(stack_len), ecx) };
+                assert_eq!(r, InstructionResult::Stop);
+                assert_eq!(*stack_len, 1);
+                assert_eq!(stack.as_slice()[0].to_u256(), U256::from(0xab));
+            });
+        }
+    });
+}
+
+#[cfg(feature = "llvm")]
+#[test]
+fn bitcode_roundtrip() {
+    crate::tests::with_llvm_backend_jit(crate::OptimizationLevel::Aggressive, |compiler| {
+        let bytecode: &[u8] = &[op::PUSH1, 1, op::PUSH1, 2, op::ADD, op::STOP];
+        compiler.inspect_stack_length(true);
+        compiler.translate("test", bytecode, SpecId::CANCUN).unwrap();
+        let other: &[u8] = &[op::PUSH1, 1, op::STOP];
+        let other_id = compiler.translate("other", other, SpecId::CANCUN).unwrap();
+        let module_key = compiler.module_cache_key();
+        let mut module_bitcode = Vec::new();
+        compiler.write_module_bitcode(&mut module_bitcode).unwrap();
+        let function_key = compiler.function_cache_key(other_id).unwrap();
+        assert_eq!(function_key, compiler.cache_key(other, SpecId::CANCUN));
+        let mut function_bitcode = Vec::new();
+        compiler.write_function_bitcode(other_id, &mut function_bitcode).unwrap();
+        assert!(function_bitcode.len() < module_bitcode.len());
+
+        unsafe { compiler.clear() }.unwrap();
+        let err = unsafe { compiler.jit_bitcode("test", &module_bitcode, function_key) };
+        assert!(err.unwrap_err().to_string().contains("cache key mismatch"));
+        let err = unsafe { compiler.jit_bitcode("test", &function_bitcode, function_key) };
+        assert!(err.is_err(), "`test` must not be exported with `other`");
+
+        let f = unsafe { compiler.jit_bitcode("test", &module_bitcode, module_key) }.unwrap();
+        with_evm_context(bytecode, |ecx, stack, stack_len| {
+            let r = unsafe { f.call(Some(stack), Some(stack_len), ecx) };
+            assert_eq!(r, InstructionResult::Stop);
+            assert_eq!(*stack_len, 1);
+            assert_eq!(stack.as_slice()[0].to_u256(), U256::from(3));
+        });
+
+        unsafe { compiler.clear() }.unwrap();
+        let f = unsafe { compiler.jit_bitcode("other", &function_bitcode, function_key) }.unwrap();
+        with_evm_context(other, |ecx, stack, stack_len| {
+            let r = unsafe { f.call(Some(stack), Some(stack_len), ecx) };
+            assert_eq!(r, InstructionResult::Stop);
+            assert_eq!(*stack_len, 1);
+            assert_eq!(stack.as_slice()[0].to_u256(), U256::from(1));
+        });
+    });
+}
+
+#[cfg(feature = "llvm")]
+#[test]
+fn gdb_jit_registration() {
+    #[repr(C)]
+    struct JitCodeEntry {
+        next_entry: *const Self,
+        prev_entry: *const Self,
+        symfile_addr: *const u8,
+        symfile_size: u64,
+    }
+
+    #[repr(C)]
+    struct JitDescriptor {
+        version: u32,
+        action_flag: u32,
+        relevant_entry: *const JitCodeEntry,
+        first_entry: *const JitCodeEntry,
+    }
+
+    // Defined by LLVM's GDB registration listener.
+    extern "C" {
+        static __jit_debug_descriptor: JitDescriptor;
+    }
+
+    fn is_registered(name: &str) -> bool {
+        let mut entry = unsafe { std::ptr::addr_of!(__jit_debug_descriptor).read().first_entry };
+        while let Some(e) = unsafe { entry.as_ref() } {
+            let object =
+                unsafe { std::slice::from_raw_parts(e.symfile_addr, e.symfile_size as usize) };
+            if object.windows(name.len()).any(|w| w == name.as_bytes()) {
+                return true;
+            }
+            entry = e.next_entry;
+        }
+        false
+    }
+
+    crate::tests::with_llvm_backend_jit(crate::OptimizationLevel::None, |compiler| {
+        let bytecode: &[u8] = &[op::PUSH1, 1, op::PUSH1, 2, op::ADD, op::STOP];
+        let name = "gdb_jit_registration_test";
+        compiler.gdb_jit_registration(true);
+        let f = unsafe { compiler.jit(name, bytecode, SpecId::CANCUN) }.unwrap();
+        assert!(is_registered(name));
+        with_evm_context(bytecode, |ecx, stack, stack_len| {
+            let r = unsafe { f.call(Some(stack), Some(stack_len), ecx) };
+            assert_eq!(r, InstructionResult::Stop);
+        });
+
+        // The code is only unregistered when the module is cleared.
+        unsafe { compiler.clear() }.unwrap();
+        assert!(!is_registered(name));
+    });
+}
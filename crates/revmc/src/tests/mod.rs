@@ -21,11 +21,24 @@ mod macros;
 mod meta;
 
 mod fibonacci;
+mod limits;
 mod resume;
 
 mod runner;
 pub use runner::*;
 
+mod generator;
+pub use generator::*;
+
+mod eof_generator;
+pub use eof_generator::*;
+
+mod fuzz_host;
+pub use fuzz_host::*;
+
+#[cfg(test)]
+mod vm_tests;
+
 const I256_MAX: U256 = U256::from_limbs([
     0xFFFFFFFFFFFFFFFF,
     0xFFFFFFFFFFFFFFFF,
@@ -1,7 +1,7 @@
-use super::{eof, eof_sections_unchecked, with_evm_context, DEF_SPEC};
-use crate::{Backend, EvmCompiler, TEST_SUSPEND};
-use revm_interpreter::{opcode as op, InstructionResult};
-use revm_primitives::{SpecId, U256};
+use super::{eof, eof_sections_unchecked, new_interpreter, with_evm_context, TestHost, DEF_SPEC};
+use crate::{Backend, EvmCompiler, SuspendedFrame, SuspendedFrameError, TEST_SUSPEND};
+use revm_interpreter::{opcode as op, InstructionResult, InterpreterAction};
+use revm_primitives::{SpecId, B256, U256};
 
 matrix_tests!(legacy = |compiler| run(compiler, TEST, DEF_SPEC));
 matrix_tests!(eof_one_section = |compiler| run(compiler, &eof(TEST), SpecId::OSAKA));
@@ -12,6 +12,8 @@ matrix_tests!(
         SpecId::OSAKA
     )
 );
+matrix_tests!(serialized = |compiler| run_serialized(compiler, TEST, DEF_SPEC));
+matrix_tests!(serialized_eof = |compiler| run_serialized(compiler, &eof(TEST), SpecId::OSAKA));
 
 #[rustfmt::skip]
 const TEST: &[u8] = &[
@@ -131,3 +133,68 @@ fn run<B: Backend>(compiler: &mut EvmCompiler<B>, code: &[u8], spec_id: SpecId)
         assert_eq!(ecx.resume_at, resume_3);
     });
 }
+
+/// Suspends, serializes, and resumes the function in a new interpreter at every suspension point.
+fn run_serialized<B: Backend>(compiler: &mut EvmCompiler<B>, code: &[u8], spec_id: SpecId) {
+    // Done manually in `fn eof` and friends.
+    compiler.validate_eof(false);
+    compiler.stable_resume_points(true);
+    let f = unsafe { compiler.jit("resume", code, spec_id) }.unwrap();
+    let cache_key = compiler.cache_key(code, spec_id);
+
+    let mut host = TestHost::new();
+    let mut interpreter = new_interpreter(code);
+    let suspensions: [&[u64]; 3] = [&[0x42], &[0x42, 0x69], &[0x42 + 0x69]];
+    for (i, expected_stack) in suspensions.into_iter().enumerate() {
+        let action = unsafe { f.call_with_interpreter(&mut interpreter, &mut host) };
+        assert!(matches!(action, InterpreterAction::Return { .. }), "{action:?}");
+        assert_eq!(interpreter.instruction_result, InstructionResult::CallOrCreate);
+        let expected_stack = expected_stack.iter().map(|&x| U256::from(x)).collect::<Vec<_>>();
+        assert_eq!(*interpreter.stack.data(), expected_stack);
+
+        let memory = &interpreter.shared_memory;
+        let frame =
+            SuspendedFrame::from_interpreter(&interpreter, memory, spec_id, cache_key).unwrap();
+        assert_eq!(frame.resume_at, i + 1);
+        let decoded = SuspendedFrame::decode(&frame.encode()).unwrap();
+        assert_eq!(decoded, frame);
+
+        // Code compiled with a different configuration cannot resume the frame.
+        let contract = new_interpreter(code).contract;
+        let other_key = B256::repeat_byte(0xff);
+        assert_eq!(
+            decoded.clone().into_interpreter(contract.clone(), other_key).err(),
+            Some(SuspendedFrameError::CacheKeyMismatch { expected: cache_key, found: other_key })
+        );
+        interpreter = decoded.into_interpreter(contract, cache_key).unwrap();
+    }
+
+    let action = unsafe { f.call_with_interpreter(&mut interpreter, &mut host) };
+    assert!(matches!(action, InterpreterAction::Return { .. }), "{action:?}");
+    assert_eq!(interpreter.instruction_result, InstructionResult::Stop);
+    assert_eq!(*interpreter.stack.data(), [U256::from(0x42 + 0x69)]);
+}
+
+/// Block addresses are only valid in the current process, so they cannot be suspended.
+#[cfg(feature = "llvm")]
+#[test]
+fn serialized_unstable() {
+    crate::tests::with_llvm_backend_jit(crate::OptimizationLevel::None, |compiler| {
+        compiler.stable_resume_points(false);
+        let f = unsafe { compiler.jit("resume", TEST, DEF_SPEC) }.unwrap();
+        let cache_key = compiler.cache_key(TEST, DEF_SPEC);
+
+        let mut host = TestHost::new();
+        let mut interpreter = new_interpreter(TEST);
+        let action = unsafe { f.call_with_interpreter(&mut interpreter, &mut host) };
+        assert!(matches!(action, InterpreterAction::Return { .. }), "{action:?}");
+        assert_eq!(interpreter.instruction_result, InstructionResult::CallOrCreate);
+
+        let resume_at = interpreter.instruction_pointer as usize;
+        let memory = &interpreter.shared_memory;
+        assert_eq!(
+            SuspendedFrame::from_interpreter(&interpreter, memory, DEF_SPEC, cache_key).err(),
+            Some(SuspendedFrameError::UnstableResumePoint(resume_at))
+        );
+    });
+}
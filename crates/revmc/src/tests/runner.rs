@@ -319,6 +319,16 @@ pub fn with_evm_context<F: FnOnce(&mut EvmContext<'_>, &mut EvmStack, &mut usize
     bytecode: &[u8],
     f: F,
 ) -> R {
+    let mut interpreter = new_interpreter(bytecode);
+    let mut host = TestHost::new();
+
+    let (mut ecx, stack, stack_len) =
+        EvmContext::from_interpreter_with_stack(&mut interpreter, &mut host);
+    f(&mut ecx, stack, stack_len)
+}
+
+/// Creates a new interpreter for the given bytecode with the default environment.
+pub fn new_interpreter(bytecode: &[u8]) -> revm_interpreter::Interpreter {
     let contract = Contract {
         input: Bytes::from_static(DEF_CD),
         bytecode: revm_interpreter::analysis::to_analysed(revm_primitives::Bytecode::new_raw(
@@ -333,12 +343,7 @@ pub fn with_evm_context<F: FnOnce(&mut EvmContext<'_>, &mut EvmStack, &mut usize
 
     let mut interpreter = revm_interpreter::Interpreter::new(contract, DEF_GAS_LIMIT, false);
     interpreter.return_data_buffer = Bytes::from_static(DEF_RD);
-
-    let mut host = TestHost::new();
-
-    let (mut ecx, stack, stack_len) =
-        EvmContext::from_interpreter_with_stack(&mut interpreter, &mut host);
-    f(&mut ecx, stack, stack_len)
+    interpreter
 }
 
 #[cfg(feature = "llvm")]
@@ -365,17 +370,96 @@ pub fn set_test_dump<B: Backend>(compiler: &mut EvmCompiler<B>, module_path: &st
     compiler.set_dump_to(Some(dump_path));
 }
 
+/// The compiler configuration to run a test case with.
+#[derive(Clone, Copy, Debug)]
+pub struct TestConfig {
+    /// The optimization level, or `None` to keep the compiler's.
+    pub opt_level: Option<OptimizationLevel>,
+    pub gas_metering: bool,
+    pub local_stack: bool,
+    pub inspect_stack_length: bool,
+    pub debug_assertions: bool,
+    pub frame_pointers: bool,
+}
+
+impl Default for TestConfig {
+    fn default() -> Self {
+        Self {
+            opt_level: None,
+            gas_metering: true,
+            local_stack: false,
+            inspect_stack_length: true,
+            debug_assertions: cfg!(debug_assertions),
+            frame_pointers: cfg!(debug_assertions),
+        }
+    }
+}
+
+impl<'a> arbitrary::Arbitrary<'a> for TestConfig {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let opt_level = *u.choose(&[
+            OptimizationLevel::None,
+            OptimizationLevel::Less,
+            OptimizationLevel::Default,
+            OptimizationLevel::Aggressive,
+        ])?;
+        Ok(Self {
+            opt_level: Some(opt_level),
+            gas_metering: u.arbitrary()?,
+            local_stack: u.arbitrary()?,
+            inspect_stack_length: u.arbitrary()?,
+            debug_assertions: u.arbitrary()?,
+            frame_pointers: u.arbitrary()?,
+        })
+    }
+}
+
+impl TestConfig {
+    /// Applies the configuration to the compiler.
+    pub fn apply<B: Backend>(&self, compiler: &mut EvmCompiler<B>) {
+        if let Some(opt_level) = self.opt_level {
+            compiler.set_opt_level(opt_level);
+        }
+        compiler.gas_metering(self.gas_metering);
+        compiler.local_stack(self.local_stack);
+        compiler.inspect_stack_length(self.inspect_stack_length);
+        compiler.debug_assertions(self.debug_assertions);
+        compiler.frame_pointers(self.frame_pointers);
+    }
+
+    /// Returns `true` if the final stack can be read after the call.
+    fn is_stack_observable(&self) -> bool {
+        self.inspect_stack_length && !self.local_stack
+    }
+}
+
+/// Runs a test case with the compiler's current configuration, enabling `inspect_stack_length` to
+/// compare the stack.
 pub fn run_test_case<B: Backend>(test_case: &TestCase<'_>, compiler: &mut EvmCompiler<B>) {
-    let TestCase { bytecode, spec_id, .. } = *test_case;
     compiler.inspect_stack_length(true);
+    let config = compiler.test_config();
+    run_test_case_with_config(test_case, &config, compiler);
+}
+
+/// Runs a test case with the given compiler configuration.
+///
+/// The results must be equal to the interpreter's, except for what the configuration makes
+/// unobservable: the stack with `local_stack` or without `inspect_stack_length`, and gas without
+/// `gas_metering`.
+pub fn run_test_case_with_config<B: Backend>(
+    test_case: &TestCase<'_>,
+    config: &TestConfig,
+    compiler: &mut EvmCompiler<B>,
+) {
+    let TestCase { bytecode, spec_id, .. } = *test_case;
+    config.apply(compiler);
     // Done manually in `fn eof` and friends.
     compiler.validate_eof(false);
-    // compiler.debug_assertions(false);
     let f = unsafe { compiler.jit("test", bytecode, spec_id) }.unwrap();
-    run_compiled_test_case(test_case, f);
+    run_compiled_test_case(test_case, config, f);
 }
 
-fn run_compiled_test_case(test_case: &TestCase<'_>, f: EvmCompilerFn) {
+fn run_compiled_test_case(test_case: &TestCase<'_>, config: &TestConfig, f: EvmCompilerFn) {
     let TestCase {
         bytecode,
         spec_id,
@@ -469,6 +553,11 @@ fn run_compiled_test_case(test_case: &TestCase<'_>, f: EvmCompilerFn) {
             assert_host(&int_host);
         }
 
+        // Without gas metering, the compiled function gets further than the interpreter.
+        if !config.gas_metering && is_out_of_gas(expected_return) {
+            return;
+        }
+
         let actual_return = unsafe { f.call(Some(stack), Some(stack_len), ecx) };
 
         if matches!(
@@ -493,20 +582,39 @@ fn run_compiled_test_case(test_case: &TestCase<'_>, f: EvmCompilerFn) {
         // On EVM halt all available gas is consumed, so resulting stack, memory, and gas do not
         // matter. We do less work than the interpreter by bailing out earlier due to sections.
         if !actual_return.is_error() {
-            assert_eq!(actual_stack, *expected_stack, "stack mismatch");
+            // Without gas metering, the remaining gas can flow into the stack and memory.
+            let reads_gas = !config.gas_metering
+                && OpcodesIter::new(bytecode, spec_id).any(|op| op.opcode == op::GAS);
 
-            assert_eq!(
-                MemDisplay(ecx.memory.context_memory()),
-                MemDisplay(expected_memory),
-                "interpreter memory mismatch"
-            );
+            if config.is_stack_observable() && !reads_gas {
+                assert_eq!(actual_stack, *expected_stack, "stack mismatch");
+            }
 
-            assert_eq!(ecx.gas.spent(), expected_gas, "gas mismatch");
+            if !reads_gas {
+                assert_eq!(
+                    MemDisplay(ecx.memory.context_memory()),
+                    MemDisplay(expected_memory),
+                    "interpreter memory mismatch"
+                );
+            }
+
+            if config.gas_metering {
+                assert_eq!(ecx.gas.spent(), expected_gas, "gas mismatch");
+            }
         }
 
         let actual_next_action =
             if ecx.next_action.is_none() { &default_action } else { &*ecx.next_action };
-        assert_actions(actual_next_action, expected_next_action);
+        if config.gas_metering {
+            assert_actions(actual_next_action, expected_next_action);
+        } else {
+            // Gas limits of calls and results depend on the remaining gas.
+            assert_eq!(
+                std::mem::discriminant(actual_next_action),
+                std::mem::discriminant(expected_next_action),
+                "next action mismatch"
+            );
+        }
 
         if let Some(_assert_host) = assert_host {
             #[cfg(not(feature = "__fuzzing"))]
@@ -520,7 +628,7 @@ fn run_compiled_test_case(test_case: &TestCase<'_>, f: EvmCompilerFn) {
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
-struct MemDisplay<'a>(&'a [u8]);
+pub(super) struct MemDisplay<'a>(pub(super) &'a [u8]);
 impl fmt::Debug for MemDisplay<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let chunks = self.0.chunks(32).map(revm_primitives::hex::encode_prefixed);
@@ -528,8 +636,19 @@ impl fmt::Debug for MemDisplay<'_> {
     }
 }
 
+fn is_out_of_gas(result: InstructionResult) -> bool {
+    matches!(
+        result,
+        InstructionResult::OutOfGas
+            | InstructionResult::MemoryOOG
+            | InstructionResult::MemoryLimitOOG
+            | InstructionResult::PrecompileOOG
+            | InstructionResult::InvalidOperandOOG
+    )
+}
+
 #[track_caller]
-fn assert_actions(actual: &InterpreterAction, expected: &InterpreterAction) {
+pub(super) fn assert_actions(actual: &InterpreterAction, expected: &InterpreterAction) {
     match (actual, expected) {
         (
             InterpreterAction::Return { result },
@@ -0,0 +1,167 @@
+//! Runs the `VMTests` fixtures in `tests/VMTests`, in the format of ethereum/tests.
+//!
+//! Each test is run through [`run_test_case`] against the interpreter, and then in the environment
+//! of the fixture and checked against its expected gas, output, post-state and logs hash.
+
+use super::{run_test_case, FuzzAccount, FuzzHost, FuzzHostState, TestCase};
+use crate::{Backend, EvmCompiler};
+use revm_interpreter::{analysis::to_analysed, Contract, Interpreter, InterpreterAction};
+use revm_primitives::{hex, keccak256, Address, Bytecode, Bytes, Env, Log, SpecId, B256, U256};
+use serde_json::Value;
+use similar_asserts::assert_eq;
+use std::{
+    collections::BTreeMap,
+    fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// The fixtures target the gas schedule of Homestead.
+const SPEC_ID: SpecId = SpecId::HOMESTEAD;
+
+matrix_tests!(vm_tests = run_vm_tests);
+
+fn run_vm_tests<B: Backend>(compiler: &mut EvmCompiler<B>) {
+    let files = fixture_files();
+    assert!(!files.is_empty(), "no VMTests fixtures found");
+    for path in files {
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let tests: Value = serde_json::from_str(&contents).unwrap();
+        for (name, test) in tests.as_object().unwrap() {
+            let name = format!("{}/{name}", path.parent().unwrap().file_name().unwrap().display());
+            run_vm_test(&name, test, compiler);
+        }
+    }
+}
+
+fn fixture_files() -> Vec<PathBuf> {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../tests/VMTests");
+    let mut files = Vec::new();
+    for category in std::fs::read_dir(root).unwrap() {
+        for file in std::fs::read_dir(category.unwrap().path()).unwrap() {
+            let path = file.unwrap().path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+fn run_vm_test<B: Backend>(name: &str, test: &Value, compiler: &mut EvmCompiler<B>) {
+    let exec = &test["exec"];
+    let code = bytes(&exec["code"]);
+
+    // Compare with the interpreter.
+    run_test_case(&TestCase::what_interpreter_says(&code, SPEC_ID), compiler);
+    unsafe { compiler.clear() }.unwrap();
+
+    // Compare with the fixture.
+    let mut state = FuzzHostState::default();
+    for (address, account) in test["pre"].as_object().unwrap() {
+        state.accounts.insert(parse_str(address), account_state(account));
+    }
+    let mut host = FuzzHost::new(state);
+    host.env = env(test);
+    let contract = Contract {
+        input: bytes(&exec["data"]),
+        bytecode: to_analysed(Bytecode::new_raw(code.clone())),
+        hash: None,
+        bytecode_address: None,
+        target_address: parse(&exec["address"]),
+        caller: parse(&exec["caller"]),
+        call_value: parse(&exec["value"]),
+    };
+    let gas_limit = parse::<U256>(&exec["gas"]).to::<u64>();
+    let mut interpreter = Interpreter::new(contract, gas_limit, false);
+    let f = unsafe { compiler.jit("vm_test", &code, SPEC_ID) }.unwrap();
+    let action = unsafe { f.call_with_interpreter(&mut interpreter, &mut host) };
+    unsafe { compiler.clear() }.unwrap();
+    let InterpreterAction::Return { result } = action else {
+        panic!("{name}: unexpected action: {action:?}");
+    };
+
+    // Tests without a post-state are expected to halt exceptionally.
+    let Some(post) = test.get("post") else {
+        assert!(result.result.is_error(), "{name}: expected an error, got {:?}", result.result);
+        return;
+    };
+    assert!(result.result.is_ok(), "{name}: unexpected result {:?}", result.result);
+    assert_eq!(result.gas.remaining(), parse::<U256>(&test["gas"]).to::<u64>(), "{name}: gas");
+    assert_eq!(result.output, bytes(&test["out"]), "{name}: output");
+    let expected = post
+        .as_object()
+        .unwrap()
+        .iter()
+        .map(|(address, account)| (parse_str(address), account_state(account)));
+    assert_eq!(post_state(host.state.accounts), post_state(expected), "{name}: post-state");
+    assert_eq!(logs_hash(&host.logs), parse::<B256>(&test["logs"]), "{name}: logs hash");
+}
+
+/// Returns the environment of the fixture.
+fn env(test: &Value) -> Env {
+    let (env, exec) = (&test["env"], &test["exec"]);
+    let mut result = Env::default();
+    result.block.coinbase = parse(&env["currentCoinbase"]);
+    result.block.difficulty = parse(&env["currentDifficulty"]);
+    result.block.gas_limit = parse(&env["currentGasLimit"]);
+    result.block.number = parse(&env["currentNumber"]);
+    result.block.timestamp = parse(&env["currentTimestamp"]);
+    result.tx.caller = parse(&exec["origin"]);
+    result.tx.gas_price = parse(&exec["gasPrice"]);
+    result
+}
+
+fn account_state(account: &Value) -> FuzzAccount {
+    FuzzAccount {
+        balance: parse(&account["balance"]),
+        code: bytes(&account["code"]),
+        storage: account["storage"]
+            .as_object()
+            .unwrap()
+            .iter()
+            .map(|(k, v)| (parse_str(k), parse(v)))
+            .collect(),
+    }
+}
+
+/// Normalizes the accounts for comparison, removing zero storage slots and empty accounts.
+fn post_state(
+    accounts: impl IntoIterator<Item = (Address, FuzzAccount)>,
+) -> BTreeMap<Address, FuzzAccount> {
+    accounts
+        .into_iter()
+        .map(|(address, mut account)| {
+            account.storage.retain(|_, value| !value.is_zero());
+            (address, account)
+        })
+        .filter(|(_, account)| {
+            !account.balance.is_zero() || !account.code.is_empty() || !account.storage.is_empty()
+        })
+        .collect()
+}
+
+fn logs_hash(logs: &[Log]) -> B256 {
+    let mut out = Vec::new();
+    alloy_rlp::encode_list::<_, Log>(logs, &mut out);
+    keccak256(out)
+}
+
+fn parse<T: FromStr>(value: &Value) -> T
+where
+    T::Err: fmt::Debug,
+{
+    parse_str(value.as_str().unwrap())
+}
+
+fn parse_str<T: FromStr>(s: &str) -> T
+where
+    T::Err: fmt::Debug,
+{
+    s.parse().unwrap()
+}
+
+fn bytes(value: &Value) -> Bytes {
+    hex::decode(value.as_str().unwrap()).unwrap().into()
+}
@@ -0,0 +1,40 @@
+#![no_main]
+
+use libfuzzer_sys::{
+    arbitrary::{Arbitrary, Result, Unstructured},
+    fuzz_target,
+};
+use revmc::{
+    primitives::{Bytes, SpecId},
+    tests::{
+        run_test_case_with_config, GeneratedEofContainer, GeneratedProgram, TestCase, TestConfig,
+    },
+    EvmCompiler, EvmLlvmBackend, OptimizationLevel,
+};
+use std::path::PathBuf;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok((config, bytecode, spec_id)) = input(&mut Unstructured::new(data)) else { return };
+
+    let context = revmc::llvm::inkwell::context::Context::create();
+    let opt_level = config.opt_level.unwrap_or(OptimizationLevel::None);
+    let backend = EvmLlvmBackend::new(&context, false, opt_level).unwrap();
+    let mut compiler = EvmCompiler::new(backend);
+    if let Ok(dump_location) = std::env::var("COMPILER_DUMP") {
+        compiler.set_dump_to(Some(PathBuf::from(dump_location)));
+    }
+    let test_case = TestCase::what_interpreter_says(&bytecode, spec_id);
+    run_test_case_with_config(&test_case, &config, &mut compiler);
+});
+
+/// Generated programs have bounded loops, so they also terminate without gas metering.
+fn input(u: &mut Unstructured<'_>) -> Result<(TestConfig, Bytes, SpecId)> {
+    let config = TestConfig::arbitrary(u)?;
+    if u.arbitrary()? {
+        let container = GeneratedEofContainer::arbitrary(u)?;
+        Ok((config, container.bytecode, container.spec_id))
+    } else {
+        let program = GeneratedProgram::arbitrary(u)?;
+        Ok((config, program.bytecode.into(), program.spec_id))
+    }
+}
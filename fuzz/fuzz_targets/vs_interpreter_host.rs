@@ -0,0 +1,18 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use revmc::{
+    tests::{run_host_test_case, HostTestCase},
+    EvmCompiler, EvmLlvmBackend, OptimizationLevel,
+};
+use std::path::PathBuf;
+
+fuzz_target!(|test_case: HostTestCase| {
+    let context = revmc::llvm::inkwell::context::Context::create();
+    let backend = EvmLlvmBackend::new(&context, false, OptimizationLevel::None).unwrap();
+    let mut compiler = EvmCompiler::new(backend);
+    if let Ok(dump_location) = std::env::var("COMPILER_DUMP") {
+        compiler.set_dump_to(Some(PathBuf::from(dump_location)));
+    }
+    run_host_test_case(&test_case, &mut compiler);
+});
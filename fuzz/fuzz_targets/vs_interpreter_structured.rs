@@ -0,0 +1,19 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use revmc::{
+    tests::{run_test_case, GeneratedProgram, TestCase},
+    EvmCompiler, EvmLlvmBackend, OptimizationLevel,
+};
+use std::path::PathBuf;
+
+fuzz_target!(|program: GeneratedProgram| {
+    let context = revmc::llvm::inkwell::context::Context::create();
+    let backend = EvmLlvmBackend::new(&context, false, OptimizationLevel::None).unwrap();
+    let mut compiler = EvmCompiler::new(backend);
+    if let Ok(dump_location) = std::env::var("COMPILER_DUMP") {
+        compiler.set_dump_to(Some(PathBuf::from(dump_location)));
+    }
+    let test_case = TestCase::what_interpreter_says(&program.bytecode, program.spec_id);
+    run_test_case(&test_case, &mut compiler);
+});